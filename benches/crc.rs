@@ -0,0 +1,47 @@
+use std::hint::black_box;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use pngme::crc;
+
+// The implementation pngme used before the tables were precomputed, kept here
+// as a baseline.
+fn crc32_rebuilding_table(buf: &[u8]) -> u32 {
+    let mut crc32_table = [0; 256];
+    for n in 0..256 {
+        crc32_table[n as usize] = (0..8).fold(n as u32, |acc, _| match acc & 1 {
+            1 => 0xedb88320 ^ (acc >> 1),
+            _ => acc >> 1,
+        });
+    }
+
+    !buf.iter().fold(!0, |acc, octet| {
+        (acc >> 8) ^ crc32_table[((acc & 0xff) ^ *octet as u32) as usize]
+    })
+}
+
+fn bench_crc32(c: &mut Criterion) {
+    let mut group = c.benchmark_group("crc32");
+    for size in [64, 4 * 1024, 1024 * 1024] {
+        let data: Vec<u8> = (0..size).map(|i| (i * 31) as u8).collect();
+        group.throughput(Throughput::Bytes(size as u64));
+        group.bench_with_input(BenchmarkId::new("rebuilding_table", size), &data, |b, d| {
+            b.iter(|| crc32_rebuilding_table(black_box(d)))
+        });
+        group.bench_with_input(BenchmarkId::new("bytewise", size), &data, |b, d| {
+            b.iter(|| crc::update_bytewise(0, black_box(d)))
+        });
+        group.bench_with_input(BenchmarkId::new("slice_by_8", size), &data, |b, d| {
+            b.iter(|| crc::update_slice_by_8(0, black_box(d)))
+        });
+        group.bench_with_input(BenchmarkId::new("slice_by_16", size), &data, |b, d| {
+            b.iter(|| crc::update_slice_by_16(0, black_box(d)))
+        });
+        group.bench_with_input(BenchmarkId::new("crc32", size), &data, |b, d| {
+            b.iter(|| crc::crc32(black_box(d)))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_crc32);
+criterion_main!(benches);
@@ -0,0 +1,432 @@
+use std::{fmt::Display, ops::Range, str::FromStr};
+
+use crate::{chunk::Chunk, chunk_type::ChunkType, png::Png, util::read_u32};
+
+// Chunks that carry an APNG sequence number in their first four bytes.
+pub const SEQUENCE_CHUNKS: [&str; 2] = ["fcTL", "fdAT"];
+
+// The contents of the acTL chunk.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct AnimationControl {
+    pub num_frames: u32,
+    // 0 means the animation loops forever
+    pub num_plays: u32,
+}
+
+impl AnimationControl {
+    pub fn from_png(png: &Png) -> Result<AnimationControl, String> {
+        match png.chunk_by_type("acTL") {
+            Some(chunk) => AnimationControl::try_from(chunk.data()),
+            None => Err(String::from("not an animated PNG")),
+        }
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        [self.num_frames.to_be_bytes(), self.num_plays.to_be_bytes()].concat()
+    }
+}
+
+impl TryFrom<&[u8]> for AnimationControl {
+    type Error = String;
+
+    fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
+        if value.len() != 8 {
+            return Err(String::from("invalid acTL length"));
+        }
+        let num_frames = read_u32(value, 0);
+        if num_frames == 0 {
+            return Err(String::from("invalid number of frames"));
+        }
+        Ok(AnimationControl {
+            num_frames,
+            num_plays: read_u32(value, 4),
+        })
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum DisposeOp {
+    None = 0,
+    Background = 1,
+    Previous = 2,
+}
+
+impl TryFrom<u8> for DisposeOp {
+    type Error = String;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(DisposeOp::None),
+            1 => Ok(DisposeOp::Background),
+            2 => Ok(DisposeOp::Previous),
+            _ => Err(String::from("invalid dispose op")),
+        }
+    }
+}
+
+impl Display for DisposeOp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DisposeOp::None => write!(f, "none"),
+            DisposeOp::Background => write!(f, "background"),
+            DisposeOp::Previous => write!(f, "previous"),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum BlendOp {
+    Source = 0,
+    Over = 1,
+}
+
+impl TryFrom<u8> for BlendOp {
+    type Error = String;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(BlendOp::Source),
+            1 => Ok(BlendOp::Over),
+            _ => Err(String::from("invalid blend op")),
+        }
+    }
+}
+
+impl Display for BlendOp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BlendOp::Source => write!(f, "source"),
+            BlendOp::Over => write!(f, "over"),
+        }
+    }
+}
+
+// The contents of an fcTL chunk.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct FrameControl {
+    pub sequence_number: u32,
+    pub width: u32,
+    pub height: u32,
+    pub x_offset: u32,
+    pub y_offset: u32,
+    pub delay_num: u16,
+    pub delay_den: u16,
+    pub dispose_op: DisposeOp,
+    pub blend_op: BlendOp,
+}
+
+impl FrameControl {
+    // Delay in seconds, a denominator of 0 means hundredths of a second.
+    pub fn delay(&self) -> f64 {
+        let den = if self.delay_den == 0 {
+            100
+        } else {
+            self.delay_den
+        };
+        self.delay_num as f64 / den as f64
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        [
+            self.sequence_number.to_be_bytes().to_vec(),
+            self.width.to_be_bytes().to_vec(),
+            self.height.to_be_bytes().to_vec(),
+            self.x_offset.to_be_bytes().to_vec(),
+            self.y_offset.to_be_bytes().to_vec(),
+            self.delay_num.to_be_bytes().to_vec(),
+            self.delay_den.to_be_bytes().to_vec(),
+            vec![self.dispose_op as u8, self.blend_op as u8],
+        ]
+        .concat()
+    }
+}
+
+impl TryFrom<&[u8]> for FrameControl {
+    type Error = String;
+
+    fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
+        if value.len() != 26 {
+            return Err(String::from("invalid fcTL length"));
+        }
+        let width = read_u32(value, 4);
+        let height = read_u32(value, 8);
+        if width == 0 || height == 0 {
+            return Err(String::from("invalid frame size"));
+        }
+        Ok(FrameControl {
+            sequence_number: read_u32(value, 0),
+            width,
+            height,
+            x_offset: read_u32(value, 12),
+            y_offset: read_u32(value, 16),
+            delay_num: u16::from_be_bytes([value[20], value[21]]),
+            delay_den: u16::from_be_bytes([value[22], value[23]]),
+            dispose_op: DisposeOp::try_from(value[24])?,
+            blend_op: BlendOp::try_from(value[25])?,
+        })
+    }
+}
+
+// The contents of an fdAT chunk: a sequence number followed by image data,
+// which is laid out like the data of IDAT chunks.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct FrameData<'a> {
+    pub sequence_number: u32,
+    pub data: &'a [u8],
+}
+
+impl FrameData<'_> {
+    pub fn as_bytes(&self) -> Vec<u8> {
+        [&self.sequence_number.to_be_bytes()[..], self.data].concat()
+    }
+}
+
+impl<'a> TryFrom<&'a [u8]> for FrameData<'a> {
+    type Error = String;
+
+    fn try_from(value: &'a [u8]) -> Result<Self, Self::Error> {
+        if value.len() < 4 {
+            return Err(String::from("invalid fdAT length"));
+        }
+        Ok(FrameData {
+            sequence_number: read_u32(value, 0),
+            data: &value[4..],
+        })
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Frame {
+    pub control: FrameControl,
+    // Indices of the chunks that belong to this frame, from its fcTL up to the
+    // next fcTL or IEND.
+    pub chunks: Range<usize>,
+    // Whether the frame is the default image, stored in IDAT chunks.
+    pub is_default_image: bool,
+    pub data_chunks: usize,
+}
+
+// Lists the frames of an animated PNG in the order they are played.
+pub fn frames(png: &Png) -> Result<Vec<Frame>, String> {
+    AnimationControl::from_png(png)?;
+    let mut frames: Vec<Frame> = Vec::new();
+    for (i, chunk) in png.chunks().iter().enumerate() {
+        match chunk.chunk_type().to_string().as_str() {
+            "fcTL" => {
+                if let Some(previous) = frames.last_mut() {
+                    previous.chunks.end = i;
+                }
+                frames.push(Frame {
+                    control: FrameControl::try_from(chunk.data())?,
+                    chunks: i..i + 1,
+                    is_default_image: false,
+                    data_chunks: 0,
+                });
+            }
+            "IDAT" | "fdAT" => {
+                if let Some(frame) = frames.last_mut() {
+                    frame.is_default_image |= chunk.chunk_type().to_string() == "IDAT";
+                    frame.data_chunks += 1;
+                    frame.chunks.end = i + 1;
+                }
+            }
+            "IEND" => break,
+            _ => {
+                if let Some(frame) = frames.last_mut() {
+                    frame.chunks.end = i + 1;
+                }
+            }
+        }
+    }
+    Ok(frames)
+}
+
+// Checks that the fcTL and fdAT sequence numbers count up from 0 without gaps
+// and that acTL announces as many frames as there are fcTL chunks.
+pub fn validate(png: &Png) -> Result<(), String> {
+    let animation = AnimationControl::from_png(png)?;
+    let mut expected = 0;
+    let mut frame_count = 0;
+    for (i, chunk) in png.chunks().iter().enumerate() {
+        let chunk_type = chunk.chunk_type().to_string();
+        if !SEQUENCE_CHUNKS.contains(&chunk_type.as_str()) {
+            continue;
+        }
+        let sequence_number = FrameData::try_from(chunk.data())?.sequence_number;
+        if sequence_number != expected {
+            return Err(format!(
+                "invalid sequence number {} in {} chunk at index {}, expected {}",
+                sequence_number, chunk_type, i, expected
+            ));
+        }
+        expected += 1;
+        if chunk_type == "fcTL" {
+            frame_count += 1;
+        }
+    }
+    if frame_count != animation.num_frames {
+        return Err(format!(
+            "acTL announces {} frames but there are {}",
+            animation.num_frames, frame_count
+        ));
+    }
+    Ok(())
+}
+
+// Rewrites the sequence numbers of the fcTL and fdAT chunks in file order and
+// the number of frames in acTL, after chunks were inserted or removed. Returns
+// how many chunks were changed.
+pub fn renumber(chunks: &mut [Chunk]) -> usize {
+    let mut changed = 0;
+    let mut sequence_number = 0u32;
+    let mut frame_count = 0u32;
+    for chunk in chunks.iter_mut() {
+        let chunk_type = chunk.chunk_type().to_string();
+        if !SEQUENCE_CHUNKS.contains(&chunk_type.as_str()) || chunk.data().len() < 4 {
+            continue;
+        }
+        if chunk_type == "fcTL" {
+            frame_count += 1;
+        }
+        if read_u32(chunk.data(), 0) != sequence_number {
+            let mut data = chunk.data().to_vec();
+            data[..4].copy_from_slice(&sequence_number.to_be_bytes());
+            *chunk = Chunk::new(chunk.chunk_type().clone(), data);
+            changed += 1;
+        }
+        sequence_number += 1;
+    }
+
+    if let Some(chunk) = chunks
+        .iter_mut()
+        .find(|c| c.chunk_type().to_string() == "acTL")
+    {
+        if let Ok(mut animation) = AnimationControl::try_from(chunk.data()) {
+            if animation.num_frames != frame_count && frame_count > 0 {
+                animation.num_frames = frame_count;
+                *chunk = Chunk::new(
+                    ChunkType::from_str("acTL").expect("valid chunk type"),
+                    animation.as_bytes(),
+                );
+                changed += 1;
+            }
+        }
+    }
+    changed
+}
+
+pub fn is_sequence_chunk(chunk: &Chunk) -> bool {
+    SEQUENCE_CHUNKS.contains(&chunk.chunk_type().to_string().as_str())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::chunk;
+
+    fn frame_control(sequence_number: u32) -> FrameControl {
+        FrameControl {
+            sequence_number,
+            width: 4,
+            height: 3,
+            x_offset: 0,
+            y_offset: 0,
+            delay_num: 1,
+            delay_den: 10,
+            dispose_op: DisposeOp::None,
+            blend_op: BlendOp::Over,
+        }
+    }
+
+    fn frame_data(sequence_number: u32) -> Vec<u8> {
+        FrameData {
+            sequence_number,
+            data: b"data",
+        }
+        .as_bytes()
+    }
+
+    // IHDR, acTL, fcTL 0, IDAT, fcTL 1, fdAT 2, fdAT 3, fcTL 4, fdAT 5, IEND
+    fn testing_apng() -> Png {
+        let animation = AnimationControl {
+            num_frames: 3,
+            num_plays: 0,
+        };
+        Png::from_chunks(vec![
+            chunk("IHDR", vec![0; 13]),
+            chunk("acTL", animation.as_bytes()),
+            chunk("fcTL", frame_control(0).as_bytes()),
+            chunk("IDAT", b"data".to_vec()),
+            chunk("fcTL", frame_control(1).as_bytes()),
+            chunk("fdAT", frame_data(2)),
+            chunk("fdAT", frame_data(3)),
+            chunk("fcTL", frame_control(4).as_bytes()),
+            chunk("fdAT", frame_data(5)),
+            chunk("IEND", vec![]),
+        ])
+    }
+
+    #[test]
+    fn test_frame_control_round_trip() {
+        let control = frame_control(7);
+        assert_eq!(FrameControl::try_from(&control.as_bytes()[..]), Ok(control));
+        assert_eq!(control.delay(), 0.1);
+        assert!(FrameControl::try_from(&control.as_bytes()[..25]).is_err());
+    }
+
+    #[test]
+    fn test_frames() {
+        let frames = frames(&testing_apng()).unwrap();
+        assert_eq!(frames.len(), 3);
+        assert_eq!(frames[0].chunks, 2..4);
+        assert!(frames[0].is_default_image);
+        assert_eq!(frames[1].chunks, 4..7);
+        assert_eq!(frames[1].data_chunks, 2);
+        assert!(!frames[1].is_default_image);
+        assert_eq!(frames[2].chunks, 7..9);
+        assert_eq!(frames[2].control.sequence_number, 4);
+    }
+
+    #[test]
+    fn test_validate() {
+        let png = testing_apng();
+        assert!(validate(&png).is_ok());
+
+        let mut chunks = png.chunks().to_vec();
+        chunks.remove(5);
+        let png = Png::from_chunks(chunks);
+        assert_eq!(
+            validate(&png),
+            Err(String::from(
+                "invalid sequence number 3 in fdAT chunk at index 5, expected 2"
+            ))
+        );
+    }
+
+    #[test]
+    fn test_renumber() {
+        let mut chunks = testing_apng().chunks().to_vec();
+        // drop the second frame
+        chunks.drain(4..7);
+        assert_eq!(renumber(&mut chunks), 3);
+
+        let png = Png::from_chunks(chunks);
+        assert!(validate(&png).is_ok());
+        assert_eq!(AnimationControl::from_png(&png).unwrap().num_frames, 2);
+    }
+
+    #[test]
+    fn test_png_edits_renumber() {
+        let mut png = testing_apng();
+        png.remove_first_chunk("fdAT");
+        assert!(validate(&png).is_ok());
+
+        png.insert_chunk(
+            crate::png::ChunkPosition::Index(4),
+            chunk("fdAT", frame_data(0)),
+        )
+        .unwrap();
+        assert!(validate(&png).is_ok());
+        assert_eq!(frames(&png).unwrap()[0].data_chunks, 2);
+    }
+}
@@ -1,6 +1,37 @@
-use clap::{arg, ArgMatches, Command};
+use std::str::FromStr;
 
-use crate::commands::{decode, encode, print, remove};
+use clap::{
+    arg, builder::RangedU64ValueParser, value_parser, Arg, ArgAction, ArgGroup, ArgMatches, Command,
+};
+
+use pngme::{
+    chunk_type::ChunkType,
+    color::{Chromaticities, Cicp, Gamma, IccProfile, RenderingIntent},
+    compress::{Compression, DEFAULT_SIZE_LIMIT},
+    exif::Tag,
+    messages::{Selector, DEFAULT_CHUNK_TYPE},
+    metadata::{PhysicalDimensions, Timestamp},
+    naming::{lookup_type, TypeStrategy},
+    optimize::OptimizeOptions,
+    palette::PaletteFormat,
+    png::ChunkPosition,
+    Result,
+};
+
+#[cfg(feature = "json")]
+use crate::commands::{build, dump};
+use crate::{
+    batch::Batch,
+    commands::{
+        carve, color, decode, diff_files, encode, exif, extract, frames, inject, load_registry,
+        messages, optimize, palette, print, read_chunk, remove, repair, set_dpi, set_time,
+        ColorChanges, DecodeOptions, EncodeOptions, ExifChanges, MessagesAction, Outcome,
+        PaletteAction, RemoveFilter,
+    },
+    output::WriteOptions,
+};
+#[cfg(feature = "json")]
+use pngme::manifest::{DataEncoding, ManifestFormat};
 
 fn cli() -> Command {
     Command::new("pngme")
@@ -12,59 +43,692 @@ fn cli() -> Command {
             Command::new("encode")
                 .about("Encodes a message in a PNG file")
                 .arg(arg!(<PATH> "Path to a PNG file"))
-                .arg(arg!(<TYPE> "Chunk type"))
+                .arg(
+                    arg!(<TYPE> "Chunk type, or random, mimic or passphrase:<PASSPHRASE> to \
+                        generate one")
+                    .value_parser(TypeStrategy::from_str),
+                )
                 .arg(arg!(<MESSAGE> "Message that will be set"))
                 .arg(arg!(<OUTPUT> "Output PNG file").required(false))
+                .arg(frame_arg())
+                .arg(
+                    arg!(--ecc <PARITY> "Reed-Solomon parity bytes per 255 byte block, each \
+                        pair corrects one byte")
+                    .value_parser(RangedU64ValueParser::<usize>::new().range(1..=254)),
+                )
+                .arg(
+                    arg!(--compress [ALGORITHM] "Compresses the message: deflate, zstd or snappy, \
+                        also called lz4")
+                    .require_equals(true)
+                    .default_missing_value("deflate")
+                    .value_parser(Compression::from_str),
+                )
+                .arg(arg!(--raw "Writes the bare message, without the pngme container"))
+                .arg(force_arg())
+                .arg(registry_arg())
+                .args(write_args())
+                .args(batch_args())
                 .arg_required_else_help(true),
         )
         .subcommand(
             Command::new("decode")
                 .about("Decodes a message in a PNG file")
                 .arg(arg!(<PATH> "Path to a PNG file"))
-                .arg(arg!(<TYPE> "Chunk type"))
+                .arg(
+                    arg!(<TYPE> "Chunk type, or passphrase:<PASSPHRASE> for a type generated \
+                        from it")
+                    .value_parser(lookup_type),
+                )
+                .arg(frame_arg())
+                .arg(max_size_arg())
+                .args(batch_args())
                 .arg_required_else_help(true),
         )
         .subcommand(
             Command::new("remove")
                 .about("Removes a chunk type from a PNG file")
                 .arg(arg!(<PATH> "Path to a PNG file"))
-                .arg(arg!(<TYPE> "Chunk type"))
+                .arg(arg!([TYPE] "Chunk type"))
+                .arg(arg!(-a --all "Removes every matching chunk instead of the first one"))
+                .arg(
+                    arg!(-i --index <INDEX> "Removes only the matching chunk at this index, \
+                        starting at 0")
+                    .value_parser(value_parser!(usize))
+                    .conflicts_with("all"),
+                )
+                .arg(arg!(--private "Only matches private chunks"))
+                .arg(arg!(--ancillary "Only matches ancillary chunks"))
+                .arg(arg!(-f --force "Allows removing critical chunks"))
+                .group(
+                    ArgGroup::new("selection")
+                        .args(["TYPE", "private", "ancillary"])
+                        .required(true)
+                        .multiple(true),
+                )
+                .args(write_args())
+                .args(batch_args())
                 .arg_required_else_help(true),
         )
         .subcommand(
             Command::new("print")
                 .about("Prints message from a PNG file")
                 .arg(arg!(<PATH> "Path to a PNG file"))
+                .arg(registry_arg())
+                .args(batch_args())
+                .arg_required_else_help(true),
+        )
+        .subcommand(
+            Command::new("extract")
+                .about("Extracts a raw chunk from a PNG file")
+                .arg(arg!(<PATH> "Path to a PNG file"))
+                .arg(arg!(-t --type <TYPE> "Chunk type").required(true))
+                .arg(
+                    arg!(-i --index <INDEX> "Which chunk of that type to extract, starting at 0")
+                        .value_parser(value_parser!(usize))
+                        .default_value("0"),
+                )
+                .arg(arg!(-o --output <OUTPUT> "File the raw chunk is written to").required(true))
+                .args(batch_args())
+                .arg_required_else_help(true),
+        )
+        .subcommand(
+            Command::new("inject")
+                .about("Injects a raw chunk into a PNG file")
+                .arg(arg!(<PATH> "Path to a PNG file"))
+                .arg(arg!(<CHUNK> "Path to a raw chunk file"))
+                .arg(
+                    arg!(-p --position <POSITION> "Where to insert the chunk: start, end or a \
+                        chunk index")
+                    .value_parser(ChunkPosition::from_str)
+                    .default_value("end"),
+                )
+                .arg(arg!(-o --output <OUTPUT> "Output PNG file"))
+                .args(write_args())
+                .args(batch_args())
+                .arg_required_else_help(true),
+        )
+        .subcommand(
+            Command::new("optimize")
+                .about("Losslessly recompresses a PNG file, keeping every other chunk")
+                .arg(arg!(<PATH> "Path to a PNG file"))
+                .arg(arg!(-o --output <OUTPUT> "Output PNG file"))
+                .arg(
+                    arg!(-l --level <LEVEL> "Compression level to try, from 0 to 9")
+                        .action(ArgAction::Append)
+                        .value_parser(value_parser!(u32).range(0..=9)),
+                )
+                .arg(arg!(--"no-reduce" "Keeps the color type and bit depth"))
+                .args(write_args())
+                .args(batch_args())
+                .arg_required_else_help(true),
+        )
+        .subcommand(
+            Command::new("frames")
+                .about("Lists the frames of an animated PNG file")
+                .arg(arg!(<PATH> "Path to a PNG file"))
+                .args(batch_args())
+                .arg_required_else_help(true),
+        )
+        .subcommand(
+            Command::new("color")
+                .about("Shows or sets the color information of a PNG file")
+                .arg(arg!(<PATH> "Path to a PNG file"))
+                .arg(arg!(--gamma <GAMMA> "Sets gAMA, e.g. 0.45455").value_parser(Gamma::from_str))
+                .arg(
+                    arg!(--chromaticities <VALUES> "Sets cHRM from wx,wy,rx,ry,gx,gy,bx,by")
+                        .value_parser(Chromaticities::from_str),
+                )
+                .arg(
+                    arg!(--srgb <INTENT> "Sets sRGB: perceptual, relative, saturation or absolute")
+                        .value_parser(RenderingIntent::from_str)
+                        .conflicts_with("icc"),
+                )
+                .arg(arg!(--icc <FILE> "Sets iCCP from an ICC profile file"))
+                .arg(
+                    arg!(--"icc-name" <NAME> "Name of the ICC profile")
+                        .default_value("ICC profile")
+                        .requires("icc"),
+                )
+                .arg(
+                    arg!(--cicp <VALUES> "Sets cICP from primaries,transfer,matrix,full range")
+                        .value_parser(Cicp::from_str),
+                )
+                .arg(arg!(--clear "Removes every color chunk before setting new ones"))
+                .args(write_args())
+                .args(batch_args())
+                .arg_required_else_help(true),
+        )
+        .subcommand(
+            Command::new("set-time")
+                .about("Sets the last modification time of a PNG file")
+                .arg(arg!(<PATH> "Path to a PNG file"))
+                .arg(
+                    arg!([TIME] "UTC time such as 2024-05-01T12:30:00Z, now by default")
+                        .value_parser(Timestamp::from_str),
+                )
+                .args(write_args())
+                .args(batch_args())
+                .arg_required_else_help(true),
+        )
+        .subcommand(
+            Command::new("set-dpi")
+                .about("Sets the physical pixel density of a PNG file")
+                .arg(arg!(<PATH> "Path to a PNG file"))
+                .arg(
+                    arg!(<DPI> "Dots per inch, or horizontal and vertical such as 300x600")
+                        .value_parser(parse_dpi),
+                )
+                .args(write_args())
+                .args(batch_args())
+                .arg_required_else_help(true),
+        )
+        .subcommand(
+            Command::new("exif")
+                .about("Shows or removes EXIF tags of a PNG file")
+                .arg(arg!(<PATH> "Path to a PNG file"))
+                .arg(arg!(--"remove-gps" "Removes the GPS location"))
+                .arg(arg!(--"remove-thumbnail" "Removes the embedded thumbnail"))
+                .arg(
+                    arg!(--"remove-tag" <TAG> "Removes a tag by name, e.g. BodySerialNumber, or \
+                        number, prefixed by IFD0, Exif, Interop, GPS or IFD1 to pick one IFD, \
+                        e.g. GPS:0x0002")
+                    .action(ArgAction::Append)
+                    .value_parser(Tag::from_str),
+                )
+                .args(write_args())
+                .args(batch_args())
+                .arg_required_else_help(true),
+        )
+        .subcommand(
+            Command::new("palette")
+                .about("Shows, exports or imports the palette of a PNG file")
+                .arg(arg!(<PATH> "Path to a PNG file"))
+                .arg(arg!(--export <FILE> "Writes the palette to a file"))
+                .arg(
+                    arg!(--import <FILE> "Replaces the palette with the one in a file")
+                        .conflicts_with("export"),
+                )
+                .arg(
+                    arg!(--format <FORMAT> "Palette file format: gpl, pal or json, from the \
+                        extension by default")
+                    .value_parser(PaletteFormat::from_str),
+                )
+                .args(write_args())
+                .args(batch_args())
+                .arg_required_else_help(true),
+        )
+        .subcommand(
+            Command::new("messages")
+                .about("Lists, reads, adds or removes the pngme messages of a PNG file")
+                .subcommand_required(true)
+                .arg_required_else_help(true)
+                .subcommand(
+                    Command::new("ls")
+                        .about("Lists the messages with their ID, label, chunk type and size")
+                        .arg(arg!(<PATH> "Path to a PNG file"))
+                        .args(batch_args())
+                        .arg_required_else_help(true),
+                )
+                .subcommand(
+                    Command::new("get")
+                        .about("Prints one message")
+                        .arg(arg!(<PATH> "Path to a PNG file"))
+                        .arg(message_arg())
+                        .arg(max_size_arg())
+                        .args(batch_args())
+                        .arg_required_else_help(true),
+                )
+                .subcommand(
+                    Command::new("add")
+                        .about("Adds a message, or replaces one")
+                        .arg(arg!(<PATH> "Path to a PNG file"))
+                        .arg(arg!(<MESSAGE> "Message that will be added"))
+                        .arg(
+                            arg!(-l --label <LABEL> "Label of the message, unique within the \
+                            file and not a number"),
+                        )
+                        .arg(
+                            arg!(-t --type <TYPE> "Chunk type of a new message, or random, \
+                                mimic or passphrase:<PASSPHRASE>")
+                            .value_parser(TypeStrategy::from_str)
+                            .default_value(DEFAULT_CHUNK_TYPE),
+                        )
+                        .arg(
+                            arg!(--replace <MESSAGE> "ID or label of the message to replace")
+                                .value_parser(Selector::from_str),
+                        )
+                        .arg(force_arg())
+                        .arg(registry_arg())
+                        .args(write_args())
+                        .args(batch_args())
+                        .arg_required_else_help(true),
+                )
+                .subcommand(
+                    Command::new("rm")
+                        .about("Removes one message, leaving the others as they are")
+                        .arg(arg!(<PATH> "Path to a PNG file"))
+                        .arg(message_arg())
+                        .args(write_args())
+                        .args(batch_args())
+                        .arg_required_else_help(true),
+                ),
+        )
+        .subcommand(
+            Command::new("repair")
+                .about("Repairs bad crcs, damaged chunks and a missing IEND in a PNG file")
+                .arg(arg!(<PATH> "Path to a PNG file"))
+                .arg(arg!(-o --output <OUTPUT> "Output PNG file"))
+                .args(write_args())
+                .args(batch_args())
+                .arg_required_else_help(true),
+        )
+        .subcommand(
+            Command::new("carve")
+                .about("Finds PNG files and messages in raw data such as a disk image")
+                .arg(arg!(<BLOB> "Path to a file with raw data"))
+                .arg(
+                    arg!(-o --output <DIR> "Directory the complete PNG files are extracted to")
+                        .default_value("."),
+                )
+                .arg(arg!(-l --list "Only lists what was found, without extracting"))
+                .arg(registry_arg())
+                .after_help(
+                    "Takes a single file: the input is raw data rather than a PNG file, and \
+                    the PNG files found are named after their offset, which would clash \
+                    between inputs.",
+                )
+                .arg_required_else_help(true),
+        )
+        .subcommands(manifest_commands())
+        .subcommand(
+            Command::new("diff")
+                .about("Compares the chunks of a PNG file with those of other PNG files")
+                .arg(arg!(<FIRST> "Path to the PNG file the others are compared with"))
+                .arg(arg!(<PATH> "Path to another PNG file"))
+                .args(batch_args())
                 .arg_required_else_help(true),
         )
 }
 
-pub fn parse() {
+fn parse_dpi(s: &str) -> std::result::Result<PhysicalDimensions, String> {
+    let (x, y) = s.split_once('x').unwrap_or((s, s));
+    match (x.parse::<f64>(), y.parse::<f64>()) {
+        (Ok(x), Ok(y)) if x > 0.0 && y > 0.0 => Ok(PhysicalDimensions::from_dpi(x, y)),
+        _ => Err(String::from("invalid DPI")),
+    }
+}
+
+fn frame_arg() -> Arg {
+    arg!(--frame <FRAME> "Animation frame the message belongs to, starting at 0")
+        .value_parser(value_parser!(usize))
+}
+
+fn message_arg() -> Arg {
+    arg!(<ID> "ID or label of the message").value_parser(Selector::from_str)
+}
+
+fn max_size_arg() -> Arg {
+    arg!(--"max-size" <BYTES> "Largest compressed message that is decompressed, 16 MiB by default")
+        .value_parser(value_parser!(usize))
+}
+
+fn registry_arg() -> Arg {
+    arg!(--registry <FILE> "TOML file describing in-house chunk types")
+}
+
+fn force_arg() -> Arg {
+    arg!(-f --force "Allows critical chunk types and those with the reserved bit set")
+}
+
+// dump and build need JSON support for their manifests.
+#[cfg(feature = "json")]
+fn manifest_commands() -> Vec<Command> {
+    vec![
+        Command::new("dump")
+            .about("Writes the chunks of a PNG file to an editable manifest")
+            .arg(arg!(<PATH> "Path to a PNG file"))
+            .arg(
+                arg!(--format <FORMAT> "Manifest format: json or yaml")
+                    .value_parser(ManifestFormat::from_str)
+                    .default_value("json"),
+            )
+            .arg(
+                arg!(--encoding <ENCODING> "How binary chunk data is written: base64 or hex")
+                    .value_parser(DataEncoding::from_str)
+                    .default_value("base64"),
+            )
+            .arg(arg!(-o --output <OUTPUT> "File the manifest is written to"))
+            .after_help(
+                "Takes a single file: a manifest describes one PNG file, so several inputs \
+                would need as many outputs.",
+            )
+            .arg_required_else_help(true),
+        Command::new("build")
+            .about("Builds a PNG file from a manifest")
+            .arg(arg!(<MANIFEST> "Path to a manifest written by dump"))
+            .arg(arg!(<OUTPUT> "Output PNG file"))
+            .arg(
+                arg!(--format <FORMAT> "Manifest format: json or yaml, from the extension \
+                    by default")
+                .value_parser(ManifestFormat::from_str),
+            )
+            .after_help("Takes a single file: the input is a manifest rather than a PNG file.")
+            .arg_required_else_help(true),
+    ]
+}
+
+#[cfg(not(feature = "json"))]
+fn manifest_commands() -> Vec<Command> {
+    Vec::new()
+}
+
+fn write_args() -> [Arg; 3] {
+    [
+        arg!(--backup [SUFFIX] "Keeps the previous version of the file with this suffix")
+            .require_equals(true)
+            .default_missing_value(".bak"),
+        arg!(--"preserve-mtime" "Keeps the modification time of the original file"),
+        arg!(--"dry-run" "Shows the chunks that would change without writing anything"),
+    ]
+}
+
+fn write_options(sub_matches: &ArgMatches) -> WriteOptions {
+    WriteOptions {
+        backup: sub_matches.get_one::<String>("backup").cloned(),
+        preserve_mtime: sub_matches.get_flag("preserve-mtime"),
+        dry_run: sub_matches.get_flag("dry-run"),
+    }
+}
+
+fn decode_options(sub_matches: &ArgMatches) -> DecodeOptions {
+    DecodeOptions {
+        size_limit: sub_matches
+            .get_one::<usize>("max-size")
+            .copied()
+            .unwrap_or(DEFAULT_SIZE_LIMIT),
+    }
+}
+
+fn batch_args() -> [Arg; 3] {
+    [
+        arg!(-I --input <PATH> "Additional PNG file, directory or glob pattern")
+            .action(ArgAction::Append),
+        arg!(-r --recursive "Walks directories recursively"),
+        arg!(-j --jobs <JOBS> "Number of files processed in parallel")
+            .value_parser(value_parser!(usize)),
+    ]
+}
+
+fn batch(sub_matches: &ArgMatches) -> Batch {
+    let path = must_get_param(sub_matches, "PATH");
+    let inputs = sub_matches
+        .get_many::<String>("input")
+        .unwrap_or_default()
+        .cloned();
+    Batch {
+        inputs: std::iter::once(path.clone()).chain(inputs).collect(),
+        recursive: sub_matches.get_flag("recursive"),
+        jobs: sub_matches.get_one::<usize>("jobs").copied(),
+    }
+}
+
+fn single_output(batch: &Batch, output: Option<&String>) -> Result<()> {
+    if output.is_some() && batch.files()?.len() > 1 {
+        return Err("an output file can only be used with a single input file".into());
+    }
+    Ok(())
+}
+
+pub fn parse() -> Result<()> {
     let matches = cli().get_matches();
     match matches.subcommand() {
         Some(("encode", sub_matches)) => {
-            let path = must_get_param(sub_matches, "PATH");
-            let chunk_type = must_get_param(sub_matches, "TYPE");
+            let batch = batch(sub_matches);
+            let strategy = sub_matches
+                .get_one::<TypeStrategy>("TYPE")
+                .expect("required");
             let message = must_get_param(sub_matches, "MESSAGE");
             let output = sub_matches.get_one::<String>("OUTPUT");
-            encode(path, chunk_type, message, output);
+            let frame = sub_matches.get_one::<usize>("frame").copied();
+            let payload = EncodeOptions {
+                compression: sub_matches.get_one::<Compression>("compress").copied(),
+                ecc: sub_matches.get_one::<usize>("ecc").copied(),
+                raw: sub_matches.get_flag("raw"),
+                force: sub_matches.get_flag("force"),
+                registry: load_registry(sub_matches.get_one::<String>("registry"))?,
+            };
+            single_output(&batch, output)?;
+            let options = write_options(sub_matches);
+            batch.run(|path| {
+                encode(
+                    path,
+                    strategy,
+                    message,
+                    frame,
+                    &payload,
+                    output.map(String::as_str),
+                    &options,
+                )
+            })
         }
         Some(("decode", sub_matches)) => {
-            let path = must_get_param(sub_matches, "PATH");
-            let chunk_type = must_get_param(sub_matches, "TYPE");
-            decode(path, chunk_type);
+            let chunk_type = sub_matches
+                .get_one::<ChunkType>("TYPE")
+                .expect("required")
+                .to_string();
+            let frame = sub_matches.get_one::<usize>("frame").copied();
+            let payload = decode_options(sub_matches);
+            batch(sub_matches).run(|path| decode(path, &chunk_type, frame, &payload))
         }
         Some(("remove", sub_matches)) => {
-            let path = must_get_param(sub_matches, "PATH");
-            let chunk_type = must_get_param(sub_matches, "TYPE");
-            remove(path, chunk_type);
+            let chunk_type = sub_matches.get_one::<String>("TYPE");
+            let filter = RemoveFilter {
+                all: sub_matches.get_flag("all"),
+                index: sub_matches.get_one::<usize>("index").copied(),
+                private: sub_matches.get_flag("private"),
+                ancillary: sub_matches.get_flag("ancillary"),
+                force: sub_matches.get_flag("force"),
+            };
+            let options = write_options(sub_matches);
+            batch(sub_matches)
+                .run(|path| remove(path, chunk_type.map(String::as_str), &filter, &options))
         }
         Some(("print", sub_matches)) => {
+            let registry = load_registry(sub_matches.get_one::<String>("registry"))?;
+            batch(sub_matches).run(|path| print(path, &registry))
+        }
+        Some(("extract", sub_matches)) => {
+            let batch = batch(sub_matches);
+            let chunk_type = must_get_param(sub_matches, "type");
+            let index = *sub_matches.get_one::<usize>("index").expect("defaulted");
+            let output = must_get_param(sub_matches, "output");
+            single_output(&batch, Some(output))?;
+            batch.run(|path| extract(path, chunk_type, index, output))
+        }
+        Some(("inject", sub_matches)) => {
+            let batch = batch(sub_matches);
+            let chunk = read_chunk(must_get_param(sub_matches, "CHUNK"))?;
+            let position = *sub_matches
+                .get_one::<ChunkPosition>("position")
+                .expect("defaulted");
+            let output = sub_matches.get_one::<String>("output");
+            single_output(&batch, output)?;
+            let options = write_options(sub_matches);
+            batch.run(|path| inject(path, &chunk, position, output.map(String::as_str), &options))
+        }
+        Some(("optimize", sub_matches)) => {
+            let batch = batch(sub_matches);
+            let output = sub_matches.get_one::<String>("output");
+            single_output(&batch, output)?;
+            let mut optimize_options = OptimizeOptions {
+                reduce: !sub_matches.get_flag("no-reduce"),
+                ..OptimizeOptions::default()
+            };
+            if let Some(levels) = sub_matches.get_many::<u32>("level") {
+                optimize_options.levels = levels.copied().collect();
+            }
+            let options = write_options(sub_matches);
+            batch.run(|path| {
+                optimize(
+                    path,
+                    &optimize_options,
+                    output.map(String::as_str),
+                    &options,
+                )
+            })
+        }
+        Some(("frames", sub_matches)) => batch(sub_matches).run(frames),
+        Some(("color", sub_matches)) => {
+            let mut chunks = Vec::new();
+            if let Some(gamma) = sub_matches.get_one::<Gamma>("gamma") {
+                chunks.push(gamma.to_chunk());
+            }
+            if let Some(chromaticities) = sub_matches.get_one::<Chromaticities>("chromaticities") {
+                chunks.push(chromaticities.to_chunk());
+            }
+            if let Some(intent) = sub_matches.get_one::<RenderingIntent>("srgb") {
+                chunks.push(intent.to_chunk());
+            }
+            if let Some(icc) = sub_matches.get_one::<String>("icc") {
+                let name = must_get_param(sub_matches, "icc-name");
+                chunks.push(IccProfile::new(name, &std::fs::read(icc)?)?.to_chunk());
+            }
+            if let Some(cicp) = sub_matches.get_one::<Cicp>("cicp") {
+                chunks.push(cicp.to_chunk());
+            }
+            let changes = ColorChanges {
+                clear: sub_matches.get_flag("clear"),
+                chunks,
+            };
+            let options = write_options(sub_matches);
+            batch(sub_matches).run(|path| color(path, &changes, &options))
+        }
+        Some(("set-time", sub_matches)) => {
+            let time = sub_matches
+                .get_one::<Timestamp>("TIME")
+                .copied()
+                .unwrap_or_else(Timestamp::now);
+            let options = write_options(sub_matches);
+            batch(sub_matches).run(|path| set_time(path, time, &options))
+        }
+        Some(("set-dpi", sub_matches)) => {
+            let dimensions = sub_matches
+                .get_one::<PhysicalDimensions>("DPI")
+                .expect("required");
+            let options = write_options(sub_matches);
+            batch(sub_matches).run(|path| set_dpi(path, dimensions, &options))
+        }
+        Some(("exif", sub_matches)) => {
+            let changes = ExifChanges {
+                remove_gps: sub_matches.get_flag("remove-gps"),
+                remove_thumbnail: sub_matches.get_flag("remove-thumbnail"),
+                remove_tags: sub_matches
+                    .get_many::<Tag>("remove-tag")
+                    .unwrap_or_default()
+                    .copied()
+                    .collect(),
+            };
+            let options = write_options(sub_matches);
+            batch(sub_matches).run(|path| exif(path, &changes, &options))
+        }
+        Some(("palette", sub_matches)) => {
+            let batch = batch(sub_matches);
+            let format = sub_matches.get_one::<PaletteFormat>("format").copied();
+            let action = match (
+                sub_matches.get_one::<String>("export"),
+                sub_matches.get_one::<String>("import"),
+            ) {
+                (Some(path), _) => {
+                    single_output(&batch, Some(path))?;
+                    PaletteAction::Export(path.clone(), format)
+                }
+                (_, Some(path)) => PaletteAction::Import(path.clone(), format),
+                _ => PaletteAction::Show,
+            };
+            let options = write_options(sub_matches);
+            batch.run(|path| palette(path, &action, &options))
+        }
+        Some(("messages", sub_matches)) => {
+            let (name, sub_matches) = sub_matches.subcommand().expect("subcommand required");
+            let selector = || {
+                sub_matches
+                    .get_one::<Selector>("ID")
+                    .cloned()
+                    .expect("required")
+            };
+            let action = match name {
+                "get" => MessagesAction::Get(selector(), decode_options(sub_matches)),
+                "add" => MessagesAction::Add {
+                    message: must_get_param(sub_matches, "MESSAGE").clone(),
+                    label: sub_matches.get_one::<String>("label").cloned(),
+                    chunk_type: sub_matches
+                        .get_one::<TypeStrategy>("type")
+                        .cloned()
+                        .expect("defaulted"),
+                    replace: sub_matches.get_one::<Selector>("replace").cloned(),
+                    force: sub_matches.get_flag("force"),
+                    registry: load_registry(sub_matches.get_one::<String>("registry"))?,
+                },
+                "rm" => MessagesAction::Remove(selector()),
+                _ => MessagesAction::List,
+            };
+            let options = match name {
+                "add" | "rm" => write_options(sub_matches),
+                _ => WriteOptions::default(),
+            };
+            batch(sub_matches).run(|path| messages(path, &action, &options))
+        }
+        Some(("repair", sub_matches)) => {
+            let batch = batch(sub_matches);
+            let output = sub_matches.get_one::<String>("output");
+            single_output(&batch, output)?;
+            let options = write_options(sub_matches);
+            batch.run(|path| repair(path, output.map(String::as_str), &options))
+        }
+        Some(("carve", sub_matches)) => {
+            let blob = must_get_param(sub_matches, "BLOB");
+            let output = match sub_matches.get_flag("list") {
+                true => None,
+                false => Some(must_get_param(sub_matches, "output").as_str()),
+            };
+            let registry = load_registry(sub_matches.get_one::<String>("registry"))?;
+            let (Outcome::Done(report) | Outcome::Skipped(report)) =
+                carve(blob, output, &registry)?;
+            println!("{}", report);
+            Ok(())
+        }
+        #[cfg(feature = "json")]
+        Some(("dump", sub_matches)) => {
             let path = must_get_param(sub_matches, "PATH");
-            print(&path);
+            let format = *sub_matches
+                .get_one::<ManifestFormat>("format")
+                .expect("defaulted");
+            let encoding = *sub_matches
+                .get_one::<DataEncoding>("encoding")
+                .expect("defaulted");
+            let output = sub_matches.get_one::<String>("output");
+            let (Outcome::Done(report) | Outcome::Skipped(report)) =
+                dump(path, format, encoding, output.map(String::as_str))?;
+            println!("{}", report);
+            Ok(())
+        }
+        #[cfg(feature = "json")]
+        Some(("build", sub_matches)) => {
+            let manifest = must_get_param(sub_matches, "MANIFEST");
+            let output = must_get_param(sub_matches, "OUTPUT");
+            let format = sub_matches.get_one::<ManifestFormat>("format").copied();
+            let (Outcome::Done(report) | Outcome::Skipped(report)) =
+                build(manifest, format, output)?;
+            println!("{}", report);
+            Ok(())
+        }
+        Some(("diff", sub_matches)) => {
+            let first = must_get_param(sub_matches, "FIRST");
+            batch(sub_matches).run(|path| diff_files(first, path))
         }
         _ => {
-            println!("Invalid command. Use -h for help.")
+            println!("Invalid command. Use -h for help.");
+            Ok(())
         }
     }
 }
@@ -0,0 +1,185 @@
+use std::{
+    fs::{self, File},
+    io::{self, Read, Write},
+    path::Path,
+};
+
+use pngme::{png::Png, Result};
+use rayon::prelude::*;
+
+use crate::commands::Outcome;
+
+pub struct Batch {
+    pub inputs: Vec<String>,
+    pub recursive: bool,
+    pub jobs: Option<usize>,
+}
+
+struct Input {
+    path: String,
+    // files found by walking a directory or matching a glob are skipped
+    // instead of failing when they turn out not to be PNGs
+    discovered: bool,
+}
+
+enum Status {
+    Succeeded,
+    Failed,
+    Skipped,
+}
+
+impl Batch {
+    pub fn files(&self) -> Result<Vec<String>> {
+        Ok(self.expand()?.into_iter().map(|i| i.path).collect())
+    }
+
+    // Runs `command` once per input file across a thread pool, printing each
+    // file's report as it finishes. Errors are reported per file and only fail
+    // the batch as a whole at the end.
+    pub fn run<F>(&self, command: F) -> Result<()>
+    where
+        F: Fn(&str) -> Result<Outcome> + Sync,
+    {
+        let inputs = self.expand()?;
+        if inputs.is_empty() {
+            return Err("no input files found".into());
+        }
+        if let [input] = &inputs[..] {
+            if input.discovered && !has_png_header(&input.path) {
+                print_prefixed(&input.path, "not a PNG file, skipped", false);
+                return Ok(());
+            }
+            let (Outcome::Done(report) | Outcome::Skipped(report)) = command(&input.path)?;
+            print_report(&report);
+            return Ok(());
+        }
+
+        let mut pool = rayon::ThreadPoolBuilder::new();
+        if let Some(jobs) = self.jobs {
+            pool = pool.num_threads(jobs);
+        }
+        let statuses: Vec<Status> = pool.build()?.install(|| {
+            inputs
+                .par_iter()
+                .map(|input| run_one(input, &command))
+                .collect()
+        });
+
+        let count = |f: fn(&Status) -> bool| statuses.iter().filter(|s| f(s)).count();
+        let failed = count(|s| matches!(s, Status::Failed));
+        println!(
+            "{} succeeded, {} failed, {} skipped",
+            count(|s| matches!(s, Status::Succeeded)),
+            failed,
+            count(|s| matches!(s, Status::Skipped))
+        );
+        if failed > 0 {
+            return Err(format!("{} of {} files failed", failed, inputs.len()).into());
+        }
+        Ok(())
+    }
+
+    fn expand(&self) -> Result<Vec<Input>> {
+        let mut inputs = Vec::new();
+        for pattern in &self.inputs {
+            let path = Path::new(pattern);
+            if path.is_dir() {
+                walk_dir(path, self.recursive, &mut inputs)?;
+            } else if !path.exists() && pattern.contains(['*', '?', '[']) {
+                for entry in glob::glob(pattern)? {
+                    let entry = entry?;
+                    if entry.is_dir() {
+                        walk_dir(&entry, self.recursive, &mut inputs)?;
+                    } else {
+                        inputs.push(Input {
+                            path: entry.to_string_lossy().into_owned(),
+                            discovered: true,
+                        });
+                    }
+                }
+            } else {
+                inputs.push(Input {
+                    path: pattern.clone(),
+                    discovered: false,
+                });
+            }
+        }
+        Ok(inputs)
+    }
+}
+
+fn walk_dir(dir: &Path, recursive: bool, inputs: &mut Vec<Input>) -> Result<()> {
+    let mut entries: Vec<_> = fs::read_dir(dir)?
+        .map(|e| e.map(|e| e.path()))
+        .collect::<io::Result<_>>()?;
+    entries.sort();
+    for entry in entries {
+        if entry.is_dir() {
+            if recursive {
+                walk_dir(&entry, recursive, inputs)?;
+            }
+        } else if entry
+            .extension()
+            .is_some_and(|e| e.eq_ignore_ascii_case("png"))
+        {
+            inputs.push(Input {
+                path: entry.to_string_lossy().into_owned(),
+                discovered: true,
+            });
+        }
+    }
+    Ok(())
+}
+
+fn run_one<F>(input: &Input, command: &F) -> Status
+where
+    F: Fn(&str) -> Result<Outcome>,
+{
+    if input.discovered && !has_png_header(&input.path) {
+        print_prefixed(&input.path, "not a PNG file, skipped", false);
+        return Status::Skipped;
+    }
+    match command(&input.path) {
+        Ok(Outcome::Done(report)) => {
+            print_prefixed(&input.path, &report, false);
+            Status::Succeeded
+        }
+        Ok(Outcome::Skipped(report)) => {
+            print_prefixed(&input.path, &report, false);
+            Status::Skipped
+        }
+        Err(e) => {
+            print_prefixed(&input.path, &e.to_string(), true);
+            Status::Failed
+        }
+    }
+}
+
+fn has_png_header(path: &str) -> bool {
+    let mut header = [0; 8];
+    File::open(path)
+        .and_then(|mut f| f.read_exact(&mut header))
+        .is_ok_and(|_| header == Png::STANDARD_HEADER)
+}
+
+fn print_report(report: &str) {
+    let report = report.trim_end();
+    if !report.is_empty() {
+        println!("{}", report);
+    }
+}
+
+// Each file's report is written under a single lock so that output from
+// different threads is never interleaved.
+fn print_prefixed(path: &str, report: &str, error: bool) {
+    let lines: String = report
+        .trim_end()
+        .lines()
+        .map(|line| format!("{}: {}\n", path, line))
+        .collect();
+    if error {
+        let _ = io::stderr().lock().write_all(lines.as_bytes());
+    } else {
+        let _ = io::stdout().lock().write_all(lines.as_bytes());
+    }
+}
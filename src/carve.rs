@@ -0,0 +1,137 @@
+use crate::{chunk::ChunkRef, container::Container, ecc, png::Png, registry::Registry};
+
+// A PNG found in arbitrary data, as far as its chunks could be followed.
+#[derive(Debug, Clone)]
+pub struct CarvedPng<'a> {
+    pub offset: usize,
+    // from the signature to the end of the last valid chunk
+    pub bytes: &'a [u8],
+    pub chunks: Vec<ChunkRef<'a>>,
+}
+
+impl CarvedPng<'_> {
+    pub fn is_complete(&self) -> bool {
+        self.chunks.first().is_some_and(|c| is_type(c, "IHDR"))
+            && self.chunks.iter().any(|c| is_type(c, "IEND"))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CarvedMessage<'a> {
+    pub offset: usize,
+    pub chunk: ChunkRef<'a>,
+}
+
+// Every PNG signature in `data`, with the valid chunks that follow it. Chunks
+// appended after IEND are kept, that is where messages usually are.
+pub fn find_pngs(data: &[u8]) -> Vec<CarvedPng<'_>> {
+    let mut pngs = Vec::new();
+    let mut offset = 0;
+    while let Some(start) = find_signature(data, offset) {
+        let mut end = start + Png::STANDARD_HEADER.len();
+        let mut chunks = Vec::new();
+        while let Some(chunk) = valid_chunk_at(data, end) {
+            end += chunk.as_bytes().len();
+            chunks.push(chunk);
+        }
+        pngs.push(CarvedPng {
+            offset: start,
+            bytes: &data[start..end],
+            chunks,
+        });
+        offset = end;
+    }
+    pngs
+}
+
+// Chunks with a valid crc and text data anywhere in `data`, including pieces of
+// files whose signature or other chunks are gone. Chunks of a type registered in
+// `registry` are not messages.
+pub fn find_messages<'a>(data: &'a [u8], registry: &Registry) -> Vec<CarvedMessage<'a>> {
+    let mut messages = Vec::new();
+    let mut from = 0;
+    while let Some((offset, chunk)) = ChunkRef::find(data, from) {
+        from = offset + chunk.as_bytes().len();
+        if is_message(&chunk, registry) {
+            messages.push(CarvedMessage { offset, chunk });
+        }
+    }
+    messages
+}
+
+fn find_signature(data: &[u8], from: usize) -> Option<usize> {
+    data.get(from..)?
+        .windows(Png::STANDARD_HEADER.len())
+        .position(|w| w == Png::STANDARD_HEADER)
+        .map(|i| from + i)
+}
+
+fn valid_chunk_at(data: &[u8], offset: usize) -> Option<ChunkRef<'_>> {
+    let chunk = ChunkRef::parse_unchecked(data.get(offset..)?).ok()?;
+    chunk.has_valid_crc().then_some(chunk)
+}
+
+fn is_message(chunk: &ChunkRef, registry: &Registry) -> bool {
+    !registry.is_registered(chunk.chunk_type())
+        && !chunk.data().is_empty()
+        && (Container::is_container(chunk.data())
+            || ecc::parity(chunk.data()).is_some()
+            || chunk.data_as_str().is_ok())
+}
+
+fn is_type(chunk: &ChunkRef, chunk_type: &str) -> bool {
+    chunk.chunk_type().to_string() == chunk_type
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{self, chunk};
+
+    fn testing_png() -> Png {
+        let mut png = testing::testing_png(vec![]);
+        png.append_chunk(chunk("ruSt", b"hidden"));
+        png
+    }
+
+    #[test]
+    fn test_find_pngs() {
+        let png = testing_png().as_bytes();
+        let mut blob = vec![0xaa; 100];
+        blob.extend_from_slice(&png);
+        blob.extend_from_slice(&[0x55; 50]);
+        // a second file cut off in its IDAT
+        blob.extend_from_slice(&png[..50]);
+
+        let pngs = find_pngs(&blob);
+        assert_eq!(pngs.len(), 2);
+        assert_eq!(pngs[0].offset, 100);
+        assert_eq!(pngs[0].bytes, &png[..]);
+        assert_eq!(pngs[0].chunks.len(), 4);
+        assert!(pngs[0].is_complete());
+        assert_eq!(pngs[1].offset, 100 + png.len() + 50);
+        assert_eq!(pngs[1].chunks.len(), 1);
+        assert!(!pngs[1].is_complete());
+    }
+
+    #[test]
+    fn test_find_messages() {
+        let png = testing_png().as_bytes();
+        let message = chunk("heLo", b"world").as_bytes();
+        let mut blob = vec![0; 10];
+        blob.extend_from_slice(&message);
+        blob.extend_from_slice(&[1, 2, 3]);
+        // only the end of a file, without its signature
+        blob.extend_from_slice(&png[40..]);
+
+        let messages = find_messages(&blob, &Registry::builtin());
+        let found: Vec<(usize, &[u8])> = messages
+            .iter()
+            .map(|m| (m.offset, m.chunk.data()))
+            .collect();
+        assert_eq!(
+            found,
+            [(10, &b"world"[..]), (blob.len() - 18, &b"hidden"[..])]
+        );
+    }
+}
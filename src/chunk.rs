@@ -1,8 +1,6 @@
-use std::str::FromStr;
+use crate::{chunk_type::ChunkType, crc::Crc32, metadata};
 
-use crate::{chunk_type::ChunkType, crc};
-
-#[derive(PartialEq, Eq, Debug)]
+#[derive(PartialEq, Eq, Debug, Clone)]
 pub struct Chunk {
     length: u32,
     chunk_type: ChunkType,
@@ -12,16 +10,18 @@ pub struct Chunk {
 
 impl Chunk {
     pub fn new(chunk_type: ChunkType, data: Vec<u8>) -> Chunk {
-        let crc_data = [chunk_type.bytes().to_vec(), data.to_vec()].concat();
+        let mut crc = Crc32::new();
+        crc.update(&chunk_type.bytes());
+        crc.update(&data);
         Chunk {
             length: data.len() as u32,
             chunk_type,
             data,
-            crc: crate::crc::crc32(crc_data.as_slice()),
+            crc: crc.finalize(),
         }
     }
 
-    fn length(&self) -> u32 {
+    pub fn length(&self) -> u32 {
         self.length
     }
 
@@ -29,16 +29,24 @@ impl Chunk {
         &self.chunk_type
     }
 
-    fn data(&self) -> &[u8] {
+    pub fn data(&self) -> &[u8] {
         &self.data
     }
 
-    fn crc(&self) -> u32 {
+    pub fn crc(&self) -> u32 {
         self.crc
     }
 
+    // Only chunks read leniently from a damaged file can have a wrong crc.
+    pub fn has_valid_crc(&self) -> bool {
+        let mut crc = Crc32::new();
+        crc.update(&self.chunk_type.bytes());
+        crc.update(&self.data);
+        crc.finalize() == self.crc
+    }
+
     pub fn data_as_string(&self) -> Result<String, String> {
-        match std::str::from_utf8(&self.data.as_slice()) {
+        match std::str::from_utf8(self.data()) {
             Ok(string) => Ok(String::from(string)),
             Err(e) => {
                 eprintln!("{}", e);
@@ -51,7 +59,7 @@ impl Chunk {
         [
             u32::to_be_bytes(self.length()).to_vec(),
             self.chunk_type().bytes().to_vec(),
-            self.data.to_vec(),
+            self.data().to_vec(),
             u32::to_be_bytes(self.crc()).to_vec(),
         ]
         .concat()
@@ -62,47 +70,145 @@ impl TryFrom<&[u8]> for Chunk {
     type Error = String;
 
     fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
+        ChunkRef::try_from(value).map(|chunk| chunk.to_chunk())
+    }
+}
+
+// A chunk borrowed from the bytes it was parsed from, so that reading a PNG
+// does not copy every chunk's data. `to_chunk` gives an owned `Chunk`.
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct ChunkRef<'a> {
+    bytes: &'a [u8],
+    chunk_type: ChunkType,
+}
+
+impl<'a> ChunkRef<'a> {
+    // Longest chunk data `find` looks for. It tries every offset, so checking
+    // the crc of longer chunks everywhere would make it quadratic.
+    pub const MAX_FIND_LENGTH: u32 = 1 << 24;
+
+    // The first chunk with a valid crc at or after `from` in data that may be
+    // damaged or not a PNG at all, with its offset.
+    pub fn find(value: &'a [u8], from: usize) -> Option<(usize, ChunkRef<'a>)> {
+        (from..value.len()).find_map(|offset| {
+            let chunk = ChunkRef::parse_unchecked(&value[offset..]).ok()?;
+            let found = chunk.length() <= Self::MAX_FIND_LENGTH && chunk.has_valid_crc();
+            found.then_some((offset, chunk))
+        })
+    }
+
+    // Parses the chunk at the start of `value` without checking its crc, which
+    // would mean reading all of its data. `find` calls this at every offset, so
+    // failing allocates nothing.
+    pub fn parse_unchecked(value: &'a [u8]) -> Result<ChunkRef<'a>, &'static str> {
         if value.len() < 12 {
-            return Err(String::from("invalid value"));
+            return Err("invalid value");
         }
         let length: [u8; 4] = core::array::from_fn(|i| value[i]);
         let chunk_type: [u8; 4] = core::array::from_fn(|i| value[i + 4]);
-        let chunk_type = match ChunkType::try_from(chunk_type) {
-            Ok(chunk_type) => chunk_type,
-            Err(_) => return Err(String::from("invalid chunk type")),
-        };
+        if !chunk_type.iter().all(u8::is_ascii_alphabetic) {
+            return Err("invalid chunk type");
+        }
+        let chunk_type = ChunkType::try_from(chunk_type).expect("letters");
 
         let parsed_length = u32::from_be_bytes(length);
-
-        let data = &value[8..8 + (parsed_length as usize)];
-        let crc: [u8; 4] = core::array::from_fn(|i| value[i + 8 + (parsed_length as usize)]);
-        let crc = u32::from_be_bytes(crc);
-        let calc_crc = crate::crc::crc32(&value[4..8 + (parsed_length as usize)]);
-        if crc != calc_crc {
-            return Err(String::from("invalid crc"));
+        let chunk_end = 12 + (parsed_length as usize);
+        if value.len() < chunk_end {
+            return Err("truncated chunk");
         }
-        Ok(Chunk {
-            length: parsed_length,
+        Ok(ChunkRef {
+            bytes: &value[..chunk_end],
             chunk_type,
-            data: data.to_vec(),
-            crc,
         })
     }
+
+    pub fn length(&self) -> u32 {
+        (self.bytes.len() - 12) as u32
+    }
+
+    pub fn chunk_type(&self) -> &ChunkType {
+        &self.chunk_type
+    }
+
+    pub fn data(&self) -> &'a [u8] {
+        &self.bytes[8..self.bytes.len() - 4]
+    }
+
+    pub fn crc(&self) -> u32 {
+        let crc: [u8; 4] = core::array::from_fn(|i| self.bytes[self.bytes.len() - 4 + i]);
+        u32::from_be_bytes(crc)
+    }
+
+    pub fn has_valid_crc(&self) -> bool {
+        crate::crc::crc32(&self.bytes[4..self.bytes.len() - 4]) == self.crc()
+    }
+
+    pub fn data_as_str(&self) -> Result<&'a str, String> {
+        std::str::from_utf8(self.data())
+            .map_err(|_| String::from("could not convert data to string"))
+    }
+
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.bytes
+    }
+
+    pub fn to_chunk(&self) -> Chunk {
+        Chunk {
+            length: self.length(),
+            chunk_type: self.chunk_type.clone(),
+            data: self.data().to_vec(),
+            crc: self.crc(),
+        }
+    }
+}
+
+impl<'a> TryFrom<&'a [u8]> for ChunkRef<'a> {
+    type Error = String;
+
+    fn try_from(value: &'a [u8]) -> Result<Self, Self::Error> {
+        let chunk = ChunkRef::parse_unchecked(value)?;
+        if !chunk.has_valid_crc() {
+            return Err(String::from("invalid crc"));
+        }
+        Ok(chunk)
+    }
+}
+
+impl std::fmt::Display for ChunkRef<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "length: {}, type: {}, data: {}, crc: {}",
+            self.length(),
+            self.chunk_type,
+            display_data(&self.chunk_type, self.data()),
+            self.crc()
+        )
+    }
 }
 
 impl std::fmt::Display for Chunk {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
             f,
-            "length: {}, type: {}, data: {:?}, crc: {}",
+            "length: {}, type: {}, data: {}, crc: {}",
             self.length(),
-            self.chunk_type.to_string(),
-            std::str::from_utf8(&self.data).unwrap_or_else(|_| "non utf-8"),
+            self.chunk_type,
+            display_data(&self.chunk_type, &self.data),
             self.crc()
         )
     }
 }
 
+// Typed chunks are shown human readable, anything else as a string if it is
+// valid utf-8.
+fn display_data(chunk_type: &ChunkType, data: &[u8]) -> String {
+    match metadata::describe(&chunk_type.to_string(), data) {
+        Some(description) => description,
+        None => format!("{:?}", std::str::from_utf8(data).unwrap_or("non utf-8")),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -212,6 +318,38 @@ mod tests {
         assert!(chunk.is_err());
     }
 
+    #[test]
+    fn test_truncated_chunk_from_bytes() {
+        let chunk_bytes = testing_chunk().as_bytes();
+        let chunk = Chunk::try_from(&chunk_bytes[..chunk_bytes.len() - 1]);
+
+        assert!(chunk.is_err_and(|e| e.as_str() == "truncated chunk"));
+    }
+
+    #[test]
+    fn test_chunk_ref_borrows_data() {
+        let chunk_bytes = testing_chunk().as_bytes();
+        let chunk = ChunkRef::try_from(&chunk_bytes[..]).unwrap();
+
+        assert_eq!(chunk.length(), 42);
+        assert_eq!(chunk.crc(), 2882656334);
+        assert_eq!(chunk.data().as_ptr(), chunk_bytes[8..].as_ptr());
+        assert_eq!(chunk.as_bytes(), &chunk_bytes[..]);
+        assert_eq!(chunk.to_chunk(), testing_chunk());
+        assert_eq!(chunk.to_string(), testing_chunk().to_string());
+    }
+
+    #[test]
+    fn test_chunk_ref_unchecked_crc() {
+        let mut chunk_bytes = testing_chunk().as_bytes();
+        let last = chunk_bytes.len() - 1;
+        chunk_bytes[last] ^= 1;
+
+        let chunk = ChunkRef::parse_unchecked(&chunk_bytes).unwrap();
+        assert!(!chunk.has_valid_crc());
+        assert!(ChunkRef::try_from(&chunk_bytes[..]).is_err_and(|e| e.as_str() == "invalid crc"));
+    }
+
     #[test]
     pub fn test_chunk_trait_impls() {
         let data_length: u32 = 42;
@@ -234,4 +372,15 @@ mod tests {
 
         assert_eq!(chunk.to_string(), _chunk_string)
     }
+
+    #[test]
+    fn test_find_chunk() {
+        let chunk = testing_chunk().as_bytes();
+        let mut value = b"RuSt\xff\xff\xff\xffRuSt".to_vec();
+        value.extend_from_slice(&chunk);
+        let (offset, found) = ChunkRef::find(&value, 0).unwrap();
+        assert_eq!(offset, 12);
+        assert_eq!(found.as_bytes(), chunk);
+        assert!(ChunkRef::find(&value, 13).is_none());
+    }
 }
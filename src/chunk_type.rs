@@ -1,6 +1,6 @@
 use std::str::FromStr;
 
-#[derive(PartialEq, Eq, Debug)]
+#[derive(PartialEq, Eq, Debug, Clone)]
 pub struct ChunkType {
     bytes: [u8; 4],
 }
@@ -14,19 +14,19 @@ impl ChunkType {
         self.bytes().iter().all(|b| b.is_ascii_alphabetic()) && self.bytes().len() == 4
     }
 
-    fn is_critical(&self) -> bool {
+    pub fn is_critical(&self) -> bool {
         self.is_zero_bit_from_byte_at(5, 0)
     }
 
-    fn is_public(&self) -> bool {
+    pub fn is_public(&self) -> bool {
         self.is_zero_bit_from_byte_at(5, 1)
     }
 
-    fn is_reserved_bit_valid(&self) -> bool {
+    pub fn is_reserved_bit_valid(&self) -> bool {
         self.is_zero_bit_from_byte_at(5, 2)
     }
 
-    fn is_safe_to_copy(&self) -> bool {
+    pub fn is_safe_to_copy(&self) -> bool {
         !self.is_zero_bit_from_byte_at(5, 3)
     }
 
@@ -44,10 +44,19 @@ impl TryFrom<[u8; 4]> for ChunkType {
 
     fn try_from(value: [u8; 4]) -> Result<Self, Self::Error> {
         let new_chunk_type = ChunkType { bytes: value };
-        if !new_chunk_type.is_valid() {
-            return Err(String::from("invalid chunk type"));
+        if new_chunk_type.is_valid() {
+            return Ok(new_chunk_type);
         }
-        Ok(new_chunk_type)
+        let byte = value
+            .into_iter()
+            .find(|b| !b.is_ascii_alphabetic())
+            .expect("an invalid chunk type has a byte that is not a letter");
+        // bytes outside ASCII are not characters on their own
+        let invalid = match byte.is_ascii() {
+            true => format!("{:?}", char::from(byte)),
+            false => format!("byte {:#04x}", byte),
+        };
+        Err(letter_error(&value.escape_ascii().to_string(), &invalid))
     }
 }
 
@@ -55,27 +64,35 @@ impl FromStr for ChunkType {
     type Err = String;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        if s.len() != 4 {
-            return Err(String::from("invalid chunk type"));
-        }
-        let mut bytes: [u8; 4] = [0; 4];
-        for (i, c) in s.bytes().enumerate() {
-            bytes[i] = c
+        if let Some(c) = s.chars().find(|c| !c.is_ascii_alphabetic()) {
+            return Err(letter_error(s, &format!("{:?}", c)));
         }
-        let new_chunk_type = ChunkType { bytes };
-        if !new_chunk_type.is_valid() {
-            return Err(String::from("invalid chunk type"));
+        // only ASCII letters are left, one byte each
+        if s.len() != 4 {
+            return Err(format!(
+                "invalid chunk type {:?}, expected 4 letters but got {}",
+                s,
+                s.len()
+            ));
         }
-        Ok(ChunkType { bytes })
+        let bytes: [u8; 4] = core::array::from_fn(|i| s.as_bytes()[i]);
+        ChunkType::try_from(bytes)
     }
 }
 
+fn letter_error(chunk_type: &str, invalid: &str) -> String {
+    format!(
+        "invalid chunk type \"{}\", {} is not an ASCII letter",
+        chunk_type, invalid
+    )
+}
+
 impl std::fmt::Display for ChunkType {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
             f,
             "{}",
-            std::str::from_utf8(self.bytes().as_slice()).unwrap_or_else(|_| "non utf-8")
+            std::str::from_utf8(self.bytes().as_slice()).unwrap_or("non utf-8")
         )
     }
 }
@@ -165,6 +182,30 @@ mod tests {
         assert!(chunk.is_err());
     }
 
+    #[test]
+    pub fn test_invalid_chunk_type_errors() {
+        assert_eq!(
+            ChunkType::from_str("Rus").unwrap_err(),
+            "invalid chunk type \"Rus\", expected 4 letters but got 3"
+        );
+        assert_eq!(
+            ChunkType::from_str("Rüs").unwrap_err(),
+            "invalid chunk type \"Rüs\", 'ü' is not an ASCII letter"
+        );
+        assert_eq!(
+            ChunkType::try_from([82, 0xc3, 0xbc, 115]).unwrap_err(),
+            "invalid chunk type \"R\\xc3\\xbcs\", byte 0xc3 is not an ASCII letter"
+        );
+        assert_eq!(
+            ChunkType::from_str("Ru1t").unwrap_err(),
+            "invalid chunk type \"Ru1t\", '1' is not an ASCII letter"
+        );
+        assert_eq!(
+            ChunkType::try_from([82, 0, 83, 116]).unwrap_err(),
+            "invalid chunk type \"R\\x00St\", '\\0' is not an ASCII letter"
+        );
+    }
+
     #[test]
     pub fn test_chunk_type_string() {
         let chunk = ChunkType::from_str("RuSt").unwrap();
@@ -0,0 +1,543 @@
+use std::{fmt::Display, str::FromStr};
+
+use crate::{
+    chunk::Chunk,
+    image,
+    png::Png,
+    util::{chunk, read_u32},
+};
+
+pub const COLOR_CHUNKS: [&str; 5] = ["gAMA", "cHRM", "sRGB", "iCCP", "cICP"];
+
+// The largest ICC profile that is inflated.
+const MAX_PROFILE_SIZE: usize = 16 * 1024 * 1024;
+
+// gAMA and cHRM store values multiplied by 100000.
+const SCALE: f64 = 100000.0;
+
+// The gAMA and cHRM values that sRGB images are expected to carry.
+const SRGB_GAMMA: u32 = 45455;
+const SRGB_CHROMATICITIES: [u32; 8] = [31270, 32900, 64000, 33000, 30000, 60000, 15000, 6000];
+
+// The contents of the gAMA chunk.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Gamma {
+    pub gamma: u32,
+}
+
+impl Gamma {
+    pub fn value(&self) -> f64 {
+        self.gamma as f64 / SCALE
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        self.gamma.to_be_bytes().to_vec()
+    }
+
+    pub fn to_chunk(&self) -> Chunk {
+        chunk("gAMA", self.as_bytes())
+    }
+}
+
+impl TryFrom<&[u8]> for Gamma {
+    type Error = String;
+
+    fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
+        if value.len() != 4 {
+            return Err(String::from("invalid gAMA length"));
+        }
+        let gamma = read_u32(value, 0);
+        if gamma == 0 {
+            return Err(String::from("invalid gamma"));
+        }
+        Ok(Gamma { gamma })
+    }
+}
+
+impl FromStr for Gamma {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.parse::<f64>() {
+            Ok(value) if value > 0.0 && value * SCALE <= u32::MAX as f64 => Ok(Gamma {
+                gamma: (value * SCALE).round() as u32,
+            }),
+            _ => Err(String::from("invalid gamma")),
+        }
+    }
+}
+
+// The contents of the cHRM chunk: the CIE x and y of the white point and of
+// the red, green and blue primaries.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Chromaticities {
+    pub white: (u32, u32),
+    pub red: (u32, u32),
+    pub green: (u32, u32),
+    pub blue: (u32, u32),
+}
+
+impl Chromaticities {
+    fn values(&self) -> [u32; 8] {
+        [
+            self.white.0,
+            self.white.1,
+            self.red.0,
+            self.red.1,
+            self.green.0,
+            self.green.1,
+            self.blue.0,
+            self.blue.1,
+        ]
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        self.values().iter().flat_map(|v| v.to_be_bytes()).collect()
+    }
+
+    pub fn to_chunk(&self) -> Chunk {
+        chunk("cHRM", self.as_bytes())
+    }
+}
+
+impl From<[u32; 8]> for Chromaticities {
+    fn from(v: [u32; 8]) -> Self {
+        Chromaticities {
+            white: (v[0], v[1]),
+            red: (v[2], v[3]),
+            green: (v[4], v[5]),
+            blue: (v[6], v[7]),
+        }
+    }
+}
+
+impl TryFrom<&[u8]> for Chromaticities {
+    type Error = String;
+
+    fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
+        if value.len() != 32 {
+            return Err(String::from("invalid cHRM length"));
+        }
+        let values: [u32; 8] = core::array::from_fn(|i| read_u32(value, i * 4));
+        Ok(Chromaticities::from(values))
+    }
+}
+
+// Parses the eight values as decimals, "wx,wy,rx,ry,gx,gy,bx,by".
+impl FromStr for Chromaticities {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let values: Vec<u32> = s
+            .split(',')
+            .map(|v| match v.trim().parse::<f64>() {
+                Ok(v) if (0.0..=1.0).contains(&v) => Ok((v * SCALE).round() as u32),
+                _ => Err(String::from("invalid chromaticity")),
+            })
+            .collect::<Result<_, _>>()?;
+        let values: [u32; 8] = values
+            .try_into()
+            .map_err(|_| String::from("expected 8 chromaticity values"))?;
+        Ok(Chromaticities::from(values))
+    }
+}
+
+impl Display for Chromaticities {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let point =
+            |(x, y): (u32, u32)| format!("({:.5}, {:.5})", x as f64 / SCALE, y as f64 / SCALE);
+        write!(
+            f,
+            "white {}, red {}, green {}, blue {}",
+            point(self.white),
+            point(self.red),
+            point(self.green),
+            point(self.blue)
+        )
+    }
+}
+
+// The contents of the sRGB chunk.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum RenderingIntent {
+    Perceptual = 0,
+    RelativeColorimetric = 1,
+    Saturation = 2,
+    AbsoluteColorimetric = 3,
+}
+
+impl RenderingIntent {
+    pub fn to_chunk(&self) -> Chunk {
+        chunk("sRGB", vec![*self as u8])
+    }
+}
+
+impl TryFrom<&[u8]> for RenderingIntent {
+    type Error = String;
+
+    fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
+        match value {
+            [0] => Ok(RenderingIntent::Perceptual),
+            [1] => Ok(RenderingIntent::RelativeColorimetric),
+            [2] => Ok(RenderingIntent::Saturation),
+            [3] => Ok(RenderingIntent::AbsoluteColorimetric),
+            [_] => Err(String::from("invalid rendering intent")),
+            _ => Err(String::from("invalid sRGB length")),
+        }
+    }
+}
+
+impl FromStr for RenderingIntent {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "perceptual" => Ok(RenderingIntent::Perceptual),
+            "relative" => Ok(RenderingIntent::RelativeColorimetric),
+            "saturation" => Ok(RenderingIntent::Saturation),
+            "absolute" => Ok(RenderingIntent::AbsoluteColorimetric),
+            _ => Err(String::from("invalid rendering intent")),
+        }
+    }
+}
+
+impl Display for RenderingIntent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RenderingIntent::Perceptual => write!(f, "perceptual"),
+            RenderingIntent::RelativeColorimetric => write!(f, "relative colorimetric"),
+            RenderingIntent::Saturation => write!(f, "saturation"),
+            RenderingIntent::AbsoluteColorimetric => write!(f, "absolute colorimetric"),
+        }
+    }
+}
+
+// The contents of the iCCP chunk: a Latin-1 profile name and a zlib compressed
+// ICC profile.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct IccProfile {
+    pub name: String,
+    pub compressed_profile: Vec<u8>,
+}
+
+impl IccProfile {
+    pub fn new(name: &str, profile: &[u8]) -> Result<IccProfile, String> {
+        if !name.chars().all(|c| c != '\0' && (c as u32) < 256) {
+            return Err(String::from("invalid profile name"));
+        }
+        check_profile_name(name.chars().count())?;
+        Ok(IccProfile {
+            name: String::from(name),
+            compressed_profile: image::deflate(profile, 9),
+        })
+    }
+
+    pub fn profile(&self) -> Result<Vec<u8>, String> {
+        image::inflate(&self.compressed_profile, MAX_PROFILE_SIZE)
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let mut bytes: Vec<u8> = self.name.chars().map(|c| c as u8).collect();
+        // null separator and compression method 0
+        bytes.extend_from_slice(&[0, 0]);
+        bytes.extend_from_slice(&self.compressed_profile);
+        bytes
+    }
+
+    pub fn to_chunk(&self) -> Chunk {
+        chunk("iCCP", self.as_bytes())
+    }
+}
+
+impl TryFrom<&[u8]> for IccProfile {
+    type Error = String;
+
+    fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
+        let separator = value
+            .iter()
+            .position(|&b| b == 0)
+            .ok_or_else(|| String::from("missing profile name separator"))?;
+        check_profile_name(separator)?;
+        match value.get(separator + 1) {
+            Some(0) => {}
+            Some(_) => return Err(String::from("unsupported compression method")),
+            None => return Err(String::from("missing compression method")),
+        }
+        Ok(IccProfile {
+            // Latin-1 maps directly to the first 256 code points
+            name: value[..separator].iter().map(|&b| b as char).collect(),
+            compressed_profile: value[separator + 2..].to_vec(),
+        })
+    }
+}
+
+fn check_profile_name(length: usize) -> Result<(), String> {
+    if length == 0 || length > 79 {
+        return Err(String::from("invalid profile name length"));
+    }
+    Ok(())
+}
+
+// The contents of the cICP chunk, coding-independent code points as defined in
+// ITU-T H.273.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Cicp {
+    pub color_primaries: u8,
+    pub transfer_function: u8,
+    pub matrix_coefficients: u8,
+    pub full_range: bool,
+}
+
+impl Cicp {
+    pub fn as_bytes(&self) -> Vec<u8> {
+        vec![
+            self.color_primaries,
+            self.transfer_function,
+            self.matrix_coefficients,
+            self.full_range as u8,
+        ]
+    }
+
+    pub fn to_chunk(&self) -> Chunk {
+        chunk("cICP", self.as_bytes())
+    }
+}
+
+impl TryFrom<&[u8]> for Cicp {
+    type Error = String;
+
+    fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
+        let &[color_primaries, transfer_function, matrix_coefficients, full_range] = value else {
+            return Err(String::from("invalid cICP length"));
+        };
+        // PNG only stores RGB, so the matrix must be the identity
+        if matrix_coefficients != 0 {
+            return Err(String::from("invalid cICP matrix coefficients"));
+        }
+        if full_range > 1 {
+            return Err(String::from("invalid cICP range flag"));
+        }
+        Ok(Cicp {
+            color_primaries,
+            transfer_function,
+            matrix_coefficients,
+            full_range: full_range == 1,
+        })
+    }
+}
+
+// Parses "primaries,transfer,matrix,full range", e.g. "9,16,0,1".
+impl FromStr for Cicp {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let values: Vec<u8> = s
+            .split(',')
+            .map(|v| v.trim().parse::<u8>())
+            .collect::<Result<_, _>>()
+            .map_err(|_| String::from("invalid cICP value"))?;
+        Cicp::try_from(&values[..])
+    }
+}
+
+impl Display for Cicp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "primaries {}, transfer {}, matrix {}, {} range",
+            self.color_primaries,
+            self.transfer_function,
+            self.matrix_coefficients,
+            if self.full_range { "full" } else { "narrow" }
+        )
+    }
+}
+
+// The color information of a PNG, taken from the first chunk of each type.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct ColorInfo {
+    pub gamma: Option<Gamma>,
+    pub chromaticities: Option<Chromaticities>,
+    pub rendering_intent: Option<RenderingIntent>,
+    pub icc_profile: Option<IccProfile>,
+    pub cicp: Option<Cicp>,
+}
+
+impl ColorInfo {
+    pub fn from_png(png: &Png) -> Result<ColorInfo, String> {
+        fn parse<'a, T: TryFrom<&'a [u8], Error = String>>(
+            png: &'a Png,
+            chunk_type: &str,
+        ) -> Result<Option<T>, String> {
+            png.chunk_by_type(chunk_type)
+                .map(|c| T::try_from(c.data()).map_err(|e| format!("{}: {}", chunk_type, e)))
+                .transpose()
+        }
+        Ok(ColorInfo {
+            gamma: parse(png, "gAMA")?,
+            chromaticities: parse(png, "cHRM")?,
+            rendering_intent: parse(png, "sRGB")?,
+            icc_profile: parse(png, "iCCP")?,
+            cicp: parse(png, "cICP")?,
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == ColorInfo::default()
+    }
+}
+
+impl Display for ColorInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut lines = Vec::new();
+        if let Some(gamma) = &self.gamma {
+            lines.push(format!("gAMA: {:.5}", gamma.value()));
+        }
+        if let Some(chromaticities) = &self.chromaticities {
+            lines.push(format!("cHRM: {}", chromaticities));
+        }
+        if let Some(intent) = &self.rendering_intent {
+            lines.push(format!("sRGB: {}", intent));
+        }
+        if let Some(profile) = &self.icc_profile {
+            let size = match profile.profile() {
+                Ok(profile) => format!("{} bytes", profile.len()),
+                Err(e) => e,
+            };
+            lines.push(format!("iCCP: {:?}, {}", profile.name, size));
+        }
+        if let Some(cicp) = &self.cicp {
+            lines.push(format!("cICP: {}", cicp));
+        }
+        write!(f, "{}", lines.join("\n"))
+    }
+}
+
+// Lists the problems with the color chunks of `png`: duplicates, chunks after
+// PLTE or IDAT, and color spaces that contradict each other.
+pub fn validate(png: &Png) -> Result<Vec<String>, String> {
+    let mut problems = Vec::new();
+    let first_data = png
+        .chunks()
+        .iter()
+        .position(|c| matches!(c.chunk_type().to_string().as_str(), "PLTE" | "IDAT"));
+    for chunk_type in COLOR_CHUNKS {
+        let positions: Vec<usize> = png
+            .chunks()
+            .iter()
+            .enumerate()
+            .filter(|(_, c)| c.chunk_type().to_string() == chunk_type)
+            .map(|(i, _)| i)
+            .collect();
+        if positions.len() > 1 {
+            problems.push(format!("{} appears {} times", chunk_type, positions.len()));
+        }
+        if let (Some(&position), Some(first_data)) = (positions.first(), first_data) {
+            if position > first_data {
+                problems.push(format!("{} must come before PLTE and IDAT", chunk_type));
+            }
+        }
+    }
+
+    let info = ColorInfo::from_png(png)?;
+    if info.rendering_intent.is_some() && info.icc_profile.is_some() {
+        problems.push(String::from("sRGB and iCCP must not both be present"));
+    }
+    if info.rendering_intent.is_some() {
+        if info.gamma.is_some_and(|g| g.gamma != SRGB_GAMMA) {
+            problems.push(String::from("gAMA does not match sRGB"));
+        }
+        if info
+            .chromaticities
+            .is_some_and(|c| c != Chromaticities::from(SRGB_CHROMATICITIES))
+        {
+            problems.push(String::from("cHRM does not match sRGB"));
+        }
+    }
+    if let Some(profile) = &info.icc_profile {
+        if let Err(e) = profile.profile() {
+            problems.push(format!("iCCP: {}", e));
+        }
+    }
+    Ok(problems)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::testing_png;
+
+    #[test]
+    fn test_gamma() {
+        let gamma = Gamma::from_str("0.45455").unwrap();
+        assert_eq!(gamma.gamma, 45455);
+        assert_eq!(Gamma::try_from(&gamma.as_bytes()[..]), Ok(gamma));
+        assert!(Gamma::try_from(&[0, 0, 0, 0][..]).is_err());
+    }
+
+    #[test]
+    fn test_chromaticities_round_trip() {
+        let chromaticities =
+            Chromaticities::from_str("0.3127,0.329,0.64,0.33,0.3,0.6,0.15,0.06").unwrap();
+        assert_eq!(chromaticities, Chromaticities::from(SRGB_CHROMATICITIES));
+        assert_eq!(
+            Chromaticities::try_from(&chromaticities.as_bytes()[..]),
+            Ok(chromaticities)
+        );
+        assert!(Chromaticities::from_str("0.3,0.3").is_err());
+    }
+
+    #[test]
+    fn test_icc_profile() {
+        let profile = IccProfile::new("Display P3", b"not really a profile").unwrap();
+        let parsed = IccProfile::try_from(&profile.as_bytes()[..]).unwrap();
+        assert_eq!(parsed.name, "Display P3");
+        assert_eq!(parsed.profile().unwrap(), b"not really a profile");
+
+        assert!(IccProfile::new("", b"").is_err());
+        assert!(IccProfile::try_from(&b"name\0\x01data"[..]).is_err());
+    }
+
+    #[test]
+    fn test_cicp() {
+        let cicp = Cicp::from_str("9,16,0,1").unwrap();
+        assert!(cicp.full_range);
+        assert_eq!(Cicp::try_from(&cicp.as_bytes()[..]), Ok(cicp));
+        assert!(Cicp::from_str("9,16,1,1").is_err());
+        assert!(Cicp::from_str("9,16,0").is_err());
+    }
+
+    #[test]
+    fn test_color_info() {
+        let png = testing_png(vec![
+            RenderingIntent::Perceptual.to_chunk(),
+            Gamma { gamma: SRGB_GAMMA }.to_chunk(),
+        ]);
+        let info = ColorInfo::from_png(&png).unwrap();
+        assert_eq!(info.rendering_intent, Some(RenderingIntent::Perceptual));
+        assert_eq!(info.to_string(), "gAMA: 0.45455\nsRGB: perceptual");
+        assert!(validate(&png).unwrap().is_empty());
+        assert!(ColorInfo::from_png(&testing_png(vec![]))
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_validate_conflicts() {
+        let profile = IccProfile::new("profile", b"data").unwrap();
+        let mut png = testing_png(vec![
+            RenderingIntent::Perceptual.to_chunk(),
+            profile.to_chunk(),
+            Gamma { gamma: 100000 }.to_chunk(),
+        ]);
+        png.append_chunk(Gamma { gamma: 100000 }.to_chunk());
+        assert_eq!(
+            validate(&png).unwrap(),
+            vec![
+                "gAMA appears 2 times",
+                "sRGB and iCCP must not both be present",
+                "gAMA does not match sRGB",
+            ]
+        );
+    }
+}
@@ -1,59 +1,883 @@
-use std::{fs, str::FromStr};
+use std::{
+    borrow::Cow,
+    fmt::Write,
+    fs,
+    io::{stdout, ErrorKind, IsTerminal},
+    path::Path,
+    str::FromStr,
+};
 
-use crate::{chunk::Chunk, chunk_type::ChunkType, png::Png};
+#[cfg(feature = "json")]
+use pngme::manifest::{self, DataEncoding, ManifestFormat};
+use pngme::{
+    apng::{self, AnimationControl},
+    carve::{find_messages, find_pngs},
+    chunk::Chunk,
+    chunk_type::ChunkType,
+    color::{self, ColorInfo},
+    compress::{compress, decompress, Compression},
+    container::Container,
+    diff::diff,
+    ecc,
+    exif::{Exif, ExifEditor, Tag},
+    image::ImageHeader,
+    messages::{self, MessageIndex, Selector},
+    metadata::{PhysicalDimensions, Timestamp},
+    naming::TypeStrategy,
+    optimize::{optimize as optimize_png, OptimizeOptions},
+    palette::{self, Background, Histogram, PaletteFormat, SuggestedPalette},
+    png::{ChunkPosition, Png, PngRef},
+    policy::{self, Violation},
+    registry::Registry,
+    repair::{self, parse_lenient},
+    Result,
+};
 
-pub fn print(file_path: &String) -> () {
-    let data = read_file(file_path);
-    let png = Png::try_from(&data[..]);
-    match png {
-        Ok(file) => println!("{}", file.to_string()),
-        Err(e) => eprintln!("{}", e),
+use crate::{
+    input::map_file,
+    output::{write_file, write_new_file, WriteOptions},
+};
+
+pub enum Outcome {
+    Done(String),
+    Skipped(String),
+}
+
+pub fn print(file_path: &str, registry: &Registry) -> Result<Outcome> {
+    let data = map_file(file_path)?;
+    let png = PngRef::try_from(&data[..])?;
+    let mut report = String::new();
+    for (i, chunk) in png.chunks().iter().enumerate() {
+        let description = match registry.get(chunk.chunk_type()) {
+            Some(info) => info.to_string(),
+            None if Container::is_container(chunk.data()) => String::from("pngme message"),
+            None => String::from("unknown chunk"),
+        };
+        if i > 0 {
+            report.push('\n');
+        }
+        write!(report, "{} {} ({})", i, chunk, description)?;
+    }
+    let chunk_types: Vec<ChunkType> = png
+        .chunks()
+        .iter()
+        .map(|c| c.chunk_type().clone())
+        .collect();
+    for problem in registry.validate(&chunk_types) {
+        write!(report, "\nWarning: {}", problem)?;
     }
+    Ok(Outcome::Done(report))
+}
+
+// The built-in registry, extended with the chunk types of a TOML file.
+pub fn load_registry(path: Option<&String>) -> Result<Registry> {
+    let mut registry = Registry::builtin();
+    if let Some(path) = path {
+        registry
+            .extend_from_toml(&fs::read_to_string(path)?)
+            .map_err(|e| format!("{}: {}", path, e))?;
+    }
+    Ok(registry)
+}
+
+// How a message is turned into chunk data: compressed first, wrapped in a
+// container, then protected by error correction.
+pub struct EncodeOptions {
+    pub compression: Option<Compression>,
+    pub ecc: Option<usize>,
+    // the bare message, as written before the container existed
+    pub raw: bool,
+    // writes to chunk types that break the image as well
+    pub force: bool,
+    // the chunk types a new one is checked against and picked to avoid
+    pub registry: Registry,
+}
+
+// How chunk data is read back as a message. Compression, containers and error
+// correction are recognised by themselves.
+pub struct DecodeOptions {
+    // the largest message that is decompressed
+    pub size_limit: usize,
 }
 
 pub fn encode(
-    file_path: &String,
-    chunk_type: &String,
-    message: &String,
-    output: Option<&String>,
-) -> () {
-    let data = read_file(file_path);
-    let mut png = Png::try_from(&data[..]).expect("could not convert to png");
-    let chunk_type = ChunkType::from_str(&chunk_type).expect("could not create chunk type");
-    let chunk = Chunk::new(chunk_type, message.clone().into_bytes());
-    png.append_chunk(chunk);
-    match output {
-        Some(path) => fs::write(path, png.as_bytes()),
-        None => fs::write(file_path, png.as_bytes()),
+    file_path: &str,
+    strategy: &TypeStrategy,
+    message: &str,
+    frame: Option<usize>,
+    payload: &EncodeOptions,
+    output: Option<&str>,
+    options: &WriteOptions,
+) -> Result<Outcome> {
+    let original = read_png(file_path)?;
+    let mut png = original.clone();
+    let chunk_type = strategy.chunk_type(&png, &payload.registry)?;
+    let warnings = check_policy(&chunk_type, &payload.registry, payload.force)?;
+    let mut data = match payload.compression {
+        Some(compression) => compress(message.as_bytes(), compression)?,
+        None => message.as_bytes().to_vec(),
+    };
+    if !payload.raw {
+        data = Container {
+            compressed: payload.compression.is_some(),
+            message_id: Some(messages::store_ids(&mut png)?.next_id()?),
+            ..Container::new(data)
+        }
+        .as_bytes()?;
+    }
+    if let Some(parity) = payload.ecc {
+        data = ecc::encode(&data, parity)?;
+    }
+    let chunk = Chunk::new(chunk_type.clone(), data);
+    match frame {
+        // after the frame's data, before the next frame starts
+        Some(frame) => {
+            let end = frame_chunks(&png, frame)?.end;
+            png.insert_chunk(ChunkPosition::Index(end), chunk)?;
+        }
+        None => png.append_chunk(chunk),
+    }
+    let mut report = save(output.unwrap_or(file_path), &original, &png, options)?;
+    if !matches!(strategy, TypeStrategy::Given(_)) {
+        writeln!(report, "Encoded in chunk {}", chunk_type)?;
+    }
+    for warning in warnings {
+        writeln!(report, "Warning: {}", warning)?;
+    }
+    Ok(Outcome::Done(report.trim_end().to_string()))
+}
+
+// The policy violations that are only warnings, or the first error.
+fn check_policy(
+    chunk_type: &ChunkType,
+    registry: &Registry,
+    force: bool,
+) -> Result<Vec<Violation>> {
+    let violations = policy::check(chunk_type, registry);
+    match violations.iter().find(|v| v.is_error()) {
+        Some(error) if !force => {
+            Err(format!("refusing to encode, {}, use --force to override", error).into())
+        }
+        _ => Ok(violations),
     }
-    .expect("could not write file");
 }
 
-pub fn decode(file_path: &String, chunk_type: &String) -> () {
-    let data = read_file(file_path);
-    let png = Png::try_from(&data[..]).expect("could not convert to png");
+pub fn decode(
+    file_path: &str,
+    chunk_type: &str,
+    frame: Option<usize>,
+    payload: &DecodeOptions,
+) -> Result<Outcome> {
+    if let Some(frame) = frame {
+        let png = read_png(file_path)?;
+        let chunks = &png.chunks()[frame_chunks(&png, frame)?];
+        return match chunks
+            .iter()
+            .find(|c| c.chunk_type().to_string() == chunk_type)
+        {
+            Some(chunk) => message(chunk.data(), true, payload),
+            None => Ok(Outcome::Skipped(String::from("Chunk not found"))),
+        };
+    }
+    let data = map_file(file_path)?;
+    let png = match PngRef::scan(&data) {
+        Ok(png) => png,
+        // a damaged file, the message may still be intact
+        Err(_) => {
+            let (png, _) = parse_lenient(&data)?;
+            return match png.chunk_by_type(chunk_type) {
+                Some(chunk) => message(chunk.data(), chunk.has_valid_crc(), payload),
+                None => Ok(Outcome::Skipped(String::from("Chunk not found"))),
+            };
+        }
+    };
     match png.chunk_by_type(chunk_type) {
-        Some(chunk) => println!(
-            "Data: {}",
-            chunk.data_as_string().expect("non utf-8 message")
-        ),
-        None => println!("Chunk not found"),
+        Some(chunk) => message(chunk.data(), chunk.has_valid_crc(), payload),
+        None => Ok(Outcome::Skipped(String::from("Chunk not found"))),
+    }
+}
+
+// A payload with error correction is read even when its crc is wrong, that is
+// what the parity bytes are for. Data without a container is read as a bare
+// message.
+fn message(data: &[u8], valid_crc: bool, payload: &DecodeOptions) -> Result<Outcome> {
+    let (data, corrected) = match ecc::parity(data) {
+        Some(_) => {
+            let (data, corrected) = ecc::decode(data)?;
+            (Cow::Owned(data), Some(corrected))
+        }
+        None if !valid_crc => return Err("invalid crc".into()),
+        None => (Cow::Borrowed(data), None),
+    };
+    let container = Container::from_chunk_data(&data)?;
+    let (text, compression) = message_text(container, payload)?;
+    let mut report = format!("Data: {}", text);
+    if let Some(corrected) = corrected {
+        write!(report, "\nCorrected {} errors", corrected)?;
+    }
+    if let Some(compression) = compression {
+        write!(report, "\nDecompressed from {}", compression)?;
+    }
+    Ok(Outcome::Done(report))
+}
+
+// The text of a message, decompressed when the container says it is.
+fn message_text(
+    container: Container,
+    payload: &DecodeOptions,
+) -> Result<(String, Option<Compression>)> {
+    if container.encrypted {
+        return Err("encrypted messages are not supported".into());
+    }
+    let (data, compression) = match container.compressed {
+        true => decompress(&container.payload, payload.size_limit)?,
+        false => (container.payload, None),
+    };
+    let text = String::from_utf8(data).map_err(|_| "could not convert data to string")?;
+    Ok((text, compression))
+}
+
+pub struct RemoveFilter {
+    pub all: bool,
+    pub index: Option<usize>,
+    pub private: bool,
+    pub ancillary: bool,
+    pub force: bool,
+}
+
+pub fn remove(
+    file_path: &str,
+    chunk_type: Option<&str>,
+    filter: &RemoveFilter,
+    options: &WriteOptions,
+) -> Result<Outcome> {
+    let original = read_png(file_path)?;
+    let mut png = original.clone();
+    let matching = png.chunks().iter().enumerate().filter(|(_, c)| {
+        chunk_type.is_none_or(|t| c.chunk_type().to_string() == t)
+            && (!filter.private || !c.chunk_type().is_public())
+            && (!filter.ancillary || !c.chunk_type().is_critical())
+    });
+    // without a type the filters select every matching chunk
+    let selected: Vec<usize> = match filter.index {
+        Some(index) => matching.skip(index).take(1).map(|(i, _)| i).collect(),
+        None if filter.all || chunk_type.is_none() => matching.map(|(i, _)| i).collect(),
+        None => matching.take(1).map(|(i, _)| i).collect(),
+    };
+    if selected.is_empty() {
+        return Ok(Outcome::Skipped(String::from("Chunk not found")));
+    }
+    if !filter.force {
+        if let Some(&i) = selected
+            .iter()
+            .find(|&&i| png.chunks()[i].chunk_type().is_critical())
+        {
+            return Err(format!(
+                "refusing to remove critical chunk {}, use --force to override",
+                png.chunks()[i].chunk_type()
+            )
+            .into());
+        }
     }
+
+    let mut position = 0;
+    let removed = png.remove_chunks_where(|_| {
+        position += 1;
+        selected.contains(&(position - 1))
+    });
+    let mut report = save(file_path, &original, &png, options)?;
+    let verb = if options.dry_run {
+        "Would remove"
+    } else {
+        "Removed"
+    };
+    for chunk in &removed {
+        writeln!(report, "{} chunk {}", verb, chunk.chunk_type())?;
+    }
+    if removed.len() > 1 {
+        writeln!(report, "{} {} chunks", verb, removed.len())?;
+    }
+    Ok(Outcome::Done(report))
 }
 
-pub fn remove(file_path: &String, chunk_type: &String) -> () {
-    let data = read_file(file_path);
-    let mut png = Png::try_from(&data[..]).expect("could not convert to png");
-    match png.remove_first_chunk(chunk_type) {
+pub fn extract(file_path: &str, chunk_type: &str, index: usize, output: &str) -> Result<Outcome> {
+    let data = map_file(file_path)?;
+    let png = PngRef::scan(&data)?;
+    match png.chunk_by_type_at(chunk_type, index) {
+        Some(chunk) if !chunk.has_valid_crc() => Err("invalid crc".into()),
         Some(chunk) => {
-            fs::write(file_path, png.as_bytes()).expect("could not write file");
-            println!("Removed chunk {}", chunk.chunk_type());
+            write_file(output, chunk.as_bytes(), &WriteOptions::default())?;
+            Ok(Outcome::Done(format!(
+                "Extracted chunk {} to {}",
+                chunk.chunk_type(),
+                output
+            )))
+        }
+        None => Ok(Outcome::Skipped(String::from("Chunk not found"))),
+    }
+}
+
+pub fn inject(
+    file_path: &str,
+    chunk: &Chunk,
+    position: ChunkPosition,
+    output: Option<&str>,
+    options: &WriteOptions,
+) -> Result<Outcome> {
+    let original = read_png(file_path)?;
+    let mut png = original.clone();
+    let index = png.insert_chunk(position, chunk.clone())?;
+    let mut report = save(output.unwrap_or(file_path), &original, &png, options)?;
+    write!(
+        report,
+        "Injected chunk {} at index {}",
+        chunk.chunk_type(),
+        index
+    )?;
+    Ok(Outcome::Done(report))
+}
+
+pub fn optimize(
+    file_path: &str,
+    optimize_options: &OptimizeOptions,
+    output: Option<&str>,
+    options: &WriteOptions,
+) -> Result<Outcome> {
+    let original = read_png(file_path)?;
+    let png = optimize_png(&original, optimize_options)?;
+    let before = original.as_bytes().len();
+    let after = png.as_bytes().len();
+    if after == before && output.is_none() {
+        return Ok(Outcome::Skipped(format!(
+            "Already optimal at {} bytes",
+            before
+        )));
+    }
+    let mut report = save(output.unwrap_or(file_path), &original, &png, options)?;
+    write!(
+        report,
+        "{} -> {} bytes ({:.1}% smaller)",
+        before,
+        after,
+        (before - after) as f64 * 100.0 / before as f64
+    )?;
+    Ok(Outcome::Done(report))
+}
+
+pub fn frames(file_path: &str) -> Result<Outcome> {
+    let png = read_png(file_path)?;
+    let animation = AnimationControl::from_png(&png)?;
+    let mut report = format!(
+        "{} frames, {}",
+        animation.num_frames,
+        match animation.num_plays {
+            0 => String::from("looping forever"),
+            plays => format!("played {} times", plays),
         }
-        None => println!("Chunk not found"),
+    );
+    for (i, frame) in apng::frames(&png)?.iter().enumerate() {
+        let control = &frame.control;
+        write!(
+            report,
+            "\nFrame {}: {}x{} at ({}, {}), delay {:.3}s, dispose {}, blend {}, {} {} chunks",
+            i,
+            control.width,
+            control.height,
+            control.x_offset,
+            control.y_offset,
+            control.delay(),
+            control.dispose_op,
+            control.blend_op,
+            frame.data_chunks,
+            if frame.is_default_image {
+                "IDAT"
+            } else {
+                "fdAT"
+            }
+        )?;
+    }
+    if let Err(e) = apng::validate(&png) {
+        write!(report, "\nWarning: {}", e)?;
+    }
+    Ok(Outcome::Done(report))
+}
+
+pub struct ColorChanges {
+    pub clear: bool,
+    pub chunks: Vec<Chunk>,
+}
+
+pub fn color(file_path: &str, changes: &ColorChanges, options: &WriteOptions) -> Result<Outcome> {
+    let original = read_png(file_path)?;
+    if !changes.clear && changes.chunks.is_empty() {
+        let info = ColorInfo::from_png(&original)?;
+        let mut report = match info.is_empty() {
+            true => String::from("No color information"),
+            false => info.to_string(),
+        };
+        for problem in color::validate(&original)? {
+            write!(report, "\nWarning: {}", problem)?;
+        }
+        return Ok(Outcome::Done(report));
+    }
+
+    let mut png = original.clone();
+    let mut removed = Vec::new();
+    if changes.clear {
+        removed.extend(png.remove_chunks_where(|c| {
+            color::COLOR_CHUNKS.contains(&c.chunk_type().to_string().as_str())
+        }));
+    }
+    for chunk in &changes.chunks {
+        let chunk_type = chunk.chunk_type().to_string();
+        // sRGB and iCCP are alternatives, setting one replaces the other
+        let replaced = match chunk_type.as_str() {
+            "sRGB" => "iCCP",
+            "iCCP" => "sRGB",
+            _ => chunk_type.as_str(),
+        };
+        removed.extend(png.remove_chunks_where(|c| {
+            let t = c.chunk_type().to_string();
+            t == chunk_type || t == replaced
+        }));
+        png.insert_chunk(ChunkPosition::Start, chunk.clone())?;
+    }
+    let mut report = save(file_path, &original, &png, options)?;
+    // replaced chunks show up in the color information below
+    for chunk in removed.iter().filter(|r| {
+        !changes
+            .chunks
+            .iter()
+            .any(|c| c.chunk_type() == r.chunk_type())
+    }) {
+        writeln!(report, "Removed chunk {}", chunk.chunk_type())?;
+    }
+    let info = ColorInfo::from_png(&png)?;
+    match info.is_empty() {
+        true => write!(report, "No color information")?,
+        false => write!(report, "{}", info)?,
+    }
+    Ok(Outcome::Done(report))
+}
+
+pub fn set_time(file_path: &str, time: Timestamp, options: &WriteOptions) -> Result<Outcome> {
+    let original = read_png(file_path)?;
+    let mut png = original.clone();
+    // tIME may appear anywhere, keep it with the other trailing chunks
+    set_chunk(&mut png, time.to_chunk(), ChunkPosition::End)?;
+    let mut report = save(file_path, &original, &png, options)?;
+    write!(report, "Set tIME to {}", time)?;
+    Ok(Outcome::Done(report))
+}
+
+pub fn set_dpi(
+    file_path: &str,
+    dimensions: &PhysicalDimensions,
+    options: &WriteOptions,
+) -> Result<Outcome> {
+    let original = read_png(file_path)?;
+    let mut png = original.clone();
+    // pHYs must come before the first IDAT
+    set_chunk(&mut png, dimensions.to_chunk(), ChunkPosition::Start)?;
+    let mut report = save(file_path, &original, &png, options)?;
+    write!(report, "Set pHYs to {}", dimensions)?;
+    Ok(Outcome::Done(report))
+}
+
+pub struct ExifChanges {
+    pub remove_gps: bool,
+    pub remove_thumbnail: bool,
+    pub remove_tags: Vec<Tag>,
+}
+
+pub fn exif(file_path: &str, changes: &ExifChanges, options: &WriteOptions) -> Result<Outcome> {
+    let original = read_png(file_path)?;
+    let Some(index) = original
+        .chunks()
+        .iter()
+        .position(|c| c.chunk_type().to_string() == "eXIf")
+    else {
+        return Ok(Outcome::Skipped(String::from("No EXIF data")));
+    };
+    let data = original.chunks()[index].data();
+    if !changes.remove_gps && !changes.remove_thumbnail && changes.remove_tags.is_empty() {
+        return Ok(Outcome::Done(Exif::try_from(data)?.to_string()));
+    }
+
+    let mut exif = ExifEditor::new(data)?;
+
+    let mut removed = Vec::new();
+    if changes.remove_gps && exif.remove_gps() {
+        removed.push(String::from("GPS data"));
+    }
+    if changes.remove_thumbnail && exif.remove_thumbnail() {
+        removed.push(String::from("thumbnail"));
+    }
+    for tag in &changes.remove_tags {
+        match exif.remove_tag(tag) {
+            0 => {}
+            1 => removed.push(format!("tag {}", tag)),
+            n => removed.push(format!("{} tags {}", n, tag)),
+        }
+    }
+    if removed.is_empty() {
+        return Ok(Outcome::Skipped(String::from("Nothing to remove")));
+    }
+
+    let mut png = original.clone();
+    png.remove_first_chunk("eXIf");
+    png.insert_chunk(ChunkPosition::Index(index), exif.to_chunk())?;
+    let mut report = save(file_path, &original, &png, options)?;
+    write!(report, "Removed {}", removed.join(", "))?;
+    Ok(Outcome::Done(report))
+}
+
+pub enum PaletteAction {
+    Show,
+    Export(String, Option<PaletteFormat>),
+    Import(String, Option<PaletteFormat>),
+}
+
+pub fn palette(file_path: &str, action: &PaletteAction, options: &WriteOptions) -> Result<Outcome> {
+    let original = read_png(file_path)?;
+    match action {
+        PaletteAction::Show => show_palette(&original),
+        PaletteAction::Export(path, format) => {
+            let colors = palette::colors(&original)?;
+            let name = Path::new(file_path)
+                .file_stem()
+                .map_or(String::new(), |s| s.to_string_lossy().into_owned());
+            let format = palette_format(path, *format)?;
+            let text = palette::export(&colors, format, &name);
+            write_file(path, text.as_bytes(), &WriteOptions::default())?;
+            Ok(Outcome::Done(format!(
+                "Exported {} colors to {}",
+                colors.len(),
+                path
+            )))
+        }
+        PaletteAction::Import(path, format) => {
+            let format = palette_format(path, *format)?;
+            let mut colors = palette::import(&fs::read_to_string(path)?, format)?;
+            // only JSON carries alpha, keep the current transparency otherwise
+            if !format.has_alpha() {
+                let current = palette::colors(&original).unwrap_or_default();
+                for (color, old) in colors.iter_mut().zip(current) {
+                    color[3] = old[3];
+                }
+            }
+            let mut png = original.clone();
+            let removed = palette::set_colors(&mut png, &colors)?;
+            let mut report = save(file_path, &original, &png, options)?;
+            for chunk in &removed {
+                writeln!(report, "Removed chunk {}", chunk.chunk_type())?;
+            }
+            write!(report, "Imported {} colors", colors.len())?;
+            Ok(Outcome::Done(report))
+        }
+    }
+}
+
+fn show_palette(png: &Png) -> Result<Outcome> {
+    let header = ImageHeader::from_png(png)?;
+    let mut lines = Vec::new();
+    if png.chunk_by_type("PLTE").is_some() {
+        let histogram = match png.chunk_by_type("hIST") {
+            Some(chunk) => Some(Histogram::try_from(chunk.data())?),
+            None => None,
+        };
+        // colored swatches only make sense on a terminal
+        let swatches = stdout().is_terminal();
+        for (i, color) in palette::colors(png)?.iter().enumerate() {
+            let mut line = format!("{:3} {} alpha {:3}", i, palette::hex(color), color[3]);
+            if swatches {
+                line.insert_str(
+                    0,
+                    &format!(
+                        "\x1b[48;2;{};{};{}m    \x1b[0m ",
+                        color[0], color[1], color[2]
+                    ),
+                );
+            }
+            if let Some(frequency) = histogram.as_ref().and_then(|h| h.frequencies.get(i)) {
+                write!(line, " used {}", frequency)?;
+            }
+            lines.push(line);
+        }
+    }
+    if let Some(chunk) = png.chunk_by_type("bKGD") {
+        let background = Background::parse(chunk.data(), header.color_type)?;
+        lines.push(format!("Background: {}", background));
+    }
+    for chunk in png
+        .chunks()
+        .iter()
+        .filter(|c| c.chunk_type().to_string() == "sPLT")
+    {
+        let suggested = SuggestedPalette::try_from(chunk.data())?;
+        lines.push(format!("Suggested palette: {}", suggested));
+    }
+    for problem in palette::validate(png)? {
+        lines.push(format!("Warning: {}", problem));
+    }
+    if lines.is_empty() {
+        return Ok(Outcome::Skipped(String::from("No palette")));
+    }
+    Ok(Outcome::Done(lines.join("\n")))
+}
+
+// An explicit format wins, otherwise it comes from the file extension.
+fn palette_format(path: &str, format: Option<PaletteFormat>) -> Result<PaletteFormat> {
+    if let Some(format) = format {
+        return Ok(format);
+    }
+    let extension = Path::new(path)
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    PaletteFormat::from_str(&extension)
+        .map_err(|_| format!("cannot tell the palette format of {}, use --format", path).into())
+}
+
+pub fn read_chunk(chunk_path: &str) -> Result<Chunk> {
+    let chunk_data = fs::read(chunk_path)?;
+    let chunk = Chunk::try_from(&chunk_data[..])?;
+    if chunk.as_bytes().len() != chunk_data.len() {
+        return Err("trailing data after chunk".into());
+    }
+    Ok(chunk)
+}
+
+pub fn diff_files(first_path: &str, second_path: &str) -> Result<Outcome> {
+    let first = read_png(first_path)?;
+    let second = read_png(second_path)?;
+    let changes = diff(&first, &second);
+    if changes.is_empty() {
+        return Ok(Outcome::Done(String::from("No differences")));
+    }
+    let changes: Vec<String> = changes.iter().map(|c| c.to_string()).collect();
+    Ok(Outcome::Done(changes.join("\n")))
+}
+
+pub enum MessagesAction {
+    List,
+    Get(Selector, DecodeOptions),
+    Add {
+        message: String,
+        label: Option<String>,
+        chunk_type: TypeStrategy,
+        // the message that is replaced instead of adding a new one
+        replace: Option<Selector>,
+        force: bool,
+        registry: Registry,
+    },
+    Remove(Selector),
+}
+
+pub fn messages(
+    file_path: &str,
+    action: &MessagesAction,
+    options: &WriteOptions,
+) -> Result<Outcome> {
+    let original = read_png(file_path)?;
+    let mut png = original.clone();
+    let report = match action {
+        MessagesAction::List => {
+            let index = MessageIndex::from_png(&original);
+            if index.messages().is_empty() {
+                return Ok(Outcome::Skipped(String::from("No messages")));
+            }
+            let lines: Vec<String> = index.messages().iter().map(|m| m.to_string()).collect();
+            return Ok(Outcome::Done(lines.join("\n")));
+        }
+        MessagesAction::Get(selector, payload) => {
+            let index = MessageIndex::from_png(&original);
+            let Some(message) = index.get(selector) else {
+                return Ok(Outcome::Skipped(format!("Message {} not found", selector)));
+            };
+            let (text, _) = message_text(message.container.clone(), payload)?;
+            return Ok(Outcome::Done(text));
+        }
+        MessagesAction::Add {
+            message,
+            label,
+            chunk_type,
+            replace,
+            force,
+            registry,
+        } => {
+            let mut container = Container {
+                filename: label.clone(),
+                ..Container::new(message.as_bytes().to_vec())
+            };
+            match replace {
+                Some(selector) => {
+                    // without a new label the message keeps its own
+                    if label.is_none() {
+                        container.filename = MessageIndex::from_png(&original)
+                            .get(selector)
+                            .and_then(|m| m.container.filename.clone());
+                    }
+                    messages::replace(&mut png, selector, container)?;
+                    format!("Replaced message {}", selector)
+                }
+                None => {
+                    let chunk_type = chunk_type.chunk_type(&original, registry)?;
+                    let warnings = check_policy(&chunk_type, registry, *force)?;
+                    let id = messages::add(&mut png, chunk_type, container)?;
+                    let mut report = format!("Added message {}", id);
+                    for warning in warnings {
+                        write!(report, "\nWarning: {}", warning)?;
+                    }
+                    report
+                }
+            }
+        }
+        MessagesAction::Remove(selector) => {
+            let removed = messages::remove(&mut png, selector)?;
+            format!("Removed message {}", removed.id)
+        }
+    };
+    let mut saved = save(file_path, &original, &png, options)?;
+    saved.push_str(&report);
+    Ok(Outcome::Done(saved))
+}
+
+pub fn repair(file_path: &str, output: Option<&str>, options: &WriteOptions) -> Result<Outcome> {
+    let data = fs::read(file_path)?;
+    let (original, problems) = parse_lenient(&data)?;
+    if problems.is_empty() {
+        return Ok(Outcome::Skipped(String::from("Nothing to repair")));
+    }
+    let png = repair::repair(&original, &problems);
+    let mut report = save(output.unwrap_or(file_path), &original, &png, options)?;
+    let fixes: Vec<String> = problems.iter().map(|p| p.fix()).collect();
+    report.push_str(&fixes.join("\n"));
+    Ok(Outcome::Done(report))
+}
+
+pub fn carve(blob_path: &str, output_dir: Option<&str>, registry: &Registry) -> Result<Outcome> {
+    let data = map_file(blob_path)?;
+    let mut lines = Vec::new();
+    for png in find_pngs(&data) {
+        if !png.is_complete() {
+            lines.push(format!(
+                "Incomplete PNG at offset {}, {} chunks",
+                png.offset,
+                png.chunks.len()
+            ));
+            continue;
+        }
+        let mut line = format!(
+            "PNG at offset {}, {} bytes, {} chunks",
+            png.offset,
+            png.bytes.len(),
+            png.chunks.len()
+        );
+        if let Some(dir) = output_dir {
+            fs::create_dir_all(dir)?;
+            let path = Path::new(dir).join(format!("carved-{}.png", png.offset));
+            match write_new_file(&path, png.bytes) {
+                Ok(()) => write!(line, ", extracted to {}", path.display())?,
+                Err(e) if e.kind() == ErrorKind::AlreadyExists => {
+                    write!(line, ", not extracted, {} already exists", path.display())?
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+        lines.push(line);
+    }
+    for message in find_messages(&data, registry) {
+        let data = message.chunk.data();
+        let container = match ecc::parity(data) {
+            Some(_) => ecc::decode(data).and_then(|(data, _)| Container::from_chunk_data(&data)),
+            None => Container::from_chunk_data(data),
+        };
+        let content = match container {
+            Ok(container) if container.compressed || container.encrypted => format!(
+                "{} bytes of {}",
+                container.payload.len(),
+                container.content_type
+            ),
+            Ok(container) => format!("{:?}", String::from_utf8_lossy(&container.payload)),
+            Err(e) => format!("unreadable container, {}", e),
+        };
+        lines.push(format!(
+            "Message {} at offset {}: {}",
+            message.chunk.chunk_type(),
+            message.offset,
+            content
+        ));
+    }
+    if lines.is_empty() {
+        return Ok(Outcome::Skipped(String::from("Nothing found")));
+    }
+    Ok(Outcome::Done(lines.join("\n")))
+}
+
+#[cfg(feature = "json")]
+pub fn dump(
+    file_path: &str,
+    format: ManifestFormat,
+    encoding: DataEncoding,
+    output: Option<&str>,
+) -> Result<Outcome> {
+    let png = read_png(file_path)?;
+    let text = manifest::dump(&png, format, encoding);
+    match output {
+        Some(output) => {
+            write_file(output, text.as_bytes(), &WriteOptions::default())?;
+            Ok(Outcome::Done(format!(
+                "Dumped {} chunks to {}",
+                png.chunks().len(),
+                output
+            )))
+        }
+        None => Ok(Outcome::Done(text.trim_end().to_string())),
+    }
+}
+
+#[cfg(feature = "json")]
+pub fn build(manifest_path: &str, format: Option<ManifestFormat>, output: &str) -> Result<Outcome> {
+    let format = format
+        .or_else(|| ManifestFormat::from_path(manifest_path))
+        .ok_or("unknown manifest format, use --format")?;
+    let png = manifest::build(&fs::read_to_string(manifest_path)?, format)?;
+    write_file(output, &png.as_bytes(), &WriteOptions::default())?;
+    Ok(Outcome::Done(format!(
+        "Built {} with {} chunks",
+        output,
+        png.chunks().len()
+    )))
+}
+
+fn save(file_path: &str, original: &Png, png: &Png, options: &WriteOptions) -> Result<String> {
+    if options.dry_run {
+        let mut report = format!("Dry run, {} was not written\n", file_path);
+        for change in diff(original, png) {
+            writeln!(report, "{}", change)?;
+        }
+        return Ok(report);
+    }
+    write_file(file_path, &png.as_bytes(), options)?;
+    Ok(String::new())
+}
+
+// Replaces every chunk of the same type as `chunk` with it, at `position`.
+fn set_chunk(png: &mut Png, chunk: Chunk, position: ChunkPosition) -> Result<usize> {
+    let chunk_type = chunk.chunk_type().clone();
+    png.remove_chunks_where(|c| *c.chunk_type() == chunk_type);
+    Ok(png.insert_chunk(position, chunk)?)
+}
+
+fn frame_chunks(png: &Png, frame: usize) -> Result<std::ops::Range<usize>> {
+    match apng::frames(png)?.into_iter().nth(frame) {
+        Some(frame) => Ok(frame.chunks),
+        None => Err(format!("frame {} not found", frame).into()),
     }
 }
 
-fn read_file(file_path: &String) -> Vec<u8> {
-    let data = fs::read(file_path);
-    data.expect("could not open file")
+fn read_png(file_path: &str) -> Result<Png> {
+    let data = fs::read(file_path)?;
+    Ok(Png::try_from(&data[..])?)
 }
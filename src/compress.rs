@@ -0,0 +1,225 @@
+use std::{
+    fmt::Display,
+    io::{Read, Write},
+    str::FromStr,
+};
+
+use flate2::{read::DeflateDecoder, write::DeflateEncoder};
+
+// Compressed payloads start with a header: this magic, the algorithm and the
+// uncompressed length (u32, big endian). Text messages never start with a NUL
+// byte, so raw messages are told apart from compressed ones.
+const MAGIC: [u8; 3] = *b"\0PZ";
+const HEADER_LENGTH: usize = 8;
+
+pub const DEFAULT_SIZE_LIMIT: usize = 16 * 1024 * 1024;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Compression {
+    Deflate,
+    Zstd,
+    // an LZ77 byte codec in the spirit of LZ4, faster but larger than the others,
+    // also accepted as lz4
+    Snappy,
+}
+
+impl Compression {
+    fn id(self) -> u8 {
+        match self {
+            Compression::Deflate => 1,
+            Compression::Zstd => 2,
+            Compression::Snappy => 3,
+        }
+    }
+}
+
+impl TryFrom<u8> for Compression {
+    type Error = String;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            1 => Ok(Compression::Deflate),
+            2 => Ok(Compression::Zstd),
+            3 => Ok(Compression::Snappy),
+            _ => Err(format!("unknown compression {}", value)),
+        }
+    }
+}
+
+impl FromStr for Compression {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "deflate" => Ok(Compression::Deflate),
+            "zstd" => Ok(Compression::Zstd),
+            "snappy" | "lz4" => Ok(Compression::Snappy),
+            _ => Err(String::from(
+                "invalid compression, expected deflate, zstd or snappy (lz4)",
+            )),
+        }
+    }
+}
+
+impl Display for Compression {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Compression::Deflate => write!(f, "deflate"),
+            Compression::Zstd => write!(f, "zstd"),
+            Compression::Snappy => write!(f, "snappy"),
+        }
+    }
+}
+
+pub fn compress(data: &[u8], compression: Compression) -> Result<Vec<u8>, String> {
+    let length = u32::try_from(data.len()).map_err(|_| String::from("payload too large"))?;
+    let mut out = MAGIC.to_vec();
+    out.push(compression.id());
+    out.extend_from_slice(&length.to_be_bytes());
+    let compressed = match compression {
+        Compression::Deflate => {
+            let mut encoder = DeflateEncoder::new(out, flate2::Compression::best());
+            encoder.write_all(data).map_err(|e| e.to_string())?;
+            return encoder.finish().map_err(|e| e.to_string());
+        }
+        // knowing the size up front keeps the window no larger than the data
+        Compression::Zstd => zstd::bulk::compress(data, 19).map_err(|e| e.to_string())?,
+        Compression::Snappy => snap::raw::Encoder::new()
+            .compress_vec(data)
+            .map_err(|e| e.to_string())?,
+    };
+    out.extend(compressed);
+    Ok(out)
+}
+
+pub fn is_compressed(data: &[u8]) -> bool {
+    data.len() >= HEADER_LENGTH && data[..MAGIC.len()] == MAGIC
+}
+
+// Data without the header is returned as it is. Nothing larger than `limit`
+// bytes is ever inflated, whatever the header claims, so a small chunk cannot
+// expand into gigabytes.
+pub fn decompress(data: &[u8], limit: usize) -> Result<(Vec<u8>, Option<Compression>), String> {
+    if !is_compressed(data) {
+        return Ok((data.to_vec(), None));
+    }
+    let compression = Compression::try_from(data[3])?;
+    let length = u32::from_be_bytes(core::array::from_fn(|i| data[4 + i])) as usize;
+    if length > limit {
+        return Err(format!(
+            "payload of {} bytes is larger than the limit of {} bytes",
+            length, limit
+        ));
+    }
+    let compressed = &data[HEADER_LENGTH..];
+    let mut out = Vec::with_capacity(length);
+    let read = match compression {
+        Compression::Deflate => DeflateDecoder::new(compressed)
+            .take(length as u64 + 1)
+            .read_to_end(&mut out),
+        Compression::Zstd => zstd::Decoder::new(compressed).and_then(|mut decoder| {
+            // a frame asking for a larger window than the limit is refused
+            decoder.window_log_max(window_log(limit))?;
+            decoder.take(length as u64 + 1).read_to_end(&mut out)
+        }),
+        Compression::Snappy => {
+            match snap::raw::decompress_len(compressed) {
+                Ok(len) if len == length => {}
+                _ => return Err(String::from("invalid compressed payload")),
+            }
+            out = snap::raw::Decoder::new()
+                .decompress_vec(compressed)
+                .map_err(|e| e.to_string())?;
+            Ok(out.len())
+        }
+    };
+    match read {
+        Ok(_) if out.len() == length => Ok((out, Some(compression))),
+        _ => Err(String::from("invalid compressed payload")),
+    }
+}
+
+// The smallest zstd window log that covers `size` bytes.
+fn window_log(size: usize) -> u32 {
+    (usize::BITS - size.saturating_sub(1).leading_zeros()).clamp(10, 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALL: [Compression; 3] = [Compression::Deflate, Compression::Zstd, Compression::Snappy];
+
+    #[test]
+    fn test_round_trip() {
+        let message = "a message that repeats itself, ".repeat(20);
+        for compression in ALL {
+            let compressed = compress(message.as_bytes(), compression).unwrap();
+            assert!(compressed.len() < message.len() / 2, "{}", compression);
+            assert_eq!(
+                decompress(&compressed, DEFAULT_SIZE_LIMIT).unwrap(),
+                (message.as_bytes().to_vec(), Some(compression))
+            );
+        }
+    }
+
+    #[test]
+    fn test_names() {
+        for compression in ALL {
+            assert_eq!(
+                Compression::from_str(&compression.to_string()),
+                Ok(compression)
+            );
+        }
+        assert_eq!(Compression::from_str("lz4"), Ok(Compression::Snappy));
+        assert!(Compression::from_str("lzma").is_err());
+    }
+
+    #[test]
+    fn test_raw_data() {
+        assert_eq!(
+            decompress(b"plain text", DEFAULT_SIZE_LIMIT).unwrap(),
+            (b"plain text".to_vec(), None)
+        );
+    }
+
+    #[test]
+    fn test_size_limit() {
+        let zeros = vec![0; 100_000];
+        for compression in ALL {
+            let compressed = compress(&zeros, compression).unwrap();
+            assert_eq!(
+                decompress(&compressed, 1000).unwrap_err(),
+                "payload of 100000 bytes is larger than the limit of 1000 bytes"
+            );
+            // a header lying about the length does not get past the limit either
+            let mut lying = compressed.clone();
+            lying[4..8].copy_from_slice(&10u32.to_be_bytes());
+            assert_eq!(
+                decompress(&lying, 1000).unwrap_err(),
+                "invalid compressed payload"
+            );
+        }
+    }
+
+    #[test]
+    fn test_zstd_window() {
+        assert_eq!(window_log(1000), 10);
+        assert_eq!(window_log(DEFAULT_SIZE_LIMIT), 24);
+        let message = b"a short message".to_vec();
+        let compressed = compress(&message, Compression::Zstd).unwrap();
+        assert_eq!(decompress(&compressed, 1000).unwrap().0, message);
+
+        // a frame streamed without its size keeps the window it was made with
+        let mut encoder = zstd::Encoder::new(Vec::new(), 19).unwrap();
+        encoder.window_log(20).unwrap();
+        encoder.write_all(&message).unwrap();
+        let mut streamed = compressed[..HEADER_LENGTH].to_vec();
+        streamed.extend(encoder.finish().unwrap());
+        assert_eq!(
+            decompress(&streamed, 1000).unwrap_err(),
+            "invalid compressed payload"
+        );
+        assert_eq!(decompress(&streamed, 1 << 20).unwrap().0, message);
+    }
+}
@@ -0,0 +1,336 @@
+// The pngme payload container, what `encode` writes as chunk data so that
+// messages can be recognised and the format can evolve. All integers are big
+// endian.
+//
+//   magic          4 bytes   "\0PNM"
+//   version        1 byte    1
+//   flags          1 byte    1 compressed, 2 encrypted, 4 signed, 8 fragment,
+//                            16 message id
+//   content type   1 byte length, then that many bytes of UTF-8
+//   filename       1 byte length (0 for none), then that many bytes of UTF-8
+//   fragment       8 bytes   message id (u32), index (u16), count (u16),
+//                            only with the fragment flag
+//   message id     4 bytes   stable ID of the message in its file (u32), only
+//                            with the message id flag
+//   length         4 bytes   payload length
+//   payload        length bytes, with the compression header when compressed
+//   checksum       4 bytes   CRC-32 of everything above
+//
+// Chunks written before the container existed hold just the message, possibly
+// compressed. They are read as version 0 containers.
+
+use crate::{compress, crc::crc32};
+
+const MAGIC: [u8; 4] = *b"\0PNM";
+pub const VERSION: u8 = 1;
+pub const DEFAULT_CONTENT_TYPE: &str = "text/plain";
+
+const COMPRESSED: u8 = 1;
+const ENCRYPTED: u8 = 2;
+const SIGNED: u8 = 4;
+const FRAGMENT: u8 = 8;
+const MESSAGE_ID: u8 = 16;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Fragment {
+    // shared by all fragments of one message
+    pub id: u32,
+    pub index: u16,
+    pub count: u16,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Container {
+    pub version: u8,
+    pub compressed: bool,
+    pub encrypted: bool,
+    pub signed: bool,
+    pub content_type: String,
+    pub filename: Option<String>,
+    pub fragment: Option<Fragment>,
+    pub message_id: Option<u32>,
+    pub payload: Vec<u8>,
+}
+
+impl Container {
+    pub fn new(payload: Vec<u8>) -> Container {
+        Container {
+            version: VERSION,
+            compressed: false,
+            encrypted: false,
+            signed: false,
+            content_type: String::from(DEFAULT_CONTENT_TYPE),
+            filename: None,
+            fragment: None,
+            message_id: None,
+            payload,
+        }
+    }
+
+    // Reads chunk data, falling back to a legacy container for data without the
+    // magic.
+    pub fn from_chunk_data(data: &[u8]) -> Result<Container, String> {
+        if Container::is_container(data) {
+            return Container::try_from(data);
+        }
+        Ok(Container {
+            version: 0,
+            compressed: compress::is_compressed(data),
+            ..Container::new(data.to_vec())
+        })
+    }
+
+    pub fn is_container(data: &[u8]) -> bool {
+        data.starts_with(&MAGIC)
+    }
+
+    fn flags(&self) -> u8 {
+        [
+            (self.compressed, COMPRESSED),
+            (self.encrypted, ENCRYPTED),
+            (self.signed, SIGNED),
+            (self.fragment.is_some(), FRAGMENT),
+            (self.message_id.is_some(), MESSAGE_ID),
+        ]
+        .iter()
+        .filter(|(set, _)| *set)
+        .fold(0, |flags, (_, flag)| flags | flag)
+    }
+
+    pub fn as_bytes(&self) -> Result<Vec<u8>, String> {
+        let mut bytes = MAGIC.to_vec();
+        bytes.push(VERSION);
+        bytes.push(self.flags());
+        for (name, value) in [
+            ("content type", self.content_type.as_str()),
+            ("filename", self.filename.as_deref().unwrap_or_default()),
+        ] {
+            let length = u8::try_from(value.len()).map_err(|_| format!("{} is too long", name))?;
+            bytes.push(length);
+            bytes.extend_from_slice(value.as_bytes());
+        }
+        if let Some(fragment) = self.fragment {
+            bytes.extend_from_slice(&fragment.id.to_be_bytes());
+            bytes.extend_from_slice(&fragment.index.to_be_bytes());
+            bytes.extend_from_slice(&fragment.count.to_be_bytes());
+        }
+        if let Some(id) = self.message_id {
+            bytes.extend_from_slice(&id.to_be_bytes());
+        }
+        let length =
+            u32::try_from(self.payload.len()).map_err(|_| String::from("payload too large"))?;
+        bytes.extend_from_slice(&length.to_be_bytes());
+        bytes.extend_from_slice(&self.payload);
+        bytes.extend_from_slice(&crc32(&bytes).to_be_bytes());
+        Ok(bytes)
+    }
+}
+
+impl TryFrom<&[u8]> for Container {
+    type Error = String;
+
+    fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
+        let mut reader = Reader { value, offset: 0 };
+        if reader.take(4)? != MAGIC {
+            return Err(String::from("not a pngme container"));
+        }
+        let version = reader.u8()?;
+        if version != VERSION {
+            return Err(format!("unsupported container version {}", version));
+        }
+        let flags = reader.u8()?;
+        if flags & !(COMPRESSED | ENCRYPTED | SIGNED | FRAGMENT | MESSAGE_ID) != 0 {
+            return Err(format!("unknown container flags {:#04x}", flags));
+        }
+        let content_type = reader.string("content type")?;
+        let filename = Some(reader.string("filename")?).filter(|f| !f.is_empty());
+        let fragment = match flags & FRAGMENT {
+            0 => None,
+            _ => Some(Fragment {
+                id: reader.u32()?,
+                index: reader.u16()?,
+                count: reader.u16()?,
+            }),
+        };
+        let message_id = match flags & MESSAGE_ID {
+            0 => None,
+            _ => Some(reader.u32()?),
+        };
+        let length = reader.u32()? as usize;
+        let payload = reader.take(length)?.to_vec();
+        let checksum_offset = reader.offset;
+        if reader.u32()? != crc32(&value[..checksum_offset]) {
+            return Err(String::from("invalid container checksum"));
+        }
+        if reader.offset != value.len() {
+            return Err(String::from("trailing data after container"));
+        }
+        Ok(Container {
+            version,
+            compressed: flags & COMPRESSED != 0,
+            encrypted: flags & ENCRYPTED != 0,
+            signed: flags & SIGNED != 0,
+            content_type,
+            filename,
+            fragment,
+            message_id,
+            payload,
+        })
+    }
+}
+
+struct Reader<'a> {
+    value: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, length: usize) -> Result<&'a [u8], String> {
+        let bytes = self
+            .value
+            .get(self.offset..self.offset.saturating_add(length))
+            .ok_or_else(|| String::from("truncated container"))?;
+        self.offset += length;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, String> {
+        Ok(u16::from_be_bytes([self.u8()?, self.u8()?]))
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_be_bytes(core::array::from_fn(|i| bytes[i])))
+    }
+
+    fn string(&mut self, name: &str) -> Result<String, String> {
+        let length = self.u8()? as usize;
+        String::from_utf8(self.take(length)?.to_vec()).map_err(|_| format!("invalid {}", name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn testing_container() -> Container {
+        Container {
+            filename: Some(String::from("note.txt")),
+            fragment: Some(Fragment {
+                id: 7,
+                index: 1,
+                count: 3,
+            }),
+            message_id: Some(12),
+            ..Container::new(b"hello".to_vec())
+        }
+    }
+
+    #[test]
+    fn test_layout() {
+        let bytes = Container::new(b"hi".to_vec()).as_bytes().unwrap();
+        let mut expected = b"\0PNM\x01\x00\x0atext/plain\x00\x00\x00\x00\x02hi".to_vec();
+        expected.extend_from_slice(&crc32(&expected).to_be_bytes());
+        assert_eq!(bytes, expected);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let container = testing_container();
+        let bytes = container.as_bytes().unwrap();
+        assert_eq!(Container::try_from(&bytes[..]).unwrap(), container);
+        assert_eq!(Container::from_chunk_data(&bytes).unwrap(), container);
+    }
+
+    #[test]
+    fn test_legacy_data() {
+        let container = Container::from_chunk_data(b"plain message").unwrap();
+        assert_eq!(container.version, 0);
+        assert_eq!(container.payload, b"plain message");
+        assert!(!container.compressed);
+
+        let compressed = compress::compress(b"x", compress::Compression::Deflate).unwrap();
+        assert!(Container::from_chunk_data(&compressed).unwrap().compressed);
+    }
+
+    #[test]
+    fn test_invalid_containers() {
+        let bytes = testing_container().as_bytes().unwrap();
+        let error = |bytes: &[u8]| Container::try_from(bytes).unwrap_err();
+
+        let mut damaged = bytes.clone();
+        damaged[30] ^= 1;
+        assert_eq!(error(&damaged), "invalid container checksum");
+        let mut newer = bytes.clone();
+        newer[4] = 2;
+        assert_eq!(error(&newer), "unsupported container version 2");
+        let mut flags = bytes.clone();
+        flags[5] |= 0x80;
+        assert_eq!(error(&flags), "unknown container flags 0x98");
+        assert_eq!(error(&bytes[..bytes.len() - 1]), "truncated container");
+        assert_eq!(
+            error(&[bytes.clone(), vec![0]].concat()),
+            "trailing data after container"
+        );
+        assert_eq!(error(b"text"), "not a pngme container");
+    }
+
+    fn arbitrary_container() -> impl Strategy<Value = Container> {
+        (
+            any::<(bool, bool, bool)>(),
+            "[a-z/+.-]{0,40}",
+            proptest::option::of("\\PC{1,40}"),
+            proptest::option::of(any::<(u32, u16, u16)>()),
+            proptest::option::of(any::<u32>()),
+            proptest::collection::vec(any::<u8>(), 0..2000),
+        )
+            .prop_map(
+                |(
+                    (compressed, encrypted, signed),
+                    content_type,
+                    filename,
+                    fragment,
+                    message_id,
+                    payload,
+                )| {
+                    Container {
+                        version: VERSION,
+                        compressed,
+                        encrypted,
+                        signed,
+                        content_type,
+                        filename: filename.filter(|f| f.len() <= 255),
+                        fragment: fragment.map(|(id, index, count)| Fragment { id, index, count }),
+                        message_id,
+                        payload,
+                    }
+                },
+            )
+    }
+
+    proptest! {
+        #[test]
+        fn prop_round_trip(container in arbitrary_container()) {
+            let bytes = container.as_bytes().unwrap();
+            prop_assert_eq!(Container::from_chunk_data(&bytes).unwrap(), container);
+        }
+
+        #[test]
+        fn prop_parse_never_panics(data in proptest::collection::vec(any::<u8>(), 0..200)) {
+            let _ = Container::from_chunk_data(&data);
+            let _ = Container::try_from(&[&MAGIC[..], &data].concat()[..]);
+        }
+
+        #[test]
+        fn prop_legacy_text_is_kept(text in "\\PC*") {
+            let container = Container::from_chunk_data(text.as_bytes()).unwrap();
+            prop_assert_eq!(container.version, 0);
+            prop_assert_eq!(container.payload, text.as_bytes());
+        }
+    }
+}
@@ -1,24 +1,258 @@
 // Original code from:
 // https://rosettacode.org/wiki/CRC-32#Rust
-// Small modifications were made, such as using u8 instead of str
+// Small modifications were made, such as using u8 instead of str, building the
+// tables at compile time and processing 8 or 16 bytes per step (slicing-by-N).
 
-fn crc32_compute_table() -> [u32; 256] {
-    let mut crc32_table = [0; 256];
+const POLYNOMIAL: u32 = 0xedb88320;
 
-    for n in 0..256 {
-        crc32_table[n as usize] = (0..8).fold(n as u32, |acc, _| match acc & 1 {
-            1 => 0xedb88320 ^ (acc >> 1),
-            _ => acc >> 1,
-        });
+const fn crc32_compute_tables() -> [[u32; 256]; 16] {
+    let mut tables = [[0; 256]; 16];
+
+    let mut n = 0;
+    while n < 256 {
+        let mut acc = n as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            acc = match acc & 1 {
+                1 => POLYNOMIAL ^ (acc >> 1),
+                _ => acc >> 1,
+            };
+            bit += 1;
+        }
+        tables[0][n] = acc;
+        n += 1;
+    }
+
+    // tables[k][n] is the crc of byte n followed by k zero bytes
+    let mut k = 1;
+    while k < 16 {
+        let mut n = 0;
+        while n < 256 {
+            let previous = tables[k - 1][n];
+            tables[k][n] = (previous >> 8) ^ tables[0][(previous & 0xff) as usize];
+            n += 1;
+        }
+        k += 1;
+    }
+
+    tables
+}
+
+static CRC32_TABLES: [[u32; 256]; 16] = crc32_compute_tables();
+
+pub fn crc32(buf: &[u8]) -> u32 {
+    let mut crc = Crc32::new();
+    crc.update(buf);
+    crc.finalize()
+}
+
+// Streaming CRC-32 hasher, so chunks can be checksummed piece by piece
+// (e.g. type then data) without concatenating them first.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Crc32 {
+    crc: u32,
+}
+
+impl Crc32 {
+    pub fn new() -> Crc32 {
+        Crc32 { crc: 0 }
+    }
+
+    pub fn update(&mut self, buf: &[u8]) {
+        #[cfg(target_arch = "x86_64")]
+        if clmul::is_available() {
+            // SAFETY: the required CPU features were just detected
+            self.crc = unsafe { clmul::update(self.crc, buf) };
+            return;
+        }
+        self.crc = update_slice_by_16(self.crc, buf);
     }
 
-    crc32_table
+    pub fn finalize(&self) -> u32 {
+        self.crc
+    }
 }
 
-pub(crate) fn crc32(buf: &[u8]) -> u32 {
-    let crc_table = crc32_compute_table();
+// The `update_*` functions take and return a finalized crc, so that they can be
+// chained: `update(update(0, a), b) == crc32(a ++ b)`.
+
+pub fn update_bytewise(crc: u32, buf: &[u8]) -> u32 {
+    !bytewise(!crc, buf)
+}
+
+pub fn update_slice_by_8(crc: u32, buf: &[u8]) -> u32 {
+    let t = &CRC32_TABLES;
+    let mut crc = !crc;
+    let mut chunks = buf.chunks_exact(8);
+    for b in &mut chunks {
+        crc ^= u32::from_le_bytes([b[0], b[1], b[2], b[3]]);
+        crc = t[0][b[7] as usize]
+            ^ t[1][b[6] as usize]
+            ^ t[2][b[5] as usize]
+            ^ t[3][b[4] as usize]
+            ^ t[4][(crc >> 24) as usize]
+            ^ t[5][((crc >> 16) & 0xff) as usize]
+            ^ t[6][((crc >> 8) & 0xff) as usize]
+            ^ t[7][(crc & 0xff) as usize];
+    }
+    !bytewise(crc, chunks.remainder())
+}
 
-    !buf.iter().fold(!0, |acc, octet| {
-        (acc >> 8) ^ crc_table[((acc & 0xff) ^ *octet as u32) as usize]
+pub fn update_slice_by_16(crc: u32, buf: &[u8]) -> u32 {
+    let t = &CRC32_TABLES;
+    let mut crc = !crc;
+    let mut chunks = buf.chunks_exact(16);
+    for b in &mut chunks {
+        crc ^= u32::from_le_bytes([b[0], b[1], b[2], b[3]]);
+        crc = t[0][b[15] as usize]
+            ^ t[1][b[14] as usize]
+            ^ t[2][b[13] as usize]
+            ^ t[3][b[12] as usize]
+            ^ t[4][b[11] as usize]
+            ^ t[5][b[10] as usize]
+            ^ t[6][b[9] as usize]
+            ^ t[7][b[8] as usize]
+            ^ t[8][b[7] as usize]
+            ^ t[9][b[6] as usize]
+            ^ t[10][b[5] as usize]
+            ^ t[11][b[4] as usize]
+            ^ t[12][(crc >> 24) as usize]
+            ^ t[13][((crc >> 16) & 0xff) as usize]
+            ^ t[14][((crc >> 8) & 0xff) as usize]
+            ^ t[15][(crc & 0xff) as usize];
+    }
+    !bytewise(crc, chunks.remainder())
+}
+
+fn bytewise(crc: u32, buf: &[u8]) -> u32 {
+    buf.iter().fold(crc, |acc, octet| {
+        (acc >> 8) ^ CRC32_TABLES[0][((acc & 0xff) ^ *octet as u32) as usize]
     })
 }
+
+#[cfg(target_arch = "x86_64")]
+pub mod clmul {
+    // Carry-less multiplication folding, following Intel's "Fast CRC Computation
+    // for Generic Polynomials Using PCLMULQDQ Instruction" white paper, with the
+    // constants for the bit-reflected CRC-32 polynomial.
+    use std::arch::x86_64::*;
+
+    const K1: i64 = 0x154442bd4;
+    const K2: i64 = 0x1c6e41596;
+    const K3: i64 = 0x1751997d0;
+    const K4: i64 = 0x0ccaa009e;
+    const K5: i64 = 0x163cd6124;
+    const P_X: i64 = 0x1db710641;
+    const U_PRIME: i64 = 0x1f7011641;
+
+    pub fn is_available() -> bool {
+        is_x86_feature_detected!("pclmulqdq") && is_x86_feature_detected!("sse4.1")
+    }
+
+    /// # Safety
+    ///
+    /// The CPU must support `pclmulqdq` and `sse4.1`, see [`is_available`].
+    #[target_feature(enable = "pclmulqdq", enable = "sse2", enable = "sse4.1")]
+    pub unsafe fn update(crc: u32, mut buf: &[u8]) -> u32 {
+        // folding only pays off on larger inputs
+        if buf.len() < 128 {
+            return super::update_slice_by_16(crc, buf);
+        }
+
+        // fold by 4
+        let mut x3 = load(&mut buf);
+        let mut x2 = load(&mut buf);
+        let mut x1 = load(&mut buf);
+        let mut x0 = load(&mut buf);
+        x3 = _mm_xor_si128(x3, _mm_cvtsi32_si128(!crc as i32));
+
+        let k1k2 = _mm_set_epi64x(K2, K1);
+        while buf.len() >= 64 {
+            x3 = fold(x3, load(&mut buf), k1k2);
+            x2 = fold(x2, load(&mut buf), k1k2);
+            x1 = fold(x1, load(&mut buf), k1k2);
+            x0 = fold(x0, load(&mut buf), k1k2);
+        }
+
+        let k3k4 = _mm_set_epi64x(K4, K3);
+        let mut x = fold(x3, x2, k3k4);
+        x = fold(x, x1, k3k4);
+        x = fold(x, x0, k3k4);
+
+        // fold by 1
+        while buf.len() >= 16 {
+            x = fold(x, load(&mut buf), k3k4);
+        }
+
+        // 128 bits down to 64 bits
+        let low_32 = _mm_set_epi32(0, 0, 0, !0);
+        let x = _mm_xor_si128(_mm_clmulepi64_si128(x, k3k4, 0x10), _mm_srli_si128(x, 8));
+        let x = _mm_xor_si128(
+            _mm_clmulepi64_si128(_mm_and_si128(x, low_32), _mm_set_epi64x(0, K5), 0x00),
+            _mm_srli_si128(x, 4),
+        );
+
+        // Barrett reduction from 64 bits to 32 bits
+        let pu = _mm_set_epi64x(U_PRIME, P_X);
+        let t1 = _mm_clmulepi64_si128(_mm_and_si128(x, low_32), pu, 0x10);
+        let t2 = _mm_clmulepi64_si128(_mm_and_si128(t1, low_32), pu, 0x00);
+        let crc = !(_mm_extract_epi32(_mm_xor_si128(x, t2), 1) as u32);
+
+        super::update_slice_by_16(crc, buf)
+    }
+
+    #[target_feature(enable = "pclmulqdq", enable = "sse2")]
+    unsafe fn fold(a: __m128i, b: __m128i, keys: __m128i) -> __m128i {
+        let t1 = _mm_clmulepi64_si128(a, keys, 0x00);
+        let t2 = _mm_clmulepi64_si128(a, keys, 0x11);
+        _mm_xor_si128(_mm_xor_si128(b, t1), t2)
+    }
+
+    #[target_feature(enable = "sse2")]
+    unsafe fn load(buf: &mut &[u8]) -> __m128i {
+        let value = _mm_loadu_si128(buf.as_ptr() as *const __m128i);
+        *buf = &buf[16..];
+        value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn testing_data(length: usize) -> Vec<u8> {
+        (0..length).map(|i| (i * 31 + i / 7) as u8).collect()
+    }
+
+    #[test]
+    fn test_crc32_check_value() {
+        assert_eq!(crc32(b"123456789"), 0xcbf43926);
+        assert_eq!(crc32(b""), 0);
+    }
+
+    #[test]
+    fn test_implementations_agree() {
+        for length in [0, 1, 7, 8, 15, 16, 17, 63, 127, 128, 129, 255, 1000, 4099] {
+            let data = testing_data(length);
+            let expected = update_bytewise(0, &data);
+            assert_eq!(update_slice_by_8(0, &data), expected, "length {}", length);
+            assert_eq!(update_slice_by_16(0, &data), expected, "length {}", length);
+            assert_eq!(crc32(&data), expected, "length {}", length);
+            #[cfg(target_arch = "x86_64")]
+            if clmul::is_available() {
+                let actual = unsafe { clmul::update(0, &data) };
+                assert_eq!(actual, expected, "length {}", length);
+            }
+        }
+    }
+
+    #[test]
+    fn test_streaming_matches_one_shot() {
+        let data = testing_data(5000);
+        let mut crc = Crc32::new();
+        for piece in data.chunks(333) {
+            crc.update(piece);
+        }
+        assert_eq!(crc.finalize(), update_bytewise(0, &data));
+    }
+}
@@ -0,0 +1,301 @@
+use std::fmt::Display;
+
+use crate::{chunk::Chunk, png::Png};
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct ChunkLocation {
+    pub index: usize,
+    pub offset: usize,
+    pub length: u32,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum ChunkChange {
+    Added {
+        chunk_type: String,
+        location: ChunkLocation,
+    },
+    Removed {
+        chunk_type: String,
+        location: ChunkLocation,
+    },
+    Modified {
+        chunk_type: String,
+        before: ChunkLocation,
+        after: ChunkLocation,
+    },
+}
+
+// Compares two PNGs chunk by chunk. Identical chunks are matched first (longest
+// common subsequence), then unmatched chunks of the same type between two
+// matches are reported as modified.
+pub fn diff(before: &Png, after: &Png) -> Vec<ChunkChange> {
+    let old = before.chunks();
+    let new = after.chunks();
+    let old_offsets = offsets(old);
+    let new_offsets = offsets(new);
+    let location = |chunks: &[Chunk], offsets: &[usize], index: usize| ChunkLocation {
+        index,
+        offset: offsets[index],
+        length: chunks[index].length(),
+    };
+
+    let mut changes = Vec::new();
+    let mut anchors = common_chunks(old, new);
+    anchors.push((old.len(), new.len()));
+    let (mut i, mut j) = (0, 0);
+    for (next_i, next_j) in anchors {
+        let mut added: Vec<usize> = (j..next_j).collect();
+        for old_index in i..next_i {
+            let chunk_type = old[old_index].chunk_type().to_string();
+            match added
+                .iter()
+                .position(|&n| new[n].chunk_type() == old[old_index].chunk_type())
+            {
+                Some(position) => {
+                    let new_index = added.remove(position);
+                    changes.push(ChunkChange::Modified {
+                        chunk_type,
+                        before: location(old, &old_offsets, old_index),
+                        after: location(new, &new_offsets, new_index),
+                    });
+                }
+                None => changes.push(ChunkChange::Removed {
+                    chunk_type,
+                    location: location(old, &old_offsets, old_index),
+                }),
+            }
+        }
+        for new_index in added {
+            changes.push(ChunkChange::Added {
+                chunk_type: new[new_index].chunk_type().to_string(),
+                location: location(new, &new_offsets, new_index),
+            });
+        }
+        (i, j) = (next_i + 1, next_j + 1);
+    }
+    changes
+}
+
+fn offsets(chunks: &[Chunk]) -> Vec<usize> {
+    chunks
+        .iter()
+        .scan(Png::STANDARD_HEADER.len(), |offset, chunk| {
+            let current = *offset;
+            *offset += 12 + chunk.length() as usize;
+            Some(current)
+        })
+        .collect()
+}
+
+// Chunks are told apart by type, length and crc, which is cheaper than
+// comparing their data.
+type ChunkKey = ([u8; 4], u32, u32);
+
+fn key(chunk: &Chunk) -> ChunkKey {
+    (chunk.chunk_type().bytes(), chunk.length(), chunk.crc())
+}
+
+// The longest common subsequence of chunks, found with Hirschberg's algorithm in
+// linear space.
+fn common_chunks(old: &[Chunk], new: &[Chunk]) -> Vec<(usize, usize)> {
+    let old: Vec<ChunkKey> = old.iter().map(key).collect();
+    let new: Vec<ChunkKey> = new.iter().map(key).collect();
+    let mut pairs = Vec::new();
+    hirschberg(&old, &new, (0, 0), &mut pairs);
+    pairs
+}
+
+fn hirschberg(
+    old: &[ChunkKey],
+    new: &[ChunkKey],
+    start: (usize, usize),
+    pairs: &mut Vec<(usize, usize)>,
+) {
+    if old.is_empty() || new.is_empty() {
+        return;
+    }
+    if old.len() == 1 {
+        if let Some(j) = new.iter().position(|k| *k == old[0]) {
+            pairs.push((start.0, start.1 + j));
+        }
+        return;
+    }
+    let middle = old.len() / 2;
+    let forward = lcs_lengths(old[..middle].iter(), new.iter());
+    let backward = lcs_lengths(old[middle..].iter().rev(), new.iter().rev());
+    let split = (0..=new.len())
+        .max_by_key(|&j| (forward[j] + backward[new.len() - j], usize::MAX - j))
+        .expect("not empty");
+    hirschberg(&old[..middle], &new[..split], start, pairs);
+    hirschberg(
+        &old[middle..],
+        &new[split..],
+        (start.0 + middle, start.1 + split),
+        pairs,
+    );
+}
+
+// The lengths of the longest common subsequences of `old` and every prefix of
+// `new`, keeping a single row.
+fn lcs_lengths<'a>(
+    old: impl Iterator<Item = &'a ChunkKey>,
+    new: impl Iterator<Item = &'a ChunkKey> + Clone,
+) -> Vec<usize> {
+    let mut row = vec![0; new.clone().count() + 1];
+    for o in old {
+        let mut diagonal = 0;
+        for (j, n) in new.clone().enumerate() {
+            let above = row[j + 1];
+            row[j + 1] = if o == n {
+                diagonal + 1
+            } else {
+                above.max(row[j])
+            };
+            diagonal = above;
+        }
+    }
+    row
+}
+
+impl Display for ChunkLocation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "index {}, offset {}, length {}",
+            self.index, self.offset, self.length
+        )
+    }
+}
+
+impl Display for ChunkChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ChunkChange::Added {
+                chunk_type,
+                location,
+            } => write!(f, "+ {} ({})", chunk_type, location),
+            ChunkChange::Removed {
+                chunk_type,
+                location,
+            } => write!(f, "- {} ({})", chunk_type, location),
+            ChunkChange::Modified {
+                chunk_type,
+                before,
+                after,
+            } => write!(f, "~ {} ({}) -> ({})", chunk_type, before, after),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk_type::ChunkType;
+    use proptest::prelude::*;
+    use std::str::FromStr;
+
+    fn chunk_from_strings(chunk_type: &str, data: &str) -> Chunk {
+        Chunk::new(
+            ChunkType::from_str(chunk_type).unwrap(),
+            data.as_bytes().to_vec(),
+        )
+    }
+
+    fn testing_png() -> Png {
+        Png::from_chunks(vec![
+            chunk_from_strings("FrSt", "I am the first chunk"),
+            chunk_from_strings("miDl", "I am another chunk"),
+            chunk_from_strings("LASt", "I am the last chunk"),
+        ])
+    }
+
+    #[test]
+    fn test_diff_identical() {
+        assert!(diff(&testing_png(), &testing_png()).is_empty());
+    }
+
+    #[test]
+    fn test_diff_added() {
+        let mut after = testing_png();
+        after.append_chunk(chunk_from_strings("TeSt", "hi"));
+        let changes = diff(&testing_png(), &after);
+        assert_eq!(
+            changes,
+            vec![ChunkChange::Added {
+                chunk_type: String::from("TeSt"),
+                location: ChunkLocation {
+                    index: 3,
+                    offset: 8 + 32 + 30 + 31,
+                    length: 2
+                },
+            }]
+        );
+    }
+
+    #[test]
+    fn test_diff_removed() {
+        let mut after = testing_png();
+        after.remove_first_chunk("miDl");
+        let changes = diff(&testing_png(), &after);
+        assert_eq!(
+            changes,
+            vec![ChunkChange::Removed {
+                chunk_type: String::from("miDl"),
+                location: ChunkLocation {
+                    index: 1,
+                    offset: 8 + 32,
+                    length: 18
+                },
+            }]
+        );
+    }
+
+    #[test]
+    fn test_diff_modified() {
+        let before = testing_png();
+        let after = Png::from_chunks(vec![
+            chunk_from_strings("FrSt", "I am the first chunk"),
+            chunk_from_strings("miDl", "changed"),
+            chunk_from_strings("LASt", "I am the last chunk"),
+        ]);
+        let changes = diff(&before, &after);
+        assert_eq!(changes.len(), 1);
+        assert!(matches!(
+            &changes[0],
+            ChunkChange::Modified { chunk_type, before, after }
+                if chunk_type == "miDl" && before.length == 18 && after.length == 7
+        ));
+    }
+
+    // the quadratic table the linear space version has to agree with
+    fn lcs_length(old: &[u8], new: &[u8]) -> usize {
+        let mut lengths = vec![vec![0; new.len() + 1]; old.len() + 1];
+        for i in 0..old.len() {
+            for j in 0..new.len() {
+                lengths[i + 1][j + 1] = if old[i] == new[j] {
+                    lengths[i][j] + 1
+                } else {
+                    lengths[i][j + 1].max(lengths[i + 1][j])
+                };
+            }
+        }
+        lengths[old.len()][new.len()]
+    }
+
+    proptest! {
+        #[test]
+        fn prop_common_chunks_is_longest(
+            old in proptest::collection::vec(0u8..4, 0..30),
+            new in proptest::collection::vec(0u8..4, 0..30),
+        ) {
+            let chunks = |data: &[u8]| -> Vec<Chunk> {
+                data.iter().map(|d| chunk_from_strings("TeSt", &d.to_string())).collect()
+            };
+            let pairs = common_chunks(&chunks(&old), &chunks(&new));
+            prop_assert_eq!(pairs.len(), lcs_length(&old, &new));
+            prop_assert!(pairs.windows(2).all(|w| w[0].0 < w[1].0 && w[0].1 < w[1].1));
+            prop_assert!(pairs.iter().all(|&(i, j)| old[i] == new[j]));
+        }
+    }
+}
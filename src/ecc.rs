@@ -0,0 +1,342 @@
+// Reed-Solomon error correction over GF(2^8), following:
+// https://en.wikiversity.org/wiki/Reed%E2%80%93Solomon_codes_for_coders
+// Polynomials are stored highest degree first, as in the original.
+//
+// Payloads are split into blocks of at most 255 bytes, each ending with
+// `parity` bytes that allow fixing up to `parity / 2` wrong bytes in the block.
+// They start with a header block holding a magic and the parity, protected by
+// parity bytes of its own, so decoding needs no options and survives damage to
+// the header as well.
+
+const PRIMITIVE: u16 = 0x11d;
+const BLOCK_SIZE: usize = 255;
+const MAGIC: [u8; 4] = *b"\0PNR";
+const HEADER_PARITY: usize = 8;
+const HEADER_LENGTH: usize = MAGIC.len() + 1 + HEADER_PARITY;
+
+const fn compute_tables() -> ([u8; 512], [u8; 256]) {
+    let mut exp = [0; 512];
+    let mut log = [0; 256];
+    let mut x: u16 = 1;
+    let mut i = 0;
+    while i < 255 {
+        exp[i] = x as u8;
+        log[x as usize] = i as u8;
+        x <<= 1;
+        if x & 0x100 != 0 {
+            x ^= PRIMITIVE;
+        }
+        i += 1;
+    }
+    // doubled so products of two logarithms need no modulo
+    while i < 512 {
+        exp[i] = exp[i - 255];
+        i += 1;
+    }
+    (exp, log)
+}
+
+static TABLES: ([u8; 512], [u8; 256]) = compute_tables();
+
+fn mul(x: u8, y: u8) -> u8 {
+    if x == 0 || y == 0 {
+        return 0;
+    }
+    let (exp, log) = &TABLES;
+    exp[log[x as usize] as usize + log[y as usize] as usize]
+}
+
+fn div(x: u8, y: u8) -> u8 {
+    if x == 0 {
+        return 0;
+    }
+    let (exp, log) = &TABLES;
+    exp[(log[x as usize] as usize + 255 - log[y as usize] as usize) % 255]
+}
+
+fn pow(x: u8, power: i32) -> u8 {
+    let (exp, log) = &TABLES;
+    exp[(log[x as usize] as i32 * power).rem_euclid(255) as usize]
+}
+
+fn inverse(x: u8) -> u8 {
+    div(1, x)
+}
+
+fn poly_scale(p: &[u8], x: u8) -> Vec<u8> {
+    p.iter().map(|&c| mul(c, x)).collect()
+}
+
+fn poly_add(p: &[u8], q: &[u8]) -> Vec<u8> {
+    let len = p.len().max(q.len());
+    let mut r = vec![0; len];
+    for (i, &c) in p.iter().enumerate() {
+        r[i + len - p.len()] = c;
+    }
+    for (i, &c) in q.iter().enumerate() {
+        r[i + len - q.len()] ^= c;
+    }
+    r
+}
+
+fn poly_mul(p: &[u8], q: &[u8]) -> Vec<u8> {
+    let mut r = vec![0; p.len() + q.len() - 1];
+    for (j, &b) in q.iter().enumerate() {
+        for (i, &a) in p.iter().enumerate() {
+            r[i + j] ^= mul(a, b);
+        }
+    }
+    r
+}
+
+fn poly_eval(p: &[u8], x: u8) -> u8 {
+    p[1..].iter().fold(p[0], |y, &c| mul(y, x) ^ c)
+}
+
+// The remainder of dividing by a monic polynomial.
+fn poly_rem(dividend: &[u8], divisor: &[u8]) -> Vec<u8> {
+    let mut out = dividend.to_vec();
+    for i in 0..dividend.len() - (divisor.len() - 1) {
+        let coef = out[i];
+        if coef != 0 {
+            for (j, &d) in divisor.iter().enumerate().skip(1) {
+                out[i + j] ^= mul(d, coef);
+            }
+        }
+    }
+    out.split_off(dividend.len() - (divisor.len() - 1))
+}
+
+fn generator(parity: usize) -> Vec<u8> {
+    (0..parity).fold(vec![1], |g, i| poly_mul(&g, &[1, pow(2, i as i32)]))
+}
+
+fn check_parity(parity: usize) -> Result<(), String> {
+    if !(1..BLOCK_SIZE).contains(&parity) {
+        return Err(String::from("parity must be between 1 and 254 bytes"));
+    }
+    Ok(())
+}
+
+pub fn encode(data: &[u8], parity: usize) -> Result<Vec<u8>, String> {
+    check_parity(parity)?;
+    let mut header = MAGIC.to_vec();
+    header.push(parity as u8);
+    let mut out = encode_blocks(&header, HEADER_PARITY);
+    out.extend(encode_blocks(data, parity));
+    Ok(out)
+}
+
+// The parity of an error corrected payload, None for other data.
+pub fn parity(data: &[u8]) -> Option<usize> {
+    read_header(data).map(|(parity, _)| parity)
+}
+
+// Returns the payload and how many bytes had to be corrected.
+pub fn decode(data: &[u8]) -> Result<(Vec<u8>, usize), String> {
+    let (parity, header_errors) =
+        read_header(data).ok_or_else(|| String::from("not an error corrected payload"))?;
+    let (out, corrected) = decode_blocks(&data[HEADER_LENGTH..], parity)?;
+    Ok((out, header_errors + corrected))
+}
+
+// The parity of the payload and how many bytes of the header were corrected.
+fn read_header(data: &[u8]) -> Option<(usize, usize)> {
+    let (header, errors) = correct_block(data.get(..HEADER_LENGTH)?, HEADER_PARITY)?;
+    let parity = header[MAGIC.len()] as usize;
+    (header[..MAGIC.len()] == MAGIC && check_parity(parity).is_ok()).then_some((parity, errors))
+}
+
+fn encode_blocks(data: &[u8], parity: usize) -> Vec<u8> {
+    let generator = generator(parity);
+    let mut out =
+        Vec::with_capacity(data.len() + data.len().div_ceil(BLOCK_SIZE - parity) * parity);
+    for block in data.chunks(BLOCK_SIZE - parity) {
+        let mut padded = block.to_vec();
+        padded.resize(block.len() + parity, 0);
+        out.extend_from_slice(block);
+        out.extend(poly_rem(&padded, &generator));
+    }
+    out
+}
+
+fn decode_blocks(data: &[u8], parity: usize) -> Result<(Vec<u8>, usize), String> {
+    let mut out = Vec::with_capacity(data.len());
+    let mut corrected = 0;
+    for (i, block) in data.chunks(BLOCK_SIZE).enumerate() {
+        if block.len() <= parity {
+            return Err(String::from("invalid payload length"));
+        }
+        let (block, errors) = correct_block(block, parity)
+            .ok_or_else(|| format!("unrecoverable payload, too many errors in block {}", i))?;
+        out.extend_from_slice(&block[..block.len() - parity]);
+        corrected += errors;
+    }
+    Ok((out, corrected))
+}
+
+fn correct_block(block: &[u8], parity: usize) -> Option<(Vec<u8>, usize)> {
+    let block_syndromes = syndromes(block, parity);
+    if block_syndromes.iter().all(|&s| s == 0) {
+        return Some((block.to_vec(), 0));
+    }
+    let locator = error_locator(&block_syndromes, parity)?;
+    let reversed: Vec<u8> = locator.iter().rev().copied().collect();
+    let positions = error_positions(&reversed, block.len())?;
+    let corrected = correct_errata(block, &block_syndromes, &positions);
+    // more errors than the code can handle may still yield a wrong "fix"
+    syndromes(&corrected, parity)
+        .iter()
+        .all(|&s| s == 0)
+        .then_some((corrected, positions.len()))
+}
+
+// With a leading zero, like the original, which the other steps rely on.
+fn syndromes(block: &[u8], parity: usize) -> Vec<u8> {
+    std::iter::once(0)
+        .chain((0..parity).map(|i| poly_eval(block, pow(2, i as i32))))
+        .collect()
+}
+
+// Berlekamp-Massey
+fn error_locator(syndromes: &[u8], parity: usize) -> Option<Vec<u8>> {
+    let mut locator = vec![1];
+    let mut old = vec![1];
+    let shift = syndromes.len() - parity;
+    for i in 0..parity {
+        let k = i + shift;
+        let mut delta = syndromes[k];
+        for j in 1..locator.len() {
+            delta ^= mul(locator[locator.len() - 1 - j], syndromes[k - j]);
+        }
+        old.push(0);
+        if delta != 0 {
+            if old.len() > locator.len() {
+                let new = poly_scale(&old, delta);
+                old = poly_scale(&locator, inverse(delta));
+                locator = new;
+            }
+            locator = poly_add(&locator, &poly_scale(&old, delta));
+        }
+    }
+    let start = locator.iter().position(|&c| c != 0)?;
+    let locator = locator.split_off(start);
+    ((locator.len() - 1) * 2 <= parity).then_some(locator)
+}
+
+// Chien search
+fn error_positions(locator: &[u8], len: usize) -> Option<Vec<usize>> {
+    let positions: Vec<usize> = (0..len)
+        .filter(|&i| poly_eval(locator, pow(2, i as i32)) == 0)
+        .map(|i| len - 1 - i)
+        .collect();
+    (positions.len() == locator.len() - 1).then_some(positions)
+}
+
+// Forney
+fn correct_errata(block: &[u8], syndromes: &[u8], positions: &[usize]) -> Vec<u8> {
+    let coef_positions: Vec<usize> = positions.iter().map(|p| block.len() - 1 - p).collect();
+    let locator = coef_positions.iter().fold(vec![1], |l, &i| {
+        poly_mul(&l, &poly_add(&[1], &[pow(2, i as i32), 0]))
+    });
+    let reversed: Vec<u8> = syndromes.iter().rev().copied().collect();
+    let mut divisor = vec![0; locator.len() + 1];
+    divisor[0] = 1;
+    let evaluator = poly_rem(&poly_mul(&reversed, &locator), &divisor);
+
+    let x: Vec<u8> = coef_positions
+        .iter()
+        .map(|&i| pow(2, -(BLOCK_SIZE as i32 - i as i32)))
+        .collect();
+    let mut errors = vec![0; block.len()];
+    for (i, &xi) in x.iter().enumerate() {
+        let xi_inv = inverse(xi);
+        let locator_prime = x
+            .iter()
+            .enumerate()
+            .filter(|&(j, _)| j != i)
+            .fold(1, |acc, (_, &xj)| mul(acc, 1 ^ mul(xi_inv, xj)));
+        let y = mul(xi, poly_eval(&evaluator, xi_inv));
+        errors[positions[i]] = div(y, locator_prime);
+    }
+    poly_add(block, &errors)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // deterministic noise, so the tests need no random crate
+    fn xorshift(state: &mut u32) -> u32 {
+        *state ^= *state << 13;
+        *state ^= *state >> 17;
+        *state ^= *state << 5;
+        *state
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let data: Vec<u8> = (0..600).map(|i| (i * 7) as u8).collect();
+        let encoded = encode(&data, 16).unwrap();
+        // 239 data bytes per block, so three blocks with the last one shortened
+        assert_eq!(encoded.len(), HEADER_LENGTH + 600 + 3 * 16);
+        assert_eq!(parity(&encoded), Some(16));
+        assert_eq!(parity(&data), None);
+        assert_eq!(decode(&encoded).unwrap(), (data, 0));
+    }
+
+    #[test]
+    fn test_corrects_errors() {
+        let data = b"I am a hidden message that should survive some damage".repeat(10);
+        let encoded = encode(&data, 20).unwrap();
+        let mut state = 0x2545f491;
+        for _ in 0..20 {
+            let mut damaged = encoded.clone();
+            // up to 4 wrong bytes in the header and 10 in each block
+            for _ in 0..4 {
+                let i = xorshift(&mut state) as usize % HEADER_LENGTH;
+                damaged[i] ^= (xorshift(&mut state) % 255 + 1) as u8;
+            }
+            for block in damaged[HEADER_LENGTH..].chunks_mut(BLOCK_SIZE) {
+                for _ in 0..10 {
+                    let i = xorshift(&mut state) as usize % block.len();
+                    block[i] ^= (xorshift(&mut state) % 255 + 1) as u8;
+                }
+            }
+            let (decoded, corrected) = decode(&damaged).unwrap();
+            assert_eq!(decoded, data);
+            assert!(corrected > 0 && corrected <= 34);
+        }
+    }
+
+    #[test]
+    fn test_too_many_errors() {
+        let encoded = encode(b"short message", 4).unwrap();
+        let mut damaged = encoded.clone();
+        for byte in &mut damaged[HEADER_LENGTH..HEADER_LENGTH + 5] {
+            *byte ^= 0xff;
+        }
+        assert_eq!(
+            decode(&damaged).unwrap_err(),
+            "unrecoverable payload, too many errors in block 0"
+        );
+        assert_eq!(
+            decode(&encoded[..HEADER_LENGTH + 4]).unwrap_err(),
+            "invalid payload length"
+        );
+        for byte in &mut damaged[..5] {
+            *byte ^= 0xff;
+        }
+        assert_eq!(
+            decode(&damaged).unwrap_err(),
+            "not an error corrected payload"
+        );
+    }
+
+    #[test]
+    fn test_invalid_parity() {
+        assert!(encode(b"data", 0).is_err());
+        assert!(encode(b"data", 255).is_err());
+        assert!(encode(b"data", 254).is_ok());
+    }
+}
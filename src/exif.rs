@@ -0,0 +1,972 @@
+use std::{collections::HashSet, fmt::Display, str::FromStr};
+
+use crate::{chunk::Chunk, chunk_type::ChunkType};
+
+// Tags that point to other IFDs. They are rebuilt when serializing, so they are
+// not kept as entries.
+const EXIF_POINTER: u16 = 0x8769;
+const GPS_POINTER: u16 = 0x8825;
+const INTEROP_POINTER: u16 = 0xa005;
+// The JPEG thumbnail of IFD1, also rebuilt when serializing.
+const THUMBNAIL_OFFSET: u16 = 0x0201;
+const THUMBNAIL_LENGTH: u16 = 0x0202;
+
+// Names of the common TIFF and EXIF tags, which share one number space.
+const TAGS: [(u16, &str); 35] = [
+    (0x010e, "ImageDescription"),
+    (0x010f, "Make"),
+    (0x0110, "Model"),
+    (0x0112, "Orientation"),
+    (0x011a, "XResolution"),
+    (0x011b, "YResolution"),
+    (0x0128, "ResolutionUnit"),
+    (0x0131, "Software"),
+    (0x0132, "DateTime"),
+    (0x013b, "Artist"),
+    (0x0213, "YCbCrPositioning"),
+    (0x8298, "Copyright"),
+    (0x829a, "ExposureTime"),
+    (0x829d, "FNumber"),
+    (0x8822, "ExposureProgram"),
+    (0x8827, "ISOSpeedRatings"),
+    (0x9000, "ExifVersion"),
+    (0x9003, "DateTimeOriginal"),
+    (0x9004, "DateTimeDigitized"),
+    (0x9010, "OffsetTime"),
+    (0x9201, "ShutterSpeedValue"),
+    (0x9202, "ApertureValue"),
+    (0x9209, "Flash"),
+    (0x920a, "FocalLength"),
+    (0x927c, "MakerNote"),
+    (0x9286, "UserComment"),
+    (0xa001, "ColorSpace"),
+    (0xa002, "PixelXDimension"),
+    (0xa003, "PixelYDimension"),
+    (0xa420, "ImageUniqueID"),
+    (0xa430, "CameraOwnerName"),
+    (0xa431, "BodySerialNumber"),
+    (0xa433, "LensMake"),
+    (0xa434, "LensModel"),
+    (0xa435, "LensSerialNumber"),
+];
+
+const GPS_TAGS: [(u16, &str); 12] = [
+    (0x0000, "GPSVersionID"),
+    (0x0001, "GPSLatitudeRef"),
+    (0x0002, "GPSLatitude"),
+    (0x0003, "GPSLongitudeRef"),
+    (0x0004, "GPSLongitude"),
+    (0x0005, "GPSAltitudeRef"),
+    (0x0006, "GPSAltitude"),
+    (0x0007, "GPSTimeStamp"),
+    (0x0010, "GPSImgDirectionRef"),
+    (0x0011, "GPSImgDirection"),
+    (0x0012, "GPSMapDatum"),
+    (0x001d, "GPSDateStamp"),
+];
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ByteOrder {
+    LittleEndian,
+    BigEndian,
+}
+
+impl ByteOrder {
+    fn u16(&self, bytes: &[u8]) -> u16 {
+        let bytes = [bytes[0], bytes[1]];
+        match self {
+            ByteOrder::LittleEndian => u16::from_le_bytes(bytes),
+            ByteOrder::BigEndian => u16::from_be_bytes(bytes),
+        }
+    }
+
+    fn u32(&self, bytes: &[u8]) -> u32 {
+        let bytes = [bytes[0], bytes[1], bytes[2], bytes[3]];
+        match self {
+            ByteOrder::LittleEndian => u32::from_le_bytes(bytes),
+            ByteOrder::BigEndian => u32::from_be_bytes(bytes),
+        }
+    }
+
+    fn u16_bytes(&self, value: u16) -> [u8; 2] {
+        match self {
+            ByteOrder::LittleEndian => value.to_le_bytes(),
+            ByteOrder::BigEndian => value.to_be_bytes(),
+        }
+    }
+
+    fn u32_bytes(&self, value: u32) -> [u8; 4] {
+        match self {
+            ByteOrder::LittleEndian => value.to_le_bytes(),
+            ByteOrder::BigEndian => value.to_be_bytes(),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum IfdKind {
+    // IFD0, the main image
+    Primary,
+    Exif,
+    Interop,
+    Gps,
+    // IFD1, the thumbnail
+    Thumbnail,
+}
+
+impl Display for IfdKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IfdKind::Primary => write!(f, "IFD0"),
+            IfdKind::Exif => write!(f, "Exif"),
+            IfdKind::Interop => write!(f, "Interop"),
+            IfdKind::Gps => write!(f, "GPS"),
+            IfdKind::Thumbnail => write!(f, "IFD1"),
+        }
+    }
+}
+
+// A tag with its value bytes, kept in the byte order of the file.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Entry {
+    pub tag: u16,
+    pub field_type: u16,
+    pub count: u32,
+    pub data: Vec<u8>,
+}
+
+impl Entry {
+    pub fn name(&self, kind: IfdKind) -> Option<&'static str> {
+        let tags: &[(u16, &str)] = match kind {
+            IfdKind::Gps => &GPS_TAGS,
+            IfdKind::Interop => &[(0x0001, "InteroperabilityIndex")],
+            _ => &TAGS,
+        };
+        tags.iter().find(|(t, _)| *t == self.tag).map(|(_, n)| *n)
+    }
+
+    pub fn value_to_string(&self, byte_order: ByteOrder) -> String {
+        let values: Vec<String> = match self.field_type {
+            // ASCII
+            2 => {
+                let text = self.data.split(|&b| b == 0).next().unwrap_or_default();
+                return String::from_utf8_lossy(text).into_owned();
+            }
+            // SHORT
+            3 => self
+                .data
+                .chunks_exact(2)
+                .map(|v| byte_order.u16(v).to_string())
+                .collect(),
+            // LONG
+            4 => self
+                .data
+                .chunks_exact(4)
+                .map(|v| byte_order.u32(v).to_string())
+                .collect(),
+            // RATIONAL and SRATIONAL
+            5 | 10 => self
+                .data
+                .chunks_exact(8)
+                .map(|v| {
+                    let (n, d) = (byte_order.u32(v), byte_order.u32(&v[4..]));
+                    match self.field_type {
+                        5 => format!("{}/{}", n, d),
+                        _ => format!("{}/{}", n as i32, d as i32),
+                    }
+                })
+                .collect(),
+            // SSHORT
+            8 => self
+                .data
+                .chunks_exact(2)
+                .map(|v| (byte_order.u16(v) as i16).to_string())
+                .collect(),
+            // SLONG
+            9 => self
+                .data
+                .chunks_exact(4)
+                .map(|v| (byte_order.u32(v) as i32).to_string())
+                .collect(),
+            // BYTE, SBYTE, UNDEFINED and the floating point types
+            _ if self.data.len() > 16 => return format!("{} bytes", self.data.len()),
+            _ => self.data.iter().map(|b| format!("{:02x}", b)).collect(),
+        };
+        values.join(" ")
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Ifd {
+    pub kind: IfdKind,
+    pub entries: Vec<Entry>,
+}
+
+// The TIFF structure stored in an eXIf chunk.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Exif {
+    pub byte_order: ByteOrder,
+    pub ifds: Vec<Ifd>,
+    pub thumbnail: Option<Vec<u8>>,
+}
+
+impl Exif {
+    pub fn ifd(&self, kind: IfdKind) -> Option<&Ifd> {
+        self.ifds.iter().find(|i| i.kind == kind)
+    }
+
+    // Lays the IFDs out anew. Tags holding offsets into the data, such as
+    // MakerNote, are not adjusted, parsed data is edited with ExifEditor.
+    pub fn as_bytes(&self) -> Vec<u8> {
+        Writer::new(self).write()
+    }
+
+    pub fn to_chunk(&self) -> Chunk {
+        Chunk::new(
+            ChunkType::from_str("eXIf").expect("valid chunk type"),
+            self.as_bytes(),
+        )
+    }
+}
+
+impl TryFrom<&[u8]> for Exif {
+    type Error = String;
+
+    fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
+        let byte_order = match value.get(..4) {
+            Some(b"II*\0") if value.len() >= 8 => ByteOrder::LittleEndian,
+            Some(b"MM\0*") if value.len() >= 8 => ByteOrder::BigEndian,
+            _ => return Err(String::from("invalid TIFF header")),
+        };
+        let mut reader = Reader {
+            data: value,
+            byte_order,
+            visited: HashSet::new(),
+        };
+
+        let mut ifds = Vec::new();
+        let (mut primary, next) = reader.read_ifd(byte_order.u32(&value[4..]))?;
+        let exif = take_pointer(&mut primary, EXIF_POINTER, byte_order);
+        let gps = take_pointer(&mut primary, GPS_POINTER, byte_order);
+        ifds.push(Ifd {
+            kind: IfdKind::Primary,
+            entries: primary,
+        });
+        if let Some(offset) = exif {
+            let (mut entries, _) = reader.read_ifd(offset)?;
+            let interop = take_pointer(&mut entries, INTEROP_POINTER, byte_order);
+            ifds.push(Ifd {
+                kind: IfdKind::Exif,
+                entries,
+            });
+            if let Some(offset) = interop {
+                let (entries, _) = reader.read_ifd(offset)?;
+                ifds.push(Ifd {
+                    kind: IfdKind::Interop,
+                    entries,
+                });
+            }
+        }
+        if let Some(offset) = gps {
+            let (entries, _) = reader.read_ifd(offset)?;
+            ifds.push(Ifd {
+                kind: IfdKind::Gps,
+                entries,
+            });
+        }
+
+        let mut thumbnail = None;
+        if next != 0 {
+            let (mut entries, _) = reader.read_ifd(next)?;
+            let offset = take_pointer(&mut entries, THUMBNAIL_OFFSET, byte_order);
+            let length = take_pointer(&mut entries, THUMBNAIL_LENGTH, byte_order);
+            if let (Some(offset), Some(length)) = (offset, length) {
+                let range = offset as usize..offset as usize + length as usize;
+                let data = value
+                    .get(range)
+                    .ok_or_else(|| String::from("truncated thumbnail"))?;
+                thumbnail = Some(data.to_vec());
+            }
+            ifds.push(Ifd {
+                kind: IfdKind::Thumbnail,
+                entries,
+            });
+        }
+
+        Ok(Exif {
+            byte_order,
+            ifds,
+            thumbnail,
+        })
+    }
+}
+
+// One line per tag, "IFD0 Make: Canon".
+impl Display for Exif {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut lines = Vec::new();
+        for ifd in &self.ifds {
+            for entry in &ifd.entries {
+                let name = match entry.name(ifd.kind) {
+                    Some(name) => String::from(name),
+                    None => format!("0x{:04x}", entry.tag),
+                };
+                lines.push(format!(
+                    "{} {}: {}",
+                    ifd.kind,
+                    name,
+                    entry.value_to_string(self.byte_order)
+                ));
+            }
+        }
+        if let Some(thumbnail) = &self.thumbnail {
+            lines.push(format!("IFD1 thumbnail: {} bytes", thumbnail.len()));
+        }
+        write!(f, "{}", lines.join("\n"))
+    }
+}
+
+impl FromStr for IfdKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "IFD0" => Ok(IfdKind::Primary),
+            "Exif" => Ok(IfdKind::Exif),
+            "Interop" => Ok(IfdKind::Interop),
+            "GPS" => Ok(IfdKind::Gps),
+            "IFD1" => Ok(IfdKind::Thumbnail),
+            _ => Err(format!(
+                "unknown IFD {}, expected IFD0, Exif, Interop, GPS or IFD1",
+                s
+            )),
+        }
+    }
+}
+
+// A tag selected by name or number, in one IFD or, when `ifd` is None, in IFD0,
+// Exif and IFD1, which share the TIFF and EXIF number space.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Tag {
+    pub tag: u16,
+    pub ifd: Option<IfdKind>,
+}
+
+impl Tag {
+    fn matches(&self, kind: IfdKind, tag: u16) -> bool {
+        let in_ifd = match self.ifd {
+            Some(ifd) => ifd == kind,
+            None => !matches!(kind, IfdKind::Gps | IfdKind::Interop),
+        };
+        in_ifd && tag == self.tag
+    }
+}
+
+// Parses a tag name such as "Model" or "GPSLatitude", or a number such as
+// "0x0110", optionally prefixed by an IFD as in "GPS:0x0002" or "IFD1:Make".
+impl FromStr for Tag {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (ifd, name) = match s.split_once(':') {
+            Some((ifd, name)) => (Some(IfdKind::from_str(ifd)?), name),
+            None => (None, s),
+        };
+        if let Some(hex) = name.strip_prefix("0x") {
+            return u16::from_str_radix(hex, 16)
+                .map(|tag| Tag { tag, ifd })
+                .map_err(|_| String::from("invalid tag number"));
+        }
+        let find = |tags: &[(u16, &str)]| tags.iter().find(|(_, n)| *n == name).map(|(t, _)| *t);
+        match (find(&TAGS), find(&GPS_TAGS)) {
+            (Some(tag), _) if ifd != Some(IfdKind::Gps) => Ok(Tag { tag, ifd }),
+            (_, Some(tag)) if ifd.is_none_or(|i| i == IfdKind::Gps) => Ok(Tag {
+                tag,
+                ifd: Some(IfdKind::Gps),
+            }),
+            _ => Err(format!("unknown tag {}", s)),
+        }
+    }
+}
+
+impl Display for Tag {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.ifd {
+            Some(ifd) => write!(f, "{}:0x{:04x}", ifd, self.tag),
+            None => write!(f, "0x{:04x}", self.tag),
+        }
+    }
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    byte_order: ByteOrder,
+    // offsets already read, so that IFDs pointing at each other cannot loop
+    visited: HashSet<u32>,
+}
+
+impl Reader<'_> {
+    // Returns the entries of the IFD at `offset` and the offset of the next IFD.
+    fn read_ifd(&mut self, offset: u32) -> Result<(Vec<Entry>, u32), String> {
+        if !self.visited.insert(offset) {
+            return Err(String::from("IFD loop"));
+        }
+        let truncated = || String::from("truncated IFD");
+        let start = offset as usize;
+        let count = self
+            .byte_order
+            .u16(self.data.get(start..start + 2).ok_or_else(truncated)?);
+        let end = start + 2 + count as usize * 12;
+        let bytes = self.data.get(start + 2..end + 4).ok_or_else(truncated)?;
+
+        let mut entries = Vec::with_capacity(count as usize);
+        for raw in bytes[..bytes.len() - 4].chunks_exact(12) {
+            let field_type = self.byte_order.u16(&raw[2..]);
+            let count = self.byte_order.u32(&raw[4..]);
+            let size = type_size(field_type)
+                .and_then(|s| s.checked_mul(count as usize))
+                .ok_or_else(|| String::from("invalid IFD entry"))?;
+            let data = if size <= 4 {
+                &raw[8..8 + size]
+            } else {
+                let value_offset = self.byte_order.u32(&raw[8..]) as usize;
+                self.data
+                    .get(value_offset..value_offset + size)
+                    .ok_or_else(|| String::from("truncated IFD entry"))?
+            };
+            entries.push(Entry {
+                tag: self.byte_order.u16(raw),
+                field_type,
+                count,
+                data: data.to_vec(),
+            });
+        }
+        Ok((entries, self.byte_order.u32(&bytes[bytes.len() - 4..])))
+    }
+}
+
+fn take_pointer(entries: &mut Vec<Entry>, tag: u16, byte_order: ByteOrder) -> Option<u32> {
+    let index = entries.iter().position(|e| e.tag == tag)?;
+    pointer_value(&entries.remove(index).data, byte_order)
+}
+
+// An IFD pointer is a SHORT or LONG, anything else is not followed.
+fn pointer_value(data: &[u8], byte_order: ByteOrder) -> Option<u32> {
+    match data.len() {
+        2 => Some(byte_order.u16(data) as u32),
+        4 => Some(byte_order.u32(data)),
+        _ => None,
+    }
+}
+
+fn type_size(field_type: u16) -> Option<usize> {
+    match field_type {
+        1 | 2 | 6 | 7 => Some(1),
+        3 | 8 => Some(2),
+        4 | 9 | 11 | 13 => Some(4),
+        5 | 10 | 12 => Some(8),
+        _ => None,
+    }
+}
+
+// Lays the IFDs out one after the other, each followed by the values that do
+// not fit in its entries, and the thumbnail last.
+struct Writer<'a> {
+    exif: &'a Exif,
+    ifds: Vec<(IfdKind, Vec<Entry>)>,
+}
+
+impl<'a> Writer<'a> {
+    fn new(exif: &'a Exif) -> Writer<'a> {
+        let order = [
+            IfdKind::Primary,
+            IfdKind::Exif,
+            IfdKind::Interop,
+            IfdKind::Gps,
+            IfdKind::Thumbnail,
+        ];
+        let mut ifds: Vec<(IfdKind, Vec<Entry>)> = Vec::new();
+        for kind in order {
+            // an Interop IFD can only be reached through the Exif IFD
+            if kind == IfdKind::Interop && exif.ifd(IfdKind::Exif).is_none() {
+                continue;
+            }
+            match exif.ifd(kind) {
+                Some(ifd) => ifds.push((kind, ifd.entries.clone())),
+                None if kind == IfdKind::Primary => ifds.push((kind, Vec::new())),
+                None if kind == IfdKind::Thumbnail && exif.thumbnail.is_some() => {
+                    ifds.push((kind, Vec::new()))
+                }
+                None => {}
+            }
+        }
+
+        // placeholders for the pointers, filled in once the offsets are known
+        let has = |kind| ifds.iter().any(|(k, _)| *k == kind);
+        let pointers: Vec<(IfdKind, u16)> = [
+            (IfdKind::Primary, EXIF_POINTER, has(IfdKind::Exif)),
+            (IfdKind::Primary, GPS_POINTER, has(IfdKind::Gps)),
+            (IfdKind::Exif, INTEROP_POINTER, has(IfdKind::Interop)),
+            (
+                IfdKind::Thumbnail,
+                THUMBNAIL_OFFSET,
+                exif.thumbnail.is_some(),
+            ),
+            (
+                IfdKind::Thumbnail,
+                THUMBNAIL_LENGTH,
+                exif.thumbnail.is_some(),
+            ),
+        ]
+        .into_iter()
+        .filter(|(_, _, present)| *present)
+        .map(|(kind, tag, _)| (kind, tag))
+        .collect();
+        for (kind, entries) in &mut ifds {
+            for (_, tag) in pointers.iter().filter(|(k, _)| k == kind) {
+                entries.push(Entry {
+                    tag: *tag,
+                    field_type: 4,
+                    count: 1,
+                    data: vec![0; 4],
+                });
+            }
+            entries.sort_by_key(|e| e.tag);
+        }
+        Writer { exif, ifds }
+    }
+
+    fn write(mut self) -> Vec<u8> {
+        let byte_order = self.exif.byte_order;
+        let mut offsets = Vec::new();
+        let mut offset = 8u32;
+        for (_, entries) in &self.ifds {
+            offsets.push(offset);
+            offset += ifd_size(entries);
+        }
+        let thumbnail_offset = offset;
+
+        let offset_of = |kind| {
+            self.ifds
+                .iter()
+                .position(|(k, _)| *k == kind)
+                .map(|i| offsets[i])
+        };
+        let thumbnail_length = self.exif.thumbnail.as_ref().map(|t| t.len() as u32);
+        let next_ifd = offset_of(IfdKind::Thumbnail).unwrap_or(0);
+        let pointers: Vec<Option<u32>> = self
+            .ifds
+            .iter()
+            .flat_map(|(kind, entries)| {
+                entries.iter().map(move |entry| match (kind, entry.tag) {
+                    (IfdKind::Primary, EXIF_POINTER) => offset_of(IfdKind::Exif),
+                    (IfdKind::Primary, GPS_POINTER) => offset_of(IfdKind::Gps),
+                    (IfdKind::Exif, INTEROP_POINTER) => offset_of(IfdKind::Interop),
+                    (IfdKind::Thumbnail, THUMBNAIL_OFFSET) => Some(thumbnail_offset),
+                    (IfdKind::Thumbnail, THUMBNAIL_LENGTH) => thumbnail_length,
+                    _ => None,
+                })
+            })
+            .collect();
+        let mut pointers = pointers.into_iter();
+        for (_, entries) in &mut self.ifds {
+            for entry in entries.iter_mut() {
+                if let Some(value) = pointers.next().flatten() {
+                    entry.data = byte_order.u32_bytes(value).to_vec();
+                }
+            }
+        }
+
+        let mut bytes = match byte_order {
+            ByteOrder::LittleEndian => b"II*\0".to_vec(),
+            ByteOrder::BigEndian => b"MM\0*".to_vec(),
+        };
+        bytes.extend_from_slice(&byte_order.u32_bytes(8));
+        for (i, (kind, entries)) in self.ifds.iter().enumerate() {
+            let next = match kind {
+                IfdKind::Primary => next_ifd,
+                _ => 0,
+            };
+            write_ifd(&mut bytes, entries, offsets[i], next, byte_order);
+        }
+        if let Some(thumbnail) = &self.exif.thumbnail {
+            bytes.extend_from_slice(thumbnail);
+        }
+        bytes
+    }
+}
+
+// An eXIf chunk edited in place. Removed entries are cut from their IFD and
+// their values zeroed, everything else keeps its offset, so MakerNote, SubIFDs
+// and other tags holding offsets into the data stay valid.
+pub struct ExifEditor {
+    data: Vec<u8>,
+    byte_order: ByteOrder,
+}
+
+impl ExifEditor {
+    pub fn new(data: &[u8]) -> Result<ExifEditor, String> {
+        // checks every IFD and value is in bounds
+        let exif = Exif::try_from(data)?;
+        Ok(ExifEditor {
+            data: data.to_vec(),
+            byte_order: exif.byte_order,
+        })
+    }
+
+    pub fn remove_gps(&mut self) -> bool {
+        let Some(gps) = self.ifd_offset(IfdKind::Gps) else {
+            return false;
+        };
+        self.clear_ifd(gps);
+        let primary = self.ifd_offset(IfdKind::Primary).expect("IFD0");
+        self.remove_entries(primary, |tag| tag == GPS_POINTER);
+        true
+    }
+
+    pub fn remove_thumbnail(&mut self) -> bool {
+        let Some(thumbnail) = self.ifd_offset(IfdKind::Thumbnail) else {
+            return false;
+        };
+        let offset = self.pointer(thumbnail, THUMBNAIL_OFFSET);
+        let length = self.pointer(thumbnail, THUMBNAIL_LENGTH);
+        if let (Some(offset), Some(length)) = (offset, length) {
+            self.zero(offset as usize..offset as usize + length as usize);
+        }
+        self.clear_ifd(thumbnail);
+        let primary = self.ifd_offset(IfdKind::Primary).expect("IFD0");
+        let next = self.next_ifd(primary).expect("IFD0");
+        self.zero(next..next + 4);
+        true
+    }
+
+    // Removes `tag` from the IFDs it selects and returns how many entries were
+    // removed.
+    pub fn remove_tag(&mut self, tag: &Tag) -> usize {
+        let kinds = [
+            IfdKind::Primary,
+            IfdKind::Exif,
+            IfdKind::Interop,
+            IfdKind::Gps,
+            IfdKind::Thumbnail,
+        ];
+        let mut removed = 0;
+        for kind in kinds {
+            if let Some(offset) = self.ifd_offset(kind) {
+                removed += self.remove_entries(offset, |t| tag.matches(kind, t));
+            }
+        }
+        removed
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.data
+    }
+
+    pub fn to_chunk(&self) -> Chunk {
+        Chunk::new(
+            ChunkType::from_str("eXIf").expect("valid chunk type"),
+            self.data.clone(),
+        )
+    }
+
+    fn ifd_offset(&self, kind: IfdKind) -> Option<usize> {
+        let offset = match kind {
+            IfdKind::Primary => self.u32_at(4),
+            IfdKind::Exif => self.pointer(self.ifd_offset(IfdKind::Primary)?, EXIF_POINTER),
+            IfdKind::Gps => self.pointer(self.ifd_offset(IfdKind::Primary)?, GPS_POINTER),
+            IfdKind::Interop => self.pointer(self.ifd_offset(IfdKind::Exif)?, INTEROP_POINTER),
+            IfdKind::Thumbnail => self.u32_at(self.next_ifd(self.ifd_offset(IfdKind::Primary)?)?),
+        };
+        offset.filter(|&o| o != 0).map(|o| o as usize)
+    }
+
+    fn u32_at(&self, offset: usize) -> Option<u32> {
+        let bytes = self.data.get(offset..offset + 4)?;
+        Some(self.byte_order.u32(bytes))
+    }
+
+    // The entries of the IFD at `ifd`, or None when the IFD is out of bounds.
+    fn entries(&self, ifd: usize) -> Option<Vec<Vec<u8>>> {
+        let count = self.byte_order.u16(self.data.get(ifd..ifd + 2)?) as usize;
+        let table = self.data.get(ifd + 2..ifd + 2 + count * 12 + 4)?;
+        Some(table[..count * 12].chunks(12).map(<[u8]>::to_vec).collect())
+    }
+
+    // Where the offset of the IFD following the one at `ifd` is stored.
+    fn next_ifd(&self, ifd: usize) -> Option<usize> {
+        Some(ifd + 2 + self.entries(ifd)?.len() * 12)
+    }
+
+    // Decodes the pointer the way the parser does, so only IFDs it checked are
+    // ever followed.
+    fn pointer(&self, ifd: usize, tag: u16) -> Option<u32> {
+        let entry = self
+            .entries(ifd)?
+            .into_iter()
+            .find(|e| self.byte_order.u16(e) == tag)?;
+        let field_type = self.byte_order.u16(&entry[2..]);
+        let count = self.byte_order.u32(&entry[4..]) as usize;
+        let size = type_size(field_type)?.checked_mul(count)?;
+        pointer_value(entry.get(8..8 + size)?, self.byte_order)
+    }
+
+    // Where the value of an entry is stored when it does not fit in the entry.
+    fn value_range(&self, entry: &[u8]) -> Option<std::ops::Range<usize>> {
+        let field_type = self.byte_order.u16(&entry[2..]);
+        let count = self.byte_order.u32(&entry[4..]) as usize;
+        let size = type_size(field_type)? * count;
+        let offset = self.byte_order.u32(&entry[8..]) as usize;
+        (size > 4).then_some(offset..offset + size)
+    }
+
+    fn zero(&mut self, range: std::ops::Range<usize>) {
+        if let Some(bytes) = self.data.get_mut(range) {
+            bytes.fill(0);
+        }
+    }
+
+    // Cuts the entries whose tag matches out of the IFD at `ifd`, moving the
+    // others and the next IFD offset up. Returns how many were removed.
+    fn remove_entries(&mut self, ifd: usize, remove: impl Fn(u16) -> bool) -> usize {
+        let Some(entries) = self.entries(ifd) else {
+            return 0;
+        };
+        let count = entries.len();
+        let mut kept = Vec::new();
+        for entry in entries {
+            if remove(self.byte_order.u16(&entry)) {
+                if let Some(range) = self.value_range(&entry) {
+                    self.zero(range);
+                }
+            } else {
+                kept.push(entry);
+            }
+        }
+        let removed = count - kept.len();
+        if removed == 0 {
+            return 0;
+        }
+        let next = ifd + 2 + count * 12;
+        let next_ifd = self.data[next..next + 4].to_vec();
+        let mut table = self.byte_order.u16_bytes(kept.len() as u16).to_vec();
+        table.extend(kept.concat());
+        table.extend_from_slice(&next_ifd);
+        table.resize(next + 4 - ifd, 0);
+        self.data[ifd..next + 4].copy_from_slice(&table);
+        removed
+    }
+
+    // Zeroes an IFD and the values of its entries.
+    fn clear_ifd(&mut self, ifd: usize) {
+        let Some(entries) = self.entries(ifd) else {
+            return;
+        };
+        for entry in &entries {
+            if let Some(range) = self.value_range(entry) {
+                self.zero(range);
+            }
+        }
+        self.zero(ifd..ifd + 2 + entries.len() * 12 + 4);
+    }
+}
+
+fn ifd_size(entries: &[Entry]) -> u32 {
+    let values: usize = entries
+        .iter()
+        .filter(|e| e.data.len() > 4)
+        .map(|e| e.data.len().next_multiple_of(2))
+        .sum();
+    (2 + entries.len() * 12 + 4 + values) as u32
+}
+
+fn write_ifd(bytes: &mut Vec<u8>, entries: &[Entry], offset: u32, next: u32, order: ByteOrder) {
+    let mut value_offset = offset + 2 + entries.len() as u32 * 12 + 4;
+    let mut values = Vec::new();
+    bytes.extend_from_slice(&order.u16_bytes(entries.len() as u16));
+    for entry in entries {
+        bytes.extend_from_slice(&order.u16_bytes(entry.tag));
+        bytes.extend_from_slice(&order.u16_bytes(entry.field_type));
+        bytes.extend_from_slice(&order.u32_bytes(entry.count));
+        if entry.data.len() <= 4 {
+            let mut inline = entry.data.clone();
+            inline.resize(4, 0);
+            bytes.extend_from_slice(&inline);
+        } else {
+            bytes.extend_from_slice(&order.u32_bytes(value_offset));
+            values.extend_from_slice(&entry.data);
+            // values start on a word boundary
+            if entry.data.len() % 2 == 1 {
+                values.push(0);
+            }
+            value_offset += entry.data.len().next_multiple_of(2) as u32;
+        }
+    }
+    bytes.extend_from_slice(&order.u32_bytes(next));
+    bytes.extend_from_slice(&values);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ascii(tag: u16, value: &str) -> Entry {
+        let mut data = value.as_bytes().to_vec();
+        data.push(0);
+        Entry {
+            tag,
+            field_type: 2,
+            count: data.len() as u32,
+            data,
+        }
+    }
+
+    fn rational(tag: u16, values: &[(u32, u32)], order: ByteOrder) -> Entry {
+        Entry {
+            tag,
+            field_type: 5,
+            count: values.len() as u32,
+            data: values
+                .iter()
+                .flat_map(|(n, d)| [order.u32_bytes(*n), order.u32_bytes(*d)].concat())
+                .collect(),
+        }
+    }
+
+    fn testing_exif(byte_order: ByteOrder) -> Exif {
+        Exif {
+            byte_order,
+            ifds: vec![
+                Ifd {
+                    kind: IfdKind::Primary,
+                    entries: vec![ascii(0x010f, "Canon"), ascii(0x0110, "EOS")],
+                },
+                Ifd {
+                    kind: IfdKind::Exif,
+                    entries: vec![ascii(0xa431, "123456789")],
+                },
+                Ifd {
+                    kind: IfdKind::Gps,
+                    entries: vec![
+                        ascii(0x0001, "N"),
+                        rational(0x0002, &[(52, 1), (22, 1), (1234, 100)], byte_order),
+                    ],
+                },
+                Ifd {
+                    kind: IfdKind::Thumbnail,
+                    entries: vec![],
+                },
+            ],
+            thumbnail: Some(b"\xff\xd8jpeg".to_vec()),
+        }
+    }
+
+    #[test]
+    fn test_round_trip_both_byte_orders() {
+        for byte_order in [ByteOrder::LittleEndian, ByteOrder::BigEndian] {
+            let exif = testing_exif(byte_order);
+            let bytes = exif.as_bytes();
+            let parsed = Exif::try_from(&bytes[..]).unwrap();
+            assert_eq!(parsed, exif);
+            assert_eq!(parsed.as_bytes(), bytes);
+        }
+    }
+
+    #[test]
+    fn test_display() {
+        let exif = testing_exif(ByteOrder::BigEndian);
+        assert_eq!(
+            exif.to_string(),
+            "IFD0 Make: Canon\nIFD0 Model: EOS\nExif BodySerialNumber: 123456789\n\
+             GPS GPSLatitudeRef: N\nGPS GPSLatitude: 52/1 22/1 1234/100\n\
+             IFD1 thumbnail: 6 bytes"
+        );
+    }
+
+    #[test]
+    fn test_remove_gps_and_tags() {
+        let exif = testing_exif(ByteOrder::LittleEndian);
+        let mut editor = ExifEditor::new(&exif.as_bytes()).unwrap();
+        assert!(editor.remove_gps());
+        assert_eq!(
+            editor.remove_tag(&Tag::from_str("BodySerialNumber").unwrap()),
+            1
+        );
+        assert_eq!(editor.remove_tag(&Tag::from_str("0x0110").unwrap()), 1);
+
+        let parsed = Exif::try_from(&editor.into_bytes()[..]).unwrap();
+        assert!(parsed.ifd(IfdKind::Gps).is_none());
+        assert_eq!(parsed.ifd(IfdKind::Primary).unwrap().entries.len(), 1);
+        assert!(parsed.ifd(IfdKind::Exif).unwrap().entries.is_empty());
+        assert_eq!(parsed.thumbnail, exif.thumbnail);
+    }
+
+    #[test]
+    fn test_remove_keeps_offsets() {
+        let mut exif = testing_exif(ByteOrder::BigEndian);
+        let maker_note = Entry {
+            tag: 0x927c,
+            field_type: 7,
+            count: 8,
+            data: b"Canon\0\0\0".to_vec(),
+        };
+        exif.ifds[1].entries.push(maker_note.clone());
+        let bytes = exif.as_bytes();
+        let position = |bytes: &[u8]| bytes.windows(8).position(|w| w == maker_note.data);
+        let mut editor = ExifEditor::new(&bytes).unwrap();
+        assert!(editor.remove_thumbnail());
+        assert_eq!(editor.remove_tag(&Tag::from_str("Make").unwrap()), 1);
+
+        let edited = editor.into_bytes();
+        assert_eq!(edited.len(), bytes.len());
+        assert_eq!(position(&edited), position(&bytes));
+        let parsed = Exif::try_from(&edited[..]).unwrap();
+        let entries = &parsed.ifd(IfdKind::Exif).unwrap().entries;
+        assert!(entries.contains(&maker_note));
+        assert!(parsed.ifd(IfdKind::Thumbnail).is_none());
+        assert!(parsed.thumbnail.is_none());
+    }
+
+    #[test]
+    fn test_unfollowed_pointer() {
+        // an Exif pointer of two LONGs, which the parser does not follow
+        let mut bytes = b"MM\0*\0\0\0\x08\0\x01".to_vec();
+        bytes.extend_from_slice(&[0x87, 0x69, 0, 4, 0, 0, 0, 2, 0, 0, 0, 26, 0, 0, 0, 0]);
+        bytes.extend_from_slice(&[0xff, 0xff, 0, 0, 0, 0, 0, 0]);
+        let mut editor = ExifEditor::new(&bytes).unwrap();
+        assert_eq!(editor.remove_tag(&Tag::from_str("Make").unwrap()), 0);
+        assert!(!editor.remove_gps());
+        assert!(!editor.remove_thumbnail());
+        assert_eq!(editor.into_bytes(), bytes);
+    }
+
+    #[test]
+    fn test_tag_syntax() {
+        let latitude = Tag {
+            tag: 0x0002,
+            ifd: Some(IfdKind::Gps),
+        };
+        assert_eq!(Tag::from_str("GPS:0x0002"), Ok(latitude));
+        assert_eq!(Tag::from_str("GPSLatitude"), Ok(latitude));
+        assert_eq!(Tag::from_str("GPS:GPSLatitude"), Ok(latitude));
+        assert!(Tag::from_str("IFD0:GPSLatitude").is_err());
+        assert!(Tag::from_str("Maker:0x0002").is_err());
+        assert_eq!(latitude.to_string(), "GPS:0x0002");
+
+        let mut editor = ExifEditor::new(&testing_exif(ByteOrder::BigEndian).as_bytes()).unwrap();
+        assert_eq!(editor.remove_tag(&Tag::from_str("0x0002").unwrap()), 0);
+        assert_eq!(editor.remove_tag(&latitude), 1);
+        let parsed = Exif::try_from(&editor.into_bytes()[..]).unwrap();
+        assert_eq!(parsed.ifd(IfdKind::Gps).unwrap().entries.len(), 1);
+    }
+
+    #[test]
+    fn test_invalid_exif() {
+        assert!(Exif::try_from(&b"II*\0"[..]).is_err());
+        assert!(Exif::try_from(&b"XX*\0\x08\0\0\0"[..]).is_err());
+        // IFD0 points at itself as the next IFD
+        let looping = b"II*\0\x08\0\0\0\0\0\x08\0\0\0";
+        assert!(Exif::try_from(&looping[..]).is_err_and(|e| e == "IFD loop"));
+        assert!(Tag::from_str("NotATag").is_err());
+    }
+}
@@ -0,0 +1,440 @@
+use std::io::{Read, Write};
+
+use flate2::{read::ZlibDecoder, write::ZlibEncoder, Compression};
+
+use crate::png::Png;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ColorType {
+    Grayscale = 0,
+    Rgb = 2,
+    Indexed = 3,
+    GrayscaleAlpha = 4,
+    Rgba = 6,
+}
+
+impl ColorType {
+    pub fn channels(&self) -> usize {
+        match self {
+            ColorType::Grayscale | ColorType::Indexed => 1,
+            ColorType::GrayscaleAlpha => 2,
+            ColorType::Rgb => 3,
+            ColorType::Rgba => 4,
+        }
+    }
+
+    fn allowed_bit_depths(&self) -> &'static [u8] {
+        match self {
+            ColorType::Grayscale => &[1, 2, 4, 8, 16],
+            ColorType::Indexed => &[1, 2, 4, 8],
+            _ => &[8, 16],
+        }
+    }
+}
+
+impl TryFrom<u8> for ColorType {
+    type Error = String;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(ColorType::Grayscale),
+            2 => Ok(ColorType::Rgb),
+            3 => Ok(ColorType::Indexed),
+            4 => Ok(ColorType::GrayscaleAlpha),
+            6 => Ok(ColorType::Rgba),
+            _ => Err(String::from("invalid color type")),
+        }
+    }
+}
+
+// The contents of the IHDR chunk.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct ImageHeader {
+    pub width: u32,
+    pub height: u32,
+    pub bit_depth: u8,
+    pub color_type: ColorType,
+    pub interlaced: bool,
+}
+
+impl ImageHeader {
+    pub fn from_png(png: &Png) -> Result<ImageHeader, String> {
+        match png.chunk_by_type("IHDR") {
+            Some(chunk) => ImageHeader::try_from(chunk.data()),
+            None => Err(String::from("missing IHDR chunk")),
+        }
+    }
+
+    pub fn bits_per_pixel(&self) -> usize {
+        self.color_type.channels() * self.bit_depth as usize
+    }
+
+    // Distance in bytes to the corresponding byte of the previous pixel, used by
+    // the filters. Pixels smaller than a byte use 1.
+    pub fn filter_distance(&self) -> usize {
+        self.bits_per_pixel().div_ceil(8)
+    }
+
+    pub fn stride(&self) -> usize {
+        (self.width as usize * self.bits_per_pixel()).div_ceil(8)
+    }
+
+    // Size of the filtered image data, every row with its filter type byte.
+    pub fn data_size(&self) -> usize {
+        let passes = match self.interlaced {
+            true => adam7_passes(self),
+            false => vec![*self],
+        };
+        passes
+            .iter()
+            .map(|p| (p.height as usize).saturating_mul(1 + p.stride()))
+            .fold(0, usize::saturating_add)
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        [
+            self.width.to_be_bytes().to_vec(),
+            self.height.to_be_bytes().to_vec(),
+            vec![
+                self.bit_depth,
+                self.color_type as u8,
+                0,
+                0,
+                self.interlaced as u8,
+            ],
+        ]
+        .concat()
+    }
+}
+
+impl TryFrom<&[u8]> for ImageHeader {
+    type Error = String;
+
+    fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
+        if value.len() != 13 {
+            return Err(String::from("invalid IHDR length"));
+        }
+        let width = u32::from_be_bytes(core::array::from_fn(|i| value[i]));
+        let height = u32::from_be_bytes(core::array::from_fn(|i| value[i + 4]));
+        let bit_depth = value[8];
+        let color_type = ColorType::try_from(value[9])?;
+        if width == 0 || height == 0 {
+            return Err(String::from("invalid image size"));
+        }
+        if !color_type.allowed_bit_depths().contains(&bit_depth) {
+            return Err(String::from("invalid bit depth"));
+        }
+        if value[10] != 0 || value[11] != 0 || value[12] > 1 {
+            return Err(String::from(
+                "unsupported compression, filter or interlace method",
+            ));
+        }
+        Ok(ImageHeader {
+            width,
+            height,
+            bit_depth,
+            color_type,
+            interlaced: value[12] == 1,
+        })
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum FilterStrategy {
+    None,
+    Sub,
+    Up,
+    Average,
+    Paeth,
+    // picks the filter with the smallest sum of absolute values for each row
+    Adaptive,
+}
+
+impl FilterStrategy {
+    pub const ALL: [FilterStrategy; 6] = [
+        FilterStrategy::None,
+        FilterStrategy::Sub,
+        FilterStrategy::Up,
+        FilterStrategy::Average,
+        FilterStrategy::Paeth,
+        FilterStrategy::Adaptive,
+    ];
+}
+
+// Concatenates and inflates the data of every IDAT chunk, which cannot be much
+// larger than the size IHDR gives.
+pub fn inflate_image_data(png: &Png) -> Result<Vec<u8>, String> {
+    let header = ImageHeader::from_png(png)?;
+    let compressed: Vec<u8> = png
+        .chunks()
+        .iter()
+        .filter(|c| c.chunk_type().to_string() == "IDAT")
+        .flat_map(|c| c.data().iter().copied())
+        .collect();
+    inflate(&compressed, header.data_size().saturating_add(1024))
+}
+
+// Inflates a zlib stream of at most `limit` bytes, so that a small crafted
+// stream cannot expand into gigabytes.
+pub fn inflate(data: &[u8], limit: usize) -> Result<Vec<u8>, String> {
+    let mut inflated = Vec::new();
+    ZlibDecoder::new(data)
+        .take(limit as u64 + 1)
+        .read_to_end(&mut inflated)
+        .map_err(|_| String::from("invalid zlib stream"))?;
+    if inflated.len() > limit {
+        return Err(format!("inflated data is larger than {} bytes", limit));
+    }
+    Ok(inflated)
+}
+
+pub fn deflate(data: &[u8], level: u32) -> Vec<u8> {
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::new(level));
+    encoder
+        .write_all(data)
+        .expect("writing to a Vec never fails");
+    encoder.finish().expect("writing to a Vec never fails")
+}
+
+// Turns filtered scanlines (a filter type byte followed by `stride` bytes per
+// row) back into raw scanlines without the filter bytes.
+pub fn unfilter(data: &[u8], header: &ImageHeader) -> Result<Vec<u8>, String> {
+    let stride = header.stride();
+    let distance = header.filter_distance();
+    let rows = header.height as usize;
+    if data.len() != rows * (stride + 1) {
+        return Err(String::from("invalid image data length"));
+    }
+
+    let mut raw = vec![0u8; rows * stride];
+    for row in 0..rows {
+        let filter_type = data[row * (stride + 1)];
+        let line = &data[row * (stride + 1) + 1..(row + 1) * (stride + 1)];
+        let (previous, current) = raw.split_at_mut(row * stride);
+        let previous = if row == 0 {
+            None
+        } else {
+            Some(&previous[(row - 1) * stride..])
+        };
+        let current = &mut current[..stride];
+        for i in 0..stride {
+            let a = if i >= distance {
+                current[i - distance]
+            } else {
+                0
+            };
+            let b = previous.map_or(0, |p| p[i]);
+            let c = match previous {
+                Some(p) if i >= distance => p[i - distance],
+                _ => 0,
+            };
+            current[i] = match filter_type {
+                0 => line[i],
+                1 => line[i].wrapping_add(a),
+                2 => line[i].wrapping_add(b),
+                3 => line[i].wrapping_add(((a as u16 + b as u16) / 2) as u8),
+                4 => line[i].wrapping_add(paeth(a, b, c)),
+                _ => return Err(String::from("invalid filter type")),
+            };
+        }
+    }
+    Ok(raw)
+}
+
+// The reduced images of the Adam7 passes in the order they are stored, as
+// headers of non interlaced images. Empty passes are left out.
+pub fn adam7_passes(header: &ImageHeader) -> Vec<ImageHeader> {
+    // starting column, starting row, column step and row step of each pass
+    const PASSES: [(u32, u32, u32, u32); 7] = [
+        (0, 0, 8, 8),
+        (4, 0, 8, 8),
+        (0, 4, 4, 8),
+        (2, 0, 4, 4),
+        (0, 2, 2, 4),
+        (1, 0, 2, 2),
+        (0, 1, 1, 2),
+    ];
+    PASSES
+        .iter()
+        .map(|&(x, y, dx, dy)| ImageHeader {
+            width: header.width.saturating_sub(x).div_ceil(dx),
+            height: header.height.saturating_sub(y).div_ceil(dy),
+            interlaced: false,
+            ..*header
+        })
+        .filter(|pass| pass.width > 0 && pass.height > 0)
+        .collect()
+}
+
+// Filters raw scanlines with the given strategy, prefixing each row with its
+// filter type byte.
+pub fn filter(raw: &[u8], header: &ImageHeader, strategy: FilterStrategy) -> Vec<u8> {
+    let stride = header.stride();
+    let distance = header.filter_distance();
+    let mut filtered = Vec::with_capacity(raw.len() + header.height as usize);
+    let mut line = vec![0u8; stride];
+    for row in 0..header.height as usize {
+        let current = &raw[row * stride..(row + 1) * stride];
+        let previous = (row > 0).then(|| &raw[(row - 1) * stride..row * stride]);
+        let filter_type = match strategy {
+            FilterStrategy::Adaptive => (0..5)
+                .min_by_key(|&f| {
+                    filter_line(f, current, previous, distance, &mut line);
+                    line.iter()
+                        .map(|&v| (v as i8).unsigned_abs() as u64)
+                        .sum::<u64>()
+                })
+                .expect("five filter types"),
+            _ => strategy as u8,
+        };
+        filter_line(filter_type, current, previous, distance, &mut line);
+        filtered.push(filter_type);
+        filtered.extend_from_slice(&line);
+    }
+    filtered
+}
+
+fn filter_line(
+    filter_type: u8,
+    current: &[u8],
+    previous: Option<&[u8]>,
+    distance: usize,
+    out: &mut [u8],
+) {
+    for i in 0..current.len() {
+        let a = if i >= distance {
+            current[i - distance]
+        } else {
+            0
+        };
+        let b = previous.map_or(0, |p| p[i]);
+        let c = match previous {
+            Some(p) if i >= distance => p[i - distance],
+            _ => 0,
+        };
+        out[i] = match filter_type {
+            0 => current[i],
+            1 => current[i].wrapping_sub(a),
+            2 => current[i].wrapping_sub(b),
+            3 => current[i].wrapping_sub(((a as u16 + b as u16) / 2) as u8),
+            _ => current[i].wrapping_sub(paeth(a, b, c)),
+        };
+    }
+}
+
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let p = a as i16 + b as i16 - c as i16;
+    let pa = (p - a as i16).abs();
+    let pb = (p - b as i16).abs();
+    let pc = (p - c as i16).abs();
+    if pa <= pb && pa <= pc {
+        a
+    } else if pb <= pc {
+        b
+    } else {
+        c
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn testing_header(
+        width: u32,
+        height: u32,
+        bit_depth: u8,
+        color_type: ColorType,
+    ) -> ImageHeader {
+        ImageHeader {
+            width,
+            height,
+            bit_depth,
+            color_type,
+            interlaced: false,
+        }
+    }
+
+    #[test]
+    fn test_image_header_round_trip() {
+        let header = testing_header(50, 40, 8, ColorType::Rgba);
+        let parsed = ImageHeader::try_from(&header.as_bytes()[..]).unwrap();
+        assert_eq!(parsed, header);
+    }
+
+    #[test]
+    fn test_invalid_image_header() {
+        let mut bytes = testing_header(50, 40, 8, ColorType::Rgba).as_bytes();
+        bytes[8] = 4;
+        assert!(ImageHeader::try_from(&bytes[..]).is_err());
+        assert!(ImageHeader::try_from(&bytes[..12]).is_err());
+    }
+
+    #[test]
+    fn test_stride() {
+        assert_eq!(testing_header(10, 1, 1, ColorType::Grayscale).stride(), 2);
+        assert_eq!(testing_header(10, 1, 8, ColorType::Rgb).stride(), 30);
+        assert_eq!(testing_header(10, 1, 16, ColorType::Rgba).stride(), 80);
+    }
+
+    #[test]
+    fn test_adam7_passes() {
+        let header = ImageHeader {
+            width: 3,
+            height: 3,
+            bit_depth: 8,
+            color_type: ColorType::Rgb,
+            interlaced: true,
+        };
+        let sizes: Vec<(u32, u32)> = adam7_passes(&header)
+            .iter()
+            .map(|p| (p.width, p.height))
+            .collect();
+        assert_eq!(sizes, [(1, 1), (1, 1), (2, 1), (1, 2), (3, 1)]);
+        let pixels: u32 = adam7_passes(&ImageHeader {
+            width: 17,
+            height: 9,
+            ..header
+        })
+        .iter()
+        .map(|p| p.width * p.height)
+        .sum();
+        assert_eq!(pixels, 17 * 9);
+    }
+
+    #[test]
+    fn test_filter_round_trip() {
+        let header = testing_header(7, 5, 8, ColorType::Rgb);
+        let raw: Vec<u8> = (0..header.stride() * 5)
+            .map(|i| (i * 37 % 251) as u8)
+            .collect();
+        for strategy in FilterStrategy::ALL {
+            let filtered = filter(&raw, &header, strategy);
+            assert_eq!(unfilter(&filtered, &header).unwrap(), raw, "{:?}", strategy);
+        }
+    }
+
+    #[test]
+    fn test_deflate_round_trip() {
+        let data = b"aaaaaaaaaaaaaaaabbbbbbbbbbbbbbbbbbbbb".repeat(10);
+        assert_eq!(inflate(&deflate(&data, 9), data.len()).unwrap(), data);
+        assert_eq!(
+            inflate(&deflate(&data, 9), data.len() - 1),
+            Err(format!(
+                "inflated data is larger than {} bytes",
+                data.len() - 1
+            ))
+        );
+    }
+
+    #[test]
+    fn test_data_size() {
+        assert_eq!(testing_header(7, 5, 8, ColorType::Rgb).data_size(), 5 * 22);
+        let interlaced = ImageHeader {
+            interlaced: true,
+            ..testing_header(2, 2, 8, ColorType::Grayscale)
+        };
+        // passes 1, 6 and 7 of 1x1, 1x1 and 2x1 pixels
+        assert_eq!(interlaced.data_size(), 2 + 2 + 3);
+    }
+}
@@ -0,0 +1,70 @@
+use std::{fs::File, io, ops::Deref};
+
+use memmap2::Mmap;
+
+// The contents of an input file. Read-only commands map the file instead of
+// reading it, so that only the pages holding the chunks they look at (usually
+// just the chunk headers) are ever loaded.
+pub enum FileData {
+    Mapped(Mmap),
+    Read(Vec<u8>),
+}
+
+pub fn map_file(path: &str) -> io::Result<FileData> {
+    let file = File::open(path)?;
+    // empty files and some special files cannot be mapped
+    if file.metadata()?.len() == 0 {
+        return std::fs::read(path).map(FileData::Read);
+    }
+    // SAFETY: the map is only read while the command runs; a file truncated by
+    // another process in the meantime is the usual caveat of memory mapping.
+    match unsafe { Mmap::map(&file) } {
+        Ok(map) => {
+            #[cfg(unix)]
+            let _ = map.advise(memmap2::Advice::Random);
+            Ok(FileData::Mapped(map))
+        }
+        Err(_) => std::fs::read(path).map(FileData::Read),
+    }
+}
+
+impl Deref for FileData {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            FileData::Mapped(map) => map,
+            FileData::Read(data) => data,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{fs, process};
+
+    fn testing_path(name: &str) -> String {
+        let dir = std::env::temp_dir().join(format!("pngme-input-{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        dir.join(name).to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn test_map_file() {
+        let path = testing_path("mapped.png");
+        fs::write(&path, b"some bytes").unwrap();
+        let data = map_file(&path).unwrap();
+        assert!(matches!(data, FileData::Mapped(_)));
+        assert_eq!(&data[..], b"some bytes");
+    }
+
+    #[test]
+    fn test_map_empty_file() {
+        let path = testing_path("empty.png");
+        fs::write(&path, b"").unwrap();
+        let data = map_file(&path).unwrap();
+        assert!(matches!(data, FileData::Read(_)));
+        assert!(data.is_empty());
+    }
+}
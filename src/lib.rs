@@ -0,0 +1,31 @@
+pub mod apng;
+pub mod carve;
+pub mod chunk;
+pub mod chunk_type;
+pub mod color;
+pub mod compress;
+pub mod container;
+pub mod crc;
+pub mod diff;
+pub mod ecc;
+pub mod exif;
+pub mod image;
+#[cfg(feature = "json")]
+pub mod manifest;
+pub mod messages;
+pub mod metadata;
+pub mod naming;
+pub mod optimize;
+pub mod palette;
+pub mod png;
+pub mod policy;
+pub mod registry;
+pub mod repair;
+#[cfg(feature = "serde")]
+mod serialize;
+#[cfg(test)]
+mod testing;
+mod util;
+
+pub type Error = Box<dyn std::error::Error>;
+pub type Result<T> = std::result::Result<T, Error>;
@@ -1,14 +1,17 @@
+use std::process::ExitCode;
+
 mod args;
-mod chunk;
-mod chunk_type;
+mod batch;
 mod commands;
-mod crc;
-mod png;
-
-pub type Error = Box<dyn std::error::Error>;
-pub type Result<T> = std::result::Result<T, Error>;
+mod input;
+mod output;
 
-fn main() -> Result<()> {
-    args::parse();
-    Ok(())
+fn main() -> ExitCode {
+    match args::parse() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("{}", e);
+            ExitCode::FAILURE
+        }
+    }
 }
@@ -0,0 +1,294 @@
+use std::str::FromStr;
+
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use serde_json::{json, Map, Value};
+
+use crate::{chunk::Chunk, chunk_type::ChunkType, metadata, png::Png};
+
+// A manifest lists the chunks of a PNG in a text format that can be edited and
+// kept in version control. Lengths and crcs are left out since `build`
+// recomputes them, the flags and decoded views are informational only.
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ManifestFormat {
+    Json,
+    #[cfg(feature = "yaml")]
+    Yaml,
+}
+
+impl ManifestFormat {
+    pub fn from_path(path: &str) -> Option<ManifestFormat> {
+        let extension = path.rsplit_once('.')?.1.to_ascii_lowercase();
+        match extension.as_str() {
+            "json" => Some(ManifestFormat::Json),
+            #[cfg(feature = "yaml")]
+            "yaml" | "yml" => Some(ManifestFormat::Yaml),
+            _ => None,
+        }
+    }
+}
+
+impl FromStr for ManifestFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(ManifestFormat::Json),
+            #[cfg(feature = "yaml")]
+            "yaml" | "yml" => Ok(ManifestFormat::Yaml),
+            #[cfg(not(feature = "yaml"))]
+            "yaml" | "yml" => Err(String::from("YAML manifests need the yaml feature")),
+            _ => Err(String::from(
+                "invalid manifest format, expected json or yaml",
+            )),
+        }
+    }
+}
+
+// How chunk data that is not plain text is written.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum DataEncoding {
+    Base64,
+    Hex,
+}
+
+impl FromStr for DataEncoding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "base64" => Ok(DataEncoding::Base64),
+            "hex" => Ok(DataEncoding::Hex),
+            _ => Err(String::from(
+                "invalid data encoding, expected base64 or hex",
+            )),
+        }
+    }
+}
+
+const DATA_FIELDS: [&str; 3] = ["text", "base64", "hex"];
+
+pub fn to_value(png: &Png, encoding: DataEncoding) -> Value {
+    let chunks: Vec<Value> = png
+        .chunks()
+        .iter()
+        .map(|chunk| chunk_to_value(chunk, encoding))
+        .collect();
+    json!({"signature": to_hex(&Png::STANDARD_HEADER), "chunks": chunks})
+}
+
+fn chunk_to_value(chunk: &Chunk, encoding: DataEncoding) -> Value {
+    let chunk_type = chunk.chunk_type();
+    let mut value = Map::new();
+    value.insert(String::from("type"), json!(chunk_type.to_string()));
+    value.insert(
+        String::from("flags"),
+        json!({
+            "critical": chunk_type.is_critical(),
+            "public": chunk_type.is_public(),
+            "reserved_bit_valid": chunk_type.is_reserved_bit_valid(),
+            "safe_to_copy": chunk_type.is_safe_to_copy(),
+        }),
+    );
+    // empty chunks such as IEND need no data at all
+    if !chunk.data().is_empty() {
+        let (field, data) = match as_text(chunk.data()) {
+            Some(text) => ("text", text.to_string()),
+            None => match encoding {
+                DataEncoding::Base64 => ("base64", BASE64.encode(chunk.data())),
+                DataEncoding::Hex => ("hex", to_hex(chunk.data())),
+            },
+        };
+        value.insert(String::from(field), json!(data));
+    }
+    if let Some(decoded) = metadata::describe(&chunk_type.to_string(), chunk.data()) {
+        value.insert(String::from("decoded"), json!(decoded));
+    }
+    Value::Object(value)
+}
+
+// Data is only written as text when it survives being edited by hand, so no
+// control characters apart from newlines and tabs.
+fn as_text(data: &[u8]) -> Option<&str> {
+    let text = std::str::from_utf8(data).ok()?;
+    let printable = text
+        .chars()
+        .all(|c| !c.is_control() || c == '\n' || c == '\t');
+    printable.then_some(text)
+}
+
+pub fn from_value(value: &Value) -> Result<Png, String> {
+    if let Some(signature) = value.get("signature") {
+        let signature = signature
+            .as_str()
+            .ok_or_else(|| String::from("signature must be a string"))
+            .and_then(from_hex)?;
+        if signature != Png::STANDARD_HEADER {
+            return Err(String::from("unsupported signature"));
+        }
+    }
+    let chunks = value
+        .get("chunks")
+        .and_then(Value::as_array)
+        .ok_or_else(|| String::from("missing chunks"))?;
+    let chunks = chunks
+        .iter()
+        .enumerate()
+        .map(|(i, chunk)| chunk_from_value(chunk).map_err(|e| format!("chunk {}: {}", i, e)))
+        .collect::<Result<Vec<Chunk>, String>>()?;
+    Ok(Png::from_chunks(chunks))
+}
+
+fn chunk_from_value(value: &Value) -> Result<Chunk, String> {
+    let chunk_type = value
+        .get("type")
+        .and_then(Value::as_str)
+        .ok_or_else(|| String::from("missing type"))?;
+    let chunk_type = ChunkType::from_str(chunk_type)?;
+    let fields: Vec<&str> = DATA_FIELDS
+        .into_iter()
+        .filter(|field| value.get(field).is_some())
+        .collect();
+    let data = match fields[..] {
+        [] => Vec::new(),
+        [field] => {
+            let data = value[field]
+                .as_str()
+                .ok_or_else(|| format!("{} must be a string", field))?;
+            match field {
+                "text" => data.as_bytes().to_vec(),
+                "base64" => BASE64
+                    .decode(data)
+                    .map_err(|e| format!("invalid base64: {}", e))?,
+                _ => from_hex(data)?,
+            }
+        }
+        _ => return Err(format!("only one of {} is allowed", fields.join(", "))),
+    };
+    Ok(Chunk::new(chunk_type, data))
+}
+
+pub fn dump(png: &Png, format: ManifestFormat, encoding: DataEncoding) -> String {
+    let value = to_value(png, encoding);
+    match format {
+        ManifestFormat::Json => {
+            serde_json::to_string_pretty(&value).expect("serializing a Value never fails") + "\n"
+        }
+        #[cfg(feature = "yaml")]
+        ManifestFormat::Yaml => {
+            serde_norway::to_string(&value).expect("serializing a Value never fails")
+        }
+    }
+}
+
+pub fn build(text: &str, format: ManifestFormat) -> Result<Png, String> {
+    let value: Value = match format {
+        ManifestFormat::Json => {
+            serde_json::from_str(text).map_err(|e| format!("invalid JSON: {}", e))?
+        }
+        #[cfg(feature = "yaml")]
+        ManifestFormat::Yaml => {
+            serde_norway::from_str(text).map_err(|e| format!("invalid YAML: {}", e))?
+        }
+    };
+    from_value(&value)
+}
+
+fn to_hex(data: &[u8]) -> String {
+    data.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+// Whitespace is ignored so long data can be wrapped.
+fn from_hex(s: &str) -> Result<Vec<u8>, String> {
+    let digits: Vec<u8> = s
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .map(|c| c.to_digit(16).map(|d| d as u8))
+        .collect::<Option<_>>()
+        .ok_or_else(|| String::from("invalid hex"))?;
+    if !digits.len().is_multiple_of(2) {
+        return Err(String::from("invalid hex, odd number of digits"));
+    }
+    Ok(digits.chunks(2).map(|d| d[0] << 4 | d[1]).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{self, chunk};
+
+    fn testing_png() -> Png {
+        testing::testing_png(vec![
+            chunk("pHYs", [0, 0, 0x0b, 0x13, 0, 0, 0x0b, 0x13, 1]),
+            chunk("ruSt", b"a message\nover two lines"),
+        ])
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let png = testing_png();
+        let formats = [
+            ManifestFormat::Json,
+            #[cfg(feature = "yaml")]
+            ManifestFormat::Yaml,
+        ];
+        for format in formats {
+            for encoding in [DataEncoding::Base64, DataEncoding::Hex] {
+                let text = dump(&png, format, encoding);
+                let built = build(&text, format).unwrap();
+                assert_eq!(built.as_bytes(), png.as_bytes());
+            }
+        }
+    }
+
+    #[test]
+    fn test_chunk_values() {
+        let value = to_value(&testing_png(), DataEncoding::Hex);
+        let chunks = value["chunks"].as_array().unwrap();
+        assert_eq!(chunks[0]["hex"], "00000001000000010800000000");
+        assert_eq!(chunks[0]["flags"]["critical"], true);
+        assert_eq!(
+            chunks[1]["decoded"],
+            "2835x2835 pixels per metre (72x72 DPI)"
+        );
+        assert_eq!(chunks[2]["text"], "a message\nover two lines");
+        assert_eq!(chunks[2]["flags"]["public"], false);
+        assert!(chunks[4].get("hex").is_none());
+    }
+
+    #[test]
+    fn test_build_recomputes_crc() {
+        let text = r#"{"chunks": [
+            {"type": "ruSt", "text": "hello", "crc": 1},
+            {"type": "teSt", "hex": "00 ff\n10"},
+            {"type": "IEND"}
+        ]}"#;
+        let png = build(text, ManifestFormat::Json).unwrap();
+        assert_eq!(png.chunks()[0], chunk("ruSt", b"hello"));
+        assert_eq!(png.chunks()[1].data(), &[0, 255, 16]);
+        assert!(Png::try_from(&png.as_bytes()[..]).is_ok());
+    }
+
+    #[test]
+    fn test_build_errors() {
+        let cases = [
+            (r#"{}"#, "missing chunks"),
+            (r#"{"chunks": [{"text": "a"}]}"#, "chunk 0: missing type"),
+            (
+                r#"{"chunks": [{"type": "ruSt", "text": "a", "hex": "00"}]}"#,
+                "chunk 0: only one of text, hex is allowed",
+            ),
+            (
+                r#"{"chunks": [{"type": "ruSt", "hex": "0"}]}"#,
+                "chunk 0: invalid hex, odd number of digits",
+            ),
+            (
+                r#"{"signature": "00", "chunks": []}"#,
+                "unsupported signature",
+            ),
+        ];
+        for (text, error) in cases {
+            assert_eq!(build(text, ManifestFormat::Json).unwrap_err(), error);
+        }
+    }
+}
@@ -0,0 +1,439 @@
+use std::{fmt::Display, str::FromStr};
+
+use crate::{
+    chunk::Chunk,
+    chunk_type::ChunkType,
+    container::Container,
+    ecc,
+    png::{ChunkPosition, Png},
+};
+
+pub const DEFAULT_CHUNK_TYPE: &str = "pnMe";
+
+// A pngme message, a chunk holding a container, possibly with error correction.
+// Messages keep the ID stored in their container when they were added and can
+// be labelled with the container's filename.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Message {
+    pub id: u32,
+    // position of the chunk in the file
+    pub index: usize,
+    pub chunk_type: ChunkType,
+    pub container: Container,
+    // the parity of the error correction
+    pub ecc: Option<usize>,
+}
+
+impl Message {
+    pub fn label(&self) -> Option<&str> {
+        self.container.filename.as_deref()
+    }
+}
+
+impl Display for Message {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {} {} {} bytes",
+            self.id,
+            self.label().unwrap_or("-"),
+            self.chunk_type,
+            self.container.payload.len()
+        )?;
+        if self.container.compressed {
+            write!(f, ", compressed")?;
+        }
+        if self.container.encrypted {
+            write!(f, ", encrypted")?;
+        }
+        if self.ecc.is_some() {
+            write!(f, ", error corrected")?;
+        }
+        Ok(())
+    }
+}
+
+// A message picked by its ID, or by its label when not a number.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Selector {
+    Id(u32),
+    Label(String),
+}
+
+impl Selector {
+    fn matches(&self, message: &Message) -> bool {
+        match self {
+            Selector::Id(id) => message.id == *id,
+            Selector::Label(label) => message.label() == Some(label.as_str()),
+        }
+    }
+}
+
+impl FromStr for Selector {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.parse() {
+            Ok(id) => Ok(Selector::Id(id)),
+            Err(_) if s.is_empty() => Err(String::from("empty message label")),
+            Err(_) => Ok(Selector::Label(s.to_string())),
+        }
+    }
+}
+
+impl Display for Selector {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Selector::Id(id) => write!(f, "{}", id),
+            Selector::Label(label) => write!(f, "{}", label),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct MessageIndex {
+    messages: Vec<Message>,
+}
+
+impl MessageIndex {
+    // Chunks that are not containers, or damaged ones, are not messages.
+    // Containers written before IDs were stored are numbered in file order after
+    // the highest stored ID. Editing the messages stores those numbers, see
+    // `store_ids`.
+    pub fn from_png(png: &Png) -> MessageIndex {
+        let found: Vec<(usize, ChunkType, Container, Option<usize>)> = png
+            .chunks()
+            .iter()
+            .enumerate()
+            .filter_map(|(index, c)| {
+                let (container, ecc) = read_container(c.data())?;
+                Some((index, c.chunk_type().clone(), container, ecc))
+            })
+            .collect();
+        let mut unnumbered = found
+            .iter()
+            .filter_map(|(_, _, container, _)| container.message_id)
+            .max()
+            .map_or(0, |id| id.saturating_add(1));
+        let messages = found
+            .into_iter()
+            .map(|(index, chunk_type, container, ecc)| {
+                let id = container.message_id.unwrap_or_else(|| {
+                    unnumbered = unnumbered.saturating_add(1);
+                    unnumbered - 1
+                });
+                Message {
+                    id,
+                    index,
+                    chunk_type,
+                    container,
+                    ecc,
+                }
+            })
+            .collect();
+        MessageIndex { messages }
+    }
+
+    // The ID the next message added gets.
+    pub fn next_id(&self) -> Result<u32, String> {
+        match self.messages.iter().map(|m| m.id).max() {
+            Some(id) => id
+                .checked_add(1)
+                .ok_or_else(|| String::from("no message IDs left")),
+            None => Ok(0),
+        }
+    }
+
+    pub fn messages(&self) -> &[Message] {
+        &self.messages
+    }
+
+    pub fn get(&self, selector: &Selector) -> Option<&Message> {
+        self.messages.iter().find(|m| selector.matches(m))
+    }
+
+    fn must_get(&self, selector: &Selector) -> Result<&Message, String> {
+        self.get(selector)
+            .ok_or_else(|| format!("message {} not found", selector))
+    }
+}
+
+fn read_container(data: &[u8]) -> Option<(Container, Option<usize>)> {
+    match ecc::parity(data) {
+        Some(parity) => {
+            let (data, _) = ecc::decode(data).ok()?;
+            Some((Container::try_from(&data[..]).ok()?, Some(parity)))
+        }
+        None if Container::is_container(data) => Some((Container::try_from(data).ok()?, None)),
+        None => None,
+    }
+}
+
+// Adds a message before IEND and returns its ID. Labels have to be unique and
+// cannot be numbers, which select by ID.
+pub fn add(png: &mut Png, chunk_type: ChunkType, container: Container) -> Result<u32, String> {
+    let index = store_ids(png)?;
+    if let Some(label) = &container.filename {
+        check_label(label)?;
+        if index.get(&Selector::Label(label.clone())).is_some() {
+            return Err(format!("a message labelled {} already exists", label));
+        }
+    }
+    let id = index.next_id()?;
+    let container = Container {
+        message_id: Some(id),
+        ..container
+    };
+    let chunk = Chunk::new(chunk_type, container.as_bytes()?);
+    png.insert_chunk(ChunkPosition::End, chunk)?;
+    Ok(id)
+}
+
+// Replaces the data of one message, keeping its ID, chunk type, position and
+// error correction.
+pub fn replace(png: &mut Png, selector: &Selector, container: Container) -> Result<(), String> {
+    let index = store_ids(png)?;
+    let message = index.must_get(selector)?;
+    if let Some(label) = &container.filename {
+        check_label(label)?;
+        let other = index.get(&Selector::Label(label.clone()));
+        if other.is_some_and(|m| m.id != message.id) {
+            return Err(format!("a message labelled {} already exists", label));
+        }
+    }
+    rewrite(png, message, container)
+}
+
+pub fn remove(png: &mut Png, selector: &Selector) -> Result<Message, String> {
+    let index = store_ids(png)?;
+    let message = index.must_get(selector)?.clone();
+    remove_chunk(png, message.index);
+    Ok(message)
+}
+
+// Stores the IDs of containers that have none, so that adding or removing
+// messages does not renumber them.
+pub fn store_ids(png: &mut Png) -> Result<MessageIndex, String> {
+    let index = MessageIndex::from_png(png);
+    for message in index.messages() {
+        if message.container.message_id.is_none() {
+            rewrite(png, message, message.container.clone())?;
+        }
+    }
+    Ok(MessageIndex::from_png(png))
+}
+
+// Writes `container` in place of a message, with the message's ID.
+fn rewrite(png: &mut Png, message: &Message, container: Container) -> Result<(), String> {
+    let container = Container {
+        message_id: Some(message.id),
+        ..container
+    };
+    let data = match message.ecc {
+        Some(parity) => ecc::encode(&container.as_bytes()?, parity)?,
+        None => container.as_bytes()?,
+    };
+    let chunk = Chunk::new(message.chunk_type.clone(), data);
+    remove_chunk(png, message.index);
+    png.insert_chunk(ChunkPosition::Index(message.index), chunk)?;
+    Ok(())
+}
+
+fn check_label(label: &str) -> Result<(), String> {
+    match Selector::from_str(label)? {
+        Selector::Id(_) => Err(format!(
+            "label {} is a number, which would select a message by ID",
+            label
+        )),
+        Selector::Label(_) => Ok(()),
+    }
+}
+
+fn remove_chunk(png: &mut Png, index: usize) {
+    let mut position = 0;
+    png.remove_chunks_where(|_| {
+        position += 1;
+        position - 1 == index
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{self, chunk};
+
+    fn labelled(label: &str, text: &str) -> Container {
+        Container {
+            filename: Some(String::from(label)),
+            ..Container::new(text.as_bytes().to_vec())
+        }
+    }
+
+    fn testing_png() -> Png {
+        let mut png = testing::testing_png(vec![]);
+        // a legacy message, not part of the index
+        png.insert_chunk(ChunkPosition::End, chunk("ruSt", b"hidden"))
+            .unwrap();
+        let chunk_type = ChunkType::from_str(DEFAULT_CHUNK_TYPE).unwrap();
+        add(&mut png, chunk_type.clone(), labelled("author", "Ferris")).unwrap();
+        add(&mut png, chunk_type.clone(), labelled("licence", "MIT")).unwrap();
+        add(&mut png, chunk_type, Container::new(b"build 42".to_vec())).unwrap();
+        png
+    }
+
+    fn payloads(png: &Png) -> Vec<(u32, Option<String>, Vec<u8>)> {
+        MessageIndex::from_png(png)
+            .messages()
+            .iter()
+            .map(|m| {
+                (
+                    m.id,
+                    m.label().map(String::from),
+                    m.container.payload.clone(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_index() {
+        let png = testing_png();
+        let index = MessageIndex::from_png(&png);
+        assert_eq!(index.messages().len(), 3);
+        assert_eq!(index.messages()[0].index, 3);
+        assert_eq!(index.messages()[1].to_string(), "1 licence pnMe 3 bytes");
+        assert_eq!(
+            index
+                .get(&Selector::from_str("licence").unwrap())
+                .unwrap()
+                .id,
+            1
+        );
+        assert_eq!(
+            index
+                .get(&Selector::from_str("2").unwrap())
+                .unwrap()
+                .label(),
+            None
+        );
+        assert_eq!(index.get(&Selector::Id(3)), None);
+        // IEND stays last
+        assert_eq!(
+            png.chunks().last().unwrap().chunk_type().to_string(),
+            "IEND"
+        );
+    }
+
+    #[test]
+    fn test_replace_and_remove() {
+        let mut png = testing_png();
+        replace(
+            &mut png,
+            &Selector::Id(1),
+            labelled("licence", "Apache-2.0"),
+        )
+        .unwrap();
+        let removed = remove(&mut png, &Selector::from_str("author").unwrap()).unwrap();
+        assert_eq!(removed.container.payload, b"Ferris");
+        // IDs stay with their messages
+        assert_eq!(
+            payloads(&png),
+            [
+                (1, Some(String::from("licence")), b"Apache-2.0".to_vec()),
+                (2, None, b"build 42".to_vec()),
+            ]
+        );
+        let chunk_type = ChunkType::from_str(DEFAULT_CHUNK_TYPE).unwrap();
+        assert_eq!(add(&mut png, chunk_type, labelled("author", "me")), Ok(3));
+        assert_eq!(png.chunk_by_type("ruSt").unwrap().data(), b"hidden");
+    }
+
+    #[test]
+    fn test_messages_without_id() {
+        let old = |text: &[u8]| {
+            let data = Container::new(text.to_vec()).as_bytes().unwrap();
+            chunk(DEFAULT_CHUNK_TYPE, data)
+        };
+        let mut png = Png::from_chunks(vec![old(b"first"), old(b"second"), chunk("IEND", [])]);
+        let chunk_type = ChunkType::from_str(DEFAULT_CHUNK_TYPE).unwrap();
+        assert_eq!(add(&mut png, chunk_type, labelled("new", "third")), Ok(2));
+        let ids: Vec<u32> = payloads(&png).iter().map(|(id, _, _)| *id).collect();
+        assert_eq!(ids, [0, 1, 2]);
+    }
+
+    #[test]
+    fn test_stored_and_legacy_ids() {
+        let old = |text: &[u8]| {
+            let data = Container::new(text.to_vec()).as_bytes().unwrap();
+            chunk(DEFAULT_CHUNK_TYPE, data)
+        };
+        let mut png = testing::testing_png(vec![]);
+        let chunk_type = ChunkType::from_str(DEFAULT_CHUNK_TYPE).unwrap();
+        add(&mut png, chunk_type.clone(), labelled("new", "stored")).unwrap();
+        png.insert_chunk(ChunkPosition::End, old(b"first")).unwrap();
+        png.insert_chunk(ChunkPosition::End, old(b"second"))
+            .unwrap();
+        let ids: Vec<u32> = payloads(&png).iter().map(|(id, _, _)| *id).collect();
+        assert_eq!(ids, [0, 1, 2]);
+
+        let removed = remove(&mut png, &Selector::Id(1)).unwrap();
+        assert_eq!(removed.container.payload, b"first");
+        assert_eq!(add(&mut png, chunk_type, labelled("newer", "third")), Ok(3));
+        assert_eq!(
+            payloads(&png),
+            [
+                (0, Some(String::from("new")), b"stored".to_vec()),
+                (2, None, b"second".to_vec()),
+                (3, Some(String::from("newer")), b"third".to_vec()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_error_corrected_message() {
+        let mut png = testing_png();
+        let container = Container {
+            message_id: Some(3),
+            ..labelled("signed", "by me")
+        };
+        let data = ecc::encode(&container.as_bytes().unwrap(), 8).unwrap();
+        png.insert_chunk(ChunkPosition::End, chunk(DEFAULT_CHUNK_TYPE, data))
+            .unwrap();
+        let selector = Selector::from_str("signed").unwrap();
+        let index = MessageIndex::from_png(&png);
+        let message = index.get(&selector).unwrap();
+        assert_eq!(message.ecc, Some(8));
+        assert_eq!(
+            message.to_string(),
+            "3 signed pnMe 5 bytes, error corrected"
+        );
+
+        replace(&mut png, &selector, labelled("signed", "by you")).unwrap();
+        let index = MessageIndex::from_png(&png);
+        assert_eq!(index.get(&selector).unwrap().ecc, Some(8));
+        assert_eq!(index.get(&selector).unwrap().container.payload, b"by you");
+    }
+
+    #[test]
+    fn test_unique_labels() {
+        let mut png = testing_png();
+        let chunk_type = ChunkType::from_str(DEFAULT_CHUNK_TYPE).unwrap();
+        assert_eq!(
+            add(&mut png, chunk_type, labelled("author", "someone")).unwrap_err(),
+            "a message labelled author already exists"
+        );
+        assert_eq!(
+            replace(&mut png, &Selector::Id(2), labelled("author", "someone")).unwrap_err(),
+            "a message labelled author already exists"
+        );
+        assert_eq!(
+            remove(&mut png, &Selector::from_str("nobody").unwrap()).unwrap_err(),
+            "message nobody not found"
+        );
+        let chunk_type = ChunkType::from_str(DEFAULT_CHUNK_TYPE).unwrap();
+        assert_eq!(
+            add(&mut png, chunk_type, labelled("42", "answer")).unwrap_err(),
+            "label 42 is a number, which would select a message by ID"
+        );
+    }
+}
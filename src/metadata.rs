@@ -0,0 +1,502 @@
+use std::{
+    fmt::Display,
+    str::FromStr,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    chunk::Chunk,
+    exif::Exif,
+    palette::{Histogram, Palette, SuggestedPalette},
+    util::{chunk, read_u32},
+};
+
+const METRES_PER_INCH: f64 = 0.0254;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum PhysicalUnit {
+    // only the aspect ratio is known
+    Unknown = 0,
+    Metre = 1,
+}
+
+// The contents of the pHYs chunk, pixels per unit along each axis.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct PhysicalDimensions {
+    pub x: u32,
+    pub y: u32,
+    pub unit: PhysicalUnit,
+}
+
+impl PhysicalDimensions {
+    pub fn from_dpi(x: f64, y: f64) -> PhysicalDimensions {
+        PhysicalDimensions {
+            x: (x / METRES_PER_INCH).round() as u32,
+            y: (y / METRES_PER_INCH).round() as u32,
+            unit: PhysicalUnit::Metre,
+        }
+    }
+
+    pub fn dpi(&self) -> Option<(f64, f64)> {
+        match self.unit {
+            PhysicalUnit::Metre => Some((
+                self.x as f64 * METRES_PER_INCH,
+                self.y as f64 * METRES_PER_INCH,
+            )),
+            PhysicalUnit::Unknown => None,
+        }
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        [
+            &self.x.to_be_bytes()[..],
+            &self.y.to_be_bytes()[..],
+            &[self.unit as u8],
+        ]
+        .concat()
+    }
+
+    pub fn to_chunk(&self) -> Chunk {
+        chunk("pHYs", self.as_bytes())
+    }
+}
+
+impl TryFrom<&[u8]> for PhysicalDimensions {
+    type Error = String;
+
+    fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
+        if value.len() != 9 {
+            return Err(String::from("invalid pHYs length"));
+        }
+        let unit = match value[8] {
+            0 => PhysicalUnit::Unknown,
+            1 => PhysicalUnit::Metre,
+            _ => return Err(String::from("invalid pHYs unit")),
+        };
+        Ok(PhysicalDimensions {
+            x: read_u32(value, 0),
+            y: read_u32(value, 4),
+            unit,
+        })
+    }
+}
+
+impl Display for PhysicalDimensions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.dpi() {
+            Some((x, y)) => write!(
+                f,
+                "{}x{} pixels per metre ({:.0}x{:.0} DPI)",
+                self.x, self.y, x, y
+            ),
+            None => write!(f, "{}:{} pixel aspect ratio", self.x, self.y),
+        }
+    }
+}
+
+// The contents of the tIME chunk, the last modification time in UTC.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Timestamp {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+}
+
+impl Timestamp {
+    pub fn now() -> Timestamp {
+        let seconds = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+        Timestamp::from_unix(seconds)
+    }
+
+    pub fn from_unix(seconds: u64) -> Timestamp {
+        let (year, month, day) = civil_from_days((seconds / 86400) as i64);
+        let time = seconds % 86400;
+        Timestamp {
+            year: year as u16,
+            month,
+            day,
+            hour: (time / 3600) as u8,
+            minute: (time / 60 % 60) as u8,
+            second: (time % 60) as u8,
+        }
+    }
+
+    fn validate(self) -> Result<Timestamp, String> {
+        if !(1..=12).contains(&self.month)
+            || !(1..=days_in_month(self.year, self.month)).contains(&self.day)
+            || self.hour > 23
+            || self.minute > 59
+            // 60 allows for leap seconds
+            || self.second > 60
+        {
+            return Err(String::from("invalid timestamp"));
+        }
+        Ok(self)
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let [high, low] = self.year.to_be_bytes();
+        vec![
+            high,
+            low,
+            self.month,
+            self.day,
+            self.hour,
+            self.minute,
+            self.second,
+        ]
+    }
+
+    pub fn to_chunk(&self) -> Chunk {
+        chunk("tIME", self.as_bytes())
+    }
+}
+
+impl TryFrom<&[u8]> for Timestamp {
+    type Error = String;
+
+    fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
+        let &[high, low, month, day, hour, minute, second] = value else {
+            return Err(String::from("invalid tIME length"));
+        };
+        Timestamp {
+            year: u16::from_be_bytes([high, low]),
+            month,
+            day,
+            hour,
+            minute,
+            second,
+        }
+        .validate()
+    }
+}
+
+// Parses ISO 8601 UTC timestamps such as "2024-05-01T12:30:00Z".
+impl FromStr for Timestamp {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || String::from("invalid timestamp, expected YYYY-MM-DDTHH:MM:SSZ");
+        let s = s.strip_suffix('Z').unwrap_or(s);
+        let (date, time) = s.split_once(['T', ' ']).ok_or_else(invalid)?;
+        let date: Vec<&str> = date.split('-').collect();
+        let time: Vec<&str> = time.split(':').collect();
+        let (&[year, month, day], &[hour, minute, second]) = (&date[..], &time[..]) else {
+            return Err(invalid());
+        };
+        let field = |v: &str| v.parse::<u8>().map_err(|_| invalid());
+        Timestamp {
+            year: year.parse().map_err(|_| invalid())?,
+            month: field(month)?,
+            day: field(day)?,
+            hour: field(hour)?,
+            minute: field(minute)?,
+            second: field(second)?,
+        }
+        .validate()
+    }
+}
+
+impl Display for Timestamp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+            self.year, self.month, self.day, self.hour, self.minute, self.second
+        )
+    }
+}
+
+// The number of days in `month` of `year` in the Gregorian calendar.
+fn days_in_month(year: u16, month: u8) -> u8 {
+    let is_leap_year =
+        year.is_multiple_of(4) && (!year.is_multiple_of(100) || year.is_multiple_of(400));
+    match month {
+        2 if is_leap_year => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+// Converts days since 1970-01-01 to a (year, month, day) date, following
+// Howard Hinnant's `civil_from_days`.
+fn civil_from_days(days: i64) -> (i64, u8, u8) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u8;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u8;
+    let year = yoe + era * 400 + (month <= 2) as i64;
+    (year, month, day)
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum OffsetUnit {
+    Pixel = 0,
+    Micrometre = 1,
+}
+
+// The contents of the oFFs chunk, the position of the image on a page.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Offset {
+    pub x: i32,
+    pub y: i32,
+    pub unit: OffsetUnit,
+}
+
+impl Offset {
+    pub fn as_bytes(&self) -> Vec<u8> {
+        [
+            &self.x.to_be_bytes()[..],
+            &self.y.to_be_bytes()[..],
+            &[self.unit as u8],
+        ]
+        .concat()
+    }
+
+    pub fn to_chunk(&self) -> Chunk {
+        chunk("oFFs", self.as_bytes())
+    }
+}
+
+impl TryFrom<&[u8]> for Offset {
+    type Error = String;
+
+    fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
+        if value.len() != 9 {
+            return Err(String::from("invalid oFFs length"));
+        }
+        let unit = match value[8] {
+            0 => OffsetUnit::Pixel,
+            1 => OffsetUnit::Micrometre,
+            _ => return Err(String::from("invalid oFFs unit")),
+        };
+        Ok(Offset {
+            x: read_u32(value, 0) as i32,
+            y: read_u32(value, 4) as i32,
+            unit,
+        })
+    }
+}
+
+impl Display for Offset {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let unit = match self.unit {
+            OffsetUnit::Pixel => "pixels",
+            OffsetUnit::Micrometre => "micrometres",
+        };
+        write!(f, "offset ({}, {}) {}", self.x, self.y, unit)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ScaleUnit {
+    Metre = 1,
+    Radian = 2,
+}
+
+// The contents of the sCAL chunk, the physical size of a pixel. The values are
+// kept as the ASCII decimals they are stored as, so they round trip exactly.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct PhysicalScale {
+    pub unit: ScaleUnit,
+    pub width: String,
+    pub height: String,
+}
+
+impl PhysicalScale {
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![self.unit as u8];
+        bytes.extend_from_slice(self.width.as_bytes());
+        bytes.push(0);
+        bytes.extend_from_slice(self.height.as_bytes());
+        bytes
+    }
+
+    pub fn to_chunk(&self) -> Chunk {
+        chunk("sCAL", self.as_bytes())
+    }
+}
+
+impl TryFrom<&[u8]> for PhysicalScale {
+    type Error = String;
+
+    fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
+        let unit = match value.first() {
+            Some(1) => ScaleUnit::Metre,
+            Some(2) => ScaleUnit::Radian,
+            _ => return Err(String::from("invalid sCAL unit")),
+        };
+        let values =
+            std::str::from_utf8(&value[1..]).map_err(|_| String::from("invalid sCAL value"))?;
+        let (width, height) = values
+            .split_once('\0')
+            .ok_or_else(|| String::from("missing sCAL separator"))?;
+        for v in [width, height] {
+            if !v.parse::<f64>().is_ok_and(|v| v > 0.0) {
+                return Err(String::from("invalid sCAL value"));
+            }
+        }
+        Ok(PhysicalScale {
+            unit,
+            width: String::from(width),
+            height: String::from(height),
+        })
+    }
+}
+
+impl Display for PhysicalScale {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let unit = match self.unit {
+            ScaleUnit::Metre => "metres",
+            ScaleUnit::Radian => "radians",
+        };
+        write!(f, "pixel size {} x {} {}", self.width, self.height, unit)
+    }
+}
+
+// The contents of the sBIT chunk, the number of significant bits of each
+// channel in the original image. How many channels there are depends on the
+// color type, between 1 and 4.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct SignificantBits {
+    pub bits: Vec<u8>,
+}
+
+impl SignificantBits {
+    pub fn to_chunk(&self) -> Chunk {
+        chunk("sBIT", self.bits.clone())
+    }
+}
+
+impl TryFrom<&[u8]> for SignificantBits {
+    type Error = String;
+
+    fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
+        if value.is_empty() || value.len() > 4 {
+            return Err(String::from("invalid sBIT length"));
+        }
+        if value.iter().any(|&b| b == 0 || b > 16) {
+            return Err(String::from("invalid significant bits"));
+        }
+        Ok(SignificantBits {
+            bits: value.to_vec(),
+        })
+    }
+}
+
+impl Display for SignificantBits {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let bits: Vec<String> = self.bits.iter().map(|b| b.to_string()).collect();
+        write!(f, "significant bits {}", bits.join(", "))
+    }
+}
+
+// Renders the data of typed chunks in a human readable form, for printing.
+// Returns None for other types or data that does not parse.
+pub fn describe(chunk_type: &str, data: &[u8]) -> Option<String> {
+    match chunk_type {
+        "pHYs" => PhysicalDimensions::try_from(data)
+            .ok()
+            .map(|v| v.to_string()),
+        "tIME" => Timestamp::try_from(data).ok().map(|v| v.to_string()),
+        "oFFs" => Offset::try_from(data).ok().map(|v| v.to_string()),
+        "sCAL" => PhysicalScale::try_from(data).ok().map(|v| v.to_string()),
+        "sBIT" => SignificantBits::try_from(data).ok().map(|v| v.to_string()),
+        "PLTE" => Palette::try_from(data).ok().map(|v| v.to_string()),
+        "hIST" => Histogram::try_from(data).ok().map(|v| v.to_string()),
+        "sPLT" => SuggestedPalette::try_from(data).ok().map(|v| v.to_string()),
+        "eXIf" => Exif::try_from(data)
+            .ok()
+            .map(|v| v.to_string().replace('\n', ", ")),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_physical_dimensions() {
+        let dimensions = PhysicalDimensions::from_dpi(300.0, 300.0);
+        assert_eq!(dimensions.x, 11811);
+        assert_eq!(
+            PhysicalDimensions::try_from(&dimensions.as_bytes()[..]),
+            Ok(dimensions)
+        );
+        assert_eq!(
+            dimensions.to_string(),
+            "11811x11811 pixels per metre (300x300 DPI)"
+        );
+        assert!(PhysicalDimensions::try_from(&[0; 8][..]).is_err());
+    }
+
+    #[test]
+    fn test_timestamp_round_trip() {
+        let timestamp = Timestamp::from_str("2024-02-29T23:59:60Z").unwrap();
+        assert_eq!(
+            Timestamp::try_from(&timestamp.as_bytes()[..]),
+            Ok(timestamp)
+        );
+        assert_eq!(timestamp.to_string(), "2024-02-29T23:59:60Z");
+        assert_eq!(Timestamp::from_str("2024-02-29 23:59:60"), Ok(timestamp));
+        assert!(Timestamp::from_str("2024-13-01T00:00:00Z").is_err());
+        assert!(Timestamp::from_str("2024-02-30T00:00:00Z").is_err());
+        assert!(Timestamp::from_str("2023-02-29T00:00:00Z").is_err());
+        assert!(Timestamp::from_str("1900-02-29T00:00:00Z").is_err());
+        assert!(Timestamp::from_str("2000-02-29T00:00:00Z").is_ok());
+        assert!(Timestamp::from_str("2024-04-31T00:00:00Z").is_err());
+        assert!(Timestamp::from_str("2024-12-31T00:00:00Z").is_ok());
+        assert!(Timestamp::from_str("2024-01-01").is_err());
+    }
+
+    #[test]
+    fn test_timestamp_from_unix() {
+        assert_eq!(Timestamp::from_unix(0).to_string(), "1970-01-01T00:00:00Z");
+        assert_eq!(
+            Timestamp::from_unix(1709251199).to_string(),
+            "2024-02-29T23:59:59Z"
+        );
+    }
+
+    #[test]
+    fn test_offset() {
+        let offset = Offset {
+            x: -20,
+            y: 5,
+            unit: OffsetUnit::Micrometre,
+        };
+        assert_eq!(Offset::try_from(&offset.as_bytes()[..]), Ok(offset));
+        assert_eq!(offset.to_string(), "offset (-20, 5) micrometres");
+    }
+
+    #[test]
+    fn test_physical_scale() {
+        let scale = PhysicalScale::try_from(&b"\x011.5e-4\x000.00015"[..]).unwrap();
+        assert_eq!(scale.unit, ScaleUnit::Metre);
+        assert_eq!(scale.width, "1.5e-4");
+        assert_eq!(scale.as_bytes(), b"\x011.5e-4\x000.00015");
+        assert!(PhysicalScale::try_from(&b"\x01-1\x001"[..]).is_err());
+        assert!(PhysicalScale::try_from(&b"\x031\x001"[..]).is_err());
+    }
+
+    #[test]
+    fn test_describe() {
+        assert_eq!(
+            describe("sBIT", &[5, 6, 5]).as_deref(),
+            Some("significant bits 5, 6, 5")
+        );
+        assert_eq!(describe("sBIT", &[]), None);
+        assert_eq!(describe("RuSt", b"hey"), None);
+    }
+}
@@ -0,0 +1,170 @@
+use std::str::FromStr;
+
+use crate::{chunk_type::ChunkType, crc::crc32, png::Png, registry::Registry};
+
+// How the chunk type of a message is chosen.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum TypeStrategy {
+    Given(ChunkType),
+    // private, ancillary and safe to copy, different on every call
+    Random,
+    // the same type for the same passphrase, so decode can find it again
+    Passphrase(String),
+    Mimic,
+}
+
+impl TypeStrategy {
+    // The chunk type to use in `png`, generated types avoid those already in it.
+    // Mimicked types are the private ones in `registry`.
+    pub fn chunk_type(&self, png: &Png, registry: &Registry) -> Result<ChunkType, String> {
+        let is_unused = |t: &ChunkType| png.chunks().iter().all(|c| c.chunk_type() != t);
+        match self {
+            TypeStrategy::Given(chunk_type) => Ok(chunk_type.clone()),
+            TypeStrategy::Passphrase(passphrase) => Ok(from_passphrase(passphrase)),
+            TypeStrategy::Random => loop {
+                let chunk_type = from_number(random()? as u32);
+                if is_unused(&chunk_type) {
+                    return Ok(chunk_type);
+                }
+            },
+            TypeStrategy::Mimic => {
+                let unused: Vec<ChunkType> = mimicked_types(registry).filter(is_unused).collect();
+                if unused.is_empty() {
+                    return Err(String::from("no unused chunk type left to mimic"));
+                }
+                Ok(unused[random()? as usize % unused.len()].clone())
+            }
+        }
+    }
+}
+
+// A chunk type, "random", "mimic" or "passphrase:<PASSPHRASE>".
+impl FromStr for TypeStrategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "random" => return Ok(TypeStrategy::Random),
+            "mimic" => return Ok(TypeStrategy::Mimic),
+            _ => {}
+        }
+        if let Some(passphrase) = s.strip_prefix("passphrase:") {
+            if passphrase.is_empty() {
+                return Err(String::from("empty passphrase"));
+            }
+            return Ok(TypeStrategy::Passphrase(passphrase.to_string()));
+        }
+        let chunk_type = ChunkType::from_str(s)?;
+        Ok(TypeStrategy::Given(chunk_type))
+    }
+}
+
+// The chunk type a message was encoded in, from a chunk type or a passphrase.
+pub fn lookup_type(s: &str) -> Result<ChunkType, String> {
+    match TypeStrategy::from_str(s) {
+        Ok(TypeStrategy::Passphrase(passphrase)) => Ok(from_passphrase(&passphrase)),
+        Ok(TypeStrategy::Given(chunk_type)) => Ok(chunk_type),
+        Ok(TypeStrategy::Random | TypeStrategy::Mimic) => Err(format!(
+            "a {} chunk type cannot be found again, use the one encode reported",
+            s
+        )),
+        Err(e) => Err(e),
+    }
+}
+
+// Private chunks written by common software, a message in one of them looks
+// like something an image editor left behind.
+fn mimicked_types(registry: &Registry) -> impl Iterator<Item = ChunkType> + '_ {
+    registry
+        .chunks()
+        .iter()
+        .map(|c| c.chunk_type.clone())
+        .filter(|t| !t.is_critical() && !t.is_public() && t.is_reserved_bit_valid())
+}
+
+pub fn from_passphrase(passphrase: &str) -> ChunkType {
+    from_number(crc32(passphrase.as_bytes()))
+}
+
+// Four letters from `n`: lowercase but for the third, which keeps the reserved
+// bit unset, so the type is ancillary, private and safe to copy.
+fn from_number(mut n: u32) -> ChunkType {
+    let mut bytes = [0; 4];
+    for byte in &mut bytes {
+        *byte = b'a' + (n % 26) as u8;
+        n /= 26;
+    }
+    bytes[2] = bytes[2].to_ascii_uppercase();
+    ChunkType::try_from(bytes).expect("letters")
+}
+
+fn random() -> Result<u64, String> {
+    getrandom::u64().map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::Chunk;
+
+    fn is_stealthy(chunk_type: &ChunkType) -> bool {
+        !chunk_type.is_critical()
+            && !chunk_type.is_public()
+            && chunk_type.is_reserved_bit_valid()
+            && chunk_type.is_safe_to_copy()
+    }
+
+    #[test]
+    fn test_generated_types() {
+        let png = Png::from_chunks(vec![]);
+        let registry = Registry::builtin();
+        for _ in 0..100 {
+            assert!(is_stealthy(
+                &TypeStrategy::Random.chunk_type(&png, &registry).unwrap()
+            ));
+        }
+        let passphrase = TypeStrategy::from_str("passphrase:open sesame").unwrap();
+        let chunk_type = passphrase.chunk_type(&png, &registry).unwrap();
+        assert!(is_stealthy(&chunk_type));
+        assert_eq!(chunk_type, from_passphrase("open sesame"));
+        assert_ne!(chunk_type, from_passphrase("open sesame!"));
+    }
+
+    #[test]
+    fn test_mimic() {
+        // all but one of the names are taken
+        let registry = Registry::builtin();
+        let mimicked: Vec<ChunkType> = mimicked_types(&registry).collect();
+        assert!(mimicked.len() > 10);
+        assert!(mimicked.iter().all(|t| !t.is_critical() && !t.is_public()));
+        let png = Png::from_chunks(
+            mimicked[1..]
+                .iter()
+                .map(|t| Chunk::new(t.clone(), vec![]))
+                .collect(),
+        );
+        let chunk_type = TypeStrategy::Mimic.chunk_type(&png, &registry).unwrap();
+        assert_eq!(chunk_type, mimicked[0]);
+    }
+
+    #[test]
+    fn test_invalid_types() {
+        let error = |s: &str| TypeStrategy::from_str(s).unwrap_err();
+        assert_eq!(
+            error("ruS"),
+            "invalid chunk type \"ruS\", expected 4 letters but got 3"
+        );
+        assert_eq!(
+            error("r_St"),
+            "invalid chunk type \"r_St\", '_' is not an ASCII letter"
+        );
+        assert_eq!(error("passphrase:"), "empty passphrase");
+        assert_eq!(lookup_type("RuSt").unwrap().to_string(), "RuSt");
+        assert_eq!(lookup_type("passphrase:x").unwrap(), from_passphrase("x"));
+        assert!(lookup_type("random").is_err());
+        assert_eq!(
+            TypeStrategy::from_str("ruSt").unwrap(),
+            TypeStrategy::Given(ChunkType::from_str("ruSt").unwrap())
+        );
+    }
+}
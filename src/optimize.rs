@@ -0,0 +1,397 @@
+use std::{
+    collections::{HashMap, HashSet},
+    str::FromStr,
+};
+
+use crate::{
+    chunk::Chunk,
+    chunk_type::ChunkType,
+    image::{self, ColorType, FilterStrategy, ImageHeader},
+    png::Png,
+};
+
+// Chunks whose contents depend on the color type or bit depth. Their presence
+// disables color reductions, which would otherwise have to rewrite them. APNG
+// frames in fdAT chunks share the color type of the default image.
+const COLOR_DEPENDENT_CHUNKS: [&str; 7] = ["PLTE", "tRNS", "bKGD", "sBIT", "hIST", "acTL", "fdAT"];
+
+pub struct OptimizeOptions {
+    pub levels: Vec<u32>,
+    pub strategies: Vec<FilterStrategy>,
+    pub reduce: bool,
+}
+
+impl Default for OptimizeOptions {
+    fn default() -> Self {
+        OptimizeOptions {
+            levels: vec![6, 9],
+            strategies: FilterStrategy::ALL.to_vec(),
+            reduce: true,
+        }
+    }
+}
+
+struct Candidate {
+    header: ImageHeader,
+    raw: Vec<u8>,
+    // (PLTE data, tRNS data) for candidates reduced to a palette
+    palette: Option<(Vec<u8>, Vec<u8>)>,
+}
+
+// Recompresses the image data, trying every filter strategy and compression
+// level in `options`, and when lossless converts it to a smaller color type or
+// bit depth. All IDAT chunks are merged into one and every other chunk is kept
+// as is. Returns a copy of `png` when nothing smaller was found.
+pub fn optimize(png: &Png, options: &OptimizeOptions) -> Result<Png, String> {
+    let header = ImageHeader::from_png(png)?;
+    let data = image::inflate_image_data(png)?;
+
+    // Adam7 passes are not unfiltered, interlaced images are only recompressed
+    let best = if header.interlaced {
+        options
+            .levels
+            .iter()
+            .map(|&level| build(png, &header, None, image::deflate(&data, level)))
+            .min_by_key(|p| p.as_bytes().len())
+    } else {
+        let raw = image::unfilter(&data, &header)?;
+        let mut candidates = Vec::new();
+        if options.reduce && !has_color_dependent_chunks(png) {
+            if let Some(reduced) = reduce(&header, &raw) {
+                candidates.push(reduced);
+            }
+        }
+        candidates.push(Candidate {
+            header,
+            raw,
+            palette: None,
+        });
+
+        let mut best: Option<Png> = None;
+        for candidate in &candidates {
+            for &strategy in &options.strategies {
+                let filtered = image::filter(&candidate.raw, &candidate.header, strategy);
+                for &level in &options.levels {
+                    let compressed = image::deflate(&filtered, level);
+                    let smallest = best.as_ref().map_or(usize::MAX, |b| b.as_bytes().len());
+                    let optimized = build(
+                        png,
+                        &candidate.header,
+                        candidate.palette.as_ref(),
+                        compressed,
+                    );
+                    if optimized.as_bytes().len() < smallest {
+                        best = Some(optimized);
+                    }
+                }
+            }
+        }
+        best
+    };
+
+    match best {
+        Some(best) if best.as_bytes().len() < png.as_bytes().len() => Ok(best),
+        _ => Ok(png.clone()),
+    }
+}
+
+fn has_color_dependent_chunks(png: &Png) -> bool {
+    png.chunks()
+        .iter()
+        .any(|c| COLOR_DEPENDENT_CHUNKS.contains(&c.chunk_type().to_string().as_str()))
+}
+
+fn build(
+    png: &Png,
+    header: &ImageHeader,
+    palette: Option<&(Vec<u8>, Vec<u8>)>,
+    image_data: Vec<u8>,
+) -> Png {
+    let chunk = |chunk_type: &str, data: Vec<u8>| {
+        Chunk::new(
+            ChunkType::from_str(chunk_type).expect("valid chunk type"),
+            data,
+        )
+    };
+    let mut image_data = Some(image_data);
+    let mut chunks = Vec::new();
+    for c in png.chunks() {
+        match c.chunk_type().to_string().as_str() {
+            "IHDR" => chunks.push(chunk("IHDR", header.as_bytes())),
+            "IDAT" => {
+                // the first IDAT is replaced by the merged data, the rest dropped
+                if let Some(data) = image_data.take() {
+                    if let Some((plte, trns)) = palette {
+                        chunks.push(chunk("PLTE", plte.clone()));
+                        if !trns.is_empty() {
+                            chunks.push(chunk("tRNS", trns.clone()));
+                        }
+                    }
+                    chunks.push(chunk("IDAT", data));
+                }
+            }
+            _ => chunks.push(c.clone()),
+        }
+    }
+    Png::from_chunks(chunks)
+}
+
+// Applies every lossless reduction that fits the image: 16 to 8 bits per
+// sample, dropping an alpha channel that is fully opaque, and converting
+// truecolor images with at most 256 colors to a palette.
+fn reduce(header: &ImageHeader, raw: &[u8]) -> Option<Candidate> {
+    if header.color_type == ColorType::Indexed || header.bit_depth < 8 {
+        return None;
+    }
+    let mut header = *header;
+    let mut raw = raw.to_vec();
+    let mut reduced = false;
+
+    if header.bit_depth == 16 && raw.chunks_exact(2).all(|s| s[0] == s[1]) {
+        raw = raw.iter().step_by(2).copied().collect();
+        header.bit_depth = 8;
+        reduced = true;
+    }
+
+    let has_alpha = matches!(
+        header.color_type,
+        ColorType::GrayscaleAlpha | ColorType::Rgba
+    );
+    let pixel_size = header.bits_per_pixel() / 8;
+    let alpha_size = header.bit_depth as usize / 8;
+    if has_alpha
+        && raw
+            .chunks_exact(pixel_size)
+            .all(|p| p[pixel_size - alpha_size..].iter().all(|&b| b == 0xff))
+    {
+        raw = raw
+            .chunks_exact(pixel_size)
+            .flat_map(|p| p[..pixel_size - alpha_size].iter().copied())
+            .collect();
+        header.color_type = match header.color_type {
+            ColorType::Rgba => ColorType::Rgb,
+            _ => ColorType::Grayscale,
+        };
+        reduced = true;
+    }
+
+    if header.bit_depth == 8 && matches!(header.color_type, ColorType::Rgb | ColorType::Rgba) {
+        if let Some(candidate) = to_palette(&header, &raw) {
+            return Some(candidate);
+        }
+    }
+
+    reduced.then_some(Candidate {
+        header,
+        raw,
+        palette: None,
+    })
+}
+
+fn to_palette(header: &ImageHeader, raw: &[u8]) -> Option<Candidate> {
+    let channels = header.color_type.channels();
+    let rgba = |p: &[u8]| [p[0], p[1], p[2], if channels == 4 { p[3] } else { 255 }];
+
+    let mut colors: Vec<[u8; 4]> = Vec::new();
+    let mut seen = HashSet::new();
+    for pixel in raw.chunks_exact(channels) {
+        let color = rgba(pixel);
+        if seen.insert(color) {
+            colors.push(color);
+            if colors.len() > 256 {
+                return None;
+            }
+        }
+    }
+    // transparent entries first, so tRNS only needs to cover those
+    colors.sort_by_key(|c| (c[3] == 255, *c));
+    let indices: HashMap<[u8; 4], u8> = colors
+        .iter()
+        .enumerate()
+        .map(|(i, c)| (*c, i as u8))
+        .collect();
+
+    let bit_depth: u8 = match colors.len() {
+        0..=2 => 1,
+        3..=4 => 2,
+        5..=16 => 4,
+        _ => 8,
+    };
+    let palette_header = ImageHeader {
+        bit_depth,
+        color_type: ColorType::Indexed,
+        ..*header
+    };
+    let stride = palette_header.stride();
+    let width = header.width as usize;
+    let mut packed = vec![0u8; stride * header.height as usize];
+    for (row, pixels) in raw.chunks_exact(width * channels).enumerate() {
+        for (x, pixel) in pixels.chunks_exact(channels).enumerate() {
+            let index = indices[&rgba(pixel)];
+            let bit = x * bit_depth as usize;
+            let shift = 8 - bit_depth as usize - bit % 8;
+            packed[row * stride + bit / 8] |= index << shift;
+        }
+    }
+
+    let plte = colors.iter().flat_map(|c| c[..3].to_vec()).collect();
+    let trns = colors
+        .iter()
+        .take_while(|c| c[3] != 255)
+        .map(|c| c[3])
+        .collect();
+    Some(Candidate {
+        header: palette_header,
+        raw: packed,
+        palette: Some((plte, trns)),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn testing_png(header: &ImageHeader, raw: &[u8], extra: Vec<Chunk>) -> Png {
+        let chunk = |t: &str, d: Vec<u8>| Chunk::new(ChunkType::from_str(t).unwrap(), d);
+        let filtered = image::filter(raw, header, FilterStrategy::None);
+        let data = image::deflate(&filtered, 0);
+        // split the image data over several IDAT chunks
+        let mut chunks = vec![chunk("IHDR", header.as_bytes())];
+        chunks.extend(data.chunks(100).map(|d| chunk("IDAT", d.to_vec())));
+        chunks.push(chunk("IEND", vec![]));
+        chunks.extend(extra);
+        Png::from_chunks(chunks)
+    }
+
+    // Decodes a non interlaced 8 bit RGB, RGBA or indexed PNG to RGBA.
+    fn to_rgba(png: &Png) -> Vec<u8> {
+        let header = ImageHeader::from_png(png).unwrap();
+        let raw = image::unfilter(&image::inflate_image_data(png).unwrap(), &header).unwrap();
+        let stride = header.stride();
+        let mut rgba = Vec::new();
+        for row in raw.chunks_exact(stride) {
+            for x in 0..header.width as usize {
+                match header.color_type {
+                    ColorType::Rgb => {
+                        rgba.extend_from_slice(&[row[x * 3], row[x * 3 + 1], row[x * 3 + 2], 255])
+                    }
+                    ColorType::Rgba => rgba.extend_from_slice(&row[x * 4..x * 4 + 4]),
+                    ColorType::Indexed => {
+                        let depth = header.bit_depth as usize;
+                        let bit = x * depth;
+                        let index = (row[bit / 8] >> (8 - depth - bit % 8)) & ((1 << depth) - 1);
+                        let index = index as usize;
+                        let plte = png.chunk_by_type("PLTE").unwrap().data();
+                        let alpha = png
+                            .chunk_by_type("tRNS")
+                            .and_then(|c| c.data().get(index).copied())
+                            .unwrap_or(255);
+                        rgba.extend_from_slice(&plte[index * 3..index * 3 + 3]);
+                        rgba.push(alpha);
+                    }
+                    _ => unreachable!(),
+                }
+            }
+        }
+        rgba
+    }
+
+    fn rgba_header(width: u32, height: u32) -> ImageHeader {
+        ImageHeader {
+            width,
+            height,
+            bit_depth: 8,
+            color_type: ColorType::Rgba,
+            interlaced: false,
+        }
+    }
+
+    #[test]
+    fn test_optimize_to_palette() {
+        let header = rgba_header(64, 64);
+        let colors = [[255, 0, 0, 255], [0, 255, 0, 128], [0, 0, 255, 255]];
+        // noise, so the smaller pixels pay for the PLTE and tRNS chunks
+        let mut state = 0x2545f491u32;
+        let raw: Vec<u8> = (0..64 * 64)
+            .flat_map(|_| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                colors[state as usize % 3]
+            })
+            .collect();
+        let message = Chunk::new(ChunkType::from_str("heLo").unwrap(), b"hi".to_vec());
+        let png = testing_png(&header, &raw, vec![message.clone()]);
+
+        let optimized = optimize(&png, &OptimizeOptions::default()).unwrap();
+        let optimized_header = ImageHeader::from_png(&optimized).unwrap();
+
+        assert_eq!(optimized_header.color_type, ColorType::Indexed);
+        assert_eq!(optimized_header.bit_depth, 2);
+        assert_eq!(optimized.chunk_by_type("tRNS").unwrap().data(), &[128]);
+        assert_eq!(to_rgba(&optimized), raw);
+        assert_eq!(optimized.chunk_by_type("heLo"), Some(&message));
+        assert!(optimized.as_bytes().len() < png.as_bytes().len());
+    }
+
+    #[test]
+    fn test_optimize_strips_opaque_alpha() {
+        let header = rgba_header(40, 40);
+        let raw: Vec<u8> = (0..40 * 40)
+            .flat_map(|i: usize| {
+                [
+                    (i % 251) as u8,
+                    (i / 3 % 256) as u8,
+                    (i * 7 % 256) as u8,
+                    255,
+                ]
+            })
+            .collect();
+        let png = testing_png(&header, &raw, vec![]);
+
+        let optimized = optimize(&png, &OptimizeOptions::default()).unwrap();
+
+        let optimized_header = ImageHeader::from_png(&optimized).unwrap();
+        assert_eq!(optimized_header.color_type, ColorType::Rgb);
+        assert_eq!(to_rgba(&optimized), raw);
+        let idats = optimized
+            .chunks()
+            .iter()
+            .filter(|c| c.chunk_type().to_string() == "IDAT")
+            .count();
+        assert_eq!(idats, 1);
+    }
+
+    #[test]
+    fn test_reduce_16_bit() {
+        let header = ImageHeader {
+            bit_depth: 16,
+            color_type: ColorType::Grayscale,
+            ..rgba_header(4, 1)
+        };
+        let raw = vec![1, 1, 2, 2, 3, 3, 4, 4];
+        let reduced = reduce(&header, &raw).unwrap();
+        assert_eq!(reduced.header.bit_depth, 8);
+        assert_eq!(reduced.raw, vec![1, 2, 3, 4]);
+
+        assert!(reduce(&header, &[1, 2, 2, 2, 3, 3, 4, 4]).is_none());
+    }
+
+    #[test]
+    fn test_no_reduction_with_color_dependent_chunks() {
+        let header = rgba_header(8, 8);
+        let raw: Vec<u8> = (0..64).flat_map(|_| [10, 20, 30, 255]).collect();
+        // a background color and an APNG frame in the format of IHDR
+        for (chunk_type, length) in [("bKGD", 6), ("fdAT", 4)] {
+            let extra = Chunk::new(ChunkType::from_str(chunk_type).unwrap(), vec![0; length]);
+            let png = testing_png(&header, &raw, vec![extra]);
+
+            let optimized = optimize(&png, &OptimizeOptions::default()).unwrap();
+            assert_eq!(
+                ImageHeader::from_png(&optimized).unwrap().color_type,
+                ColorType::Rgba
+            );
+            assert_eq!(to_rgba(&optimized), raw);
+        }
+    }
+}
@@ -0,0 +1,205 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    process,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+// Tells apart the temporary files of threads writing in the same process.
+static TMP_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+#[derive(Default)]
+pub struct WriteOptions {
+    pub backup: Option<String>,
+    pub preserve_mtime: bool,
+    pub dry_run: bool,
+}
+
+// Writes to a temporary file next to `path` and renames it over the original,
+// so a crash or a full disk never leaves a half written file behind. A symlink
+// is followed, the file it points to is replaced and the link kept.
+pub fn write_file(path: &str, data: &[u8], options: &WriteOptions) -> io::Result<()> {
+    let path = &resolve_symlinks(Path::new(path));
+    let original = fs::metadata(path).ok();
+    let tmp_path = tmp_path_for(path);
+
+    let result = write_tmp(&tmp_path, data, original.as_ref(), options);
+    if let Err(e) = result {
+        let _ = fs::remove_file(&tmp_path);
+        return Err(e);
+    }
+
+    if original.is_some() {
+        if let Some(suffix) = &options.backup {
+            let mut backup_path = path.as_os_str().to_owned();
+            backup_path.push(suffix);
+            if let Err(e) = fs::copy(path, backup_path) {
+                let _ = fs::remove_file(&tmp_path);
+                return Err(e);
+            }
+        }
+    }
+
+    fs::rename(&tmp_path, path)?;
+    sync_dir(path)
+}
+
+// Writes a file that must not exist yet, failing with AlreadyExists otherwise.
+pub fn write_new_file(path: &Path, data: &[u8]) -> io::Result<()> {
+    let mut file = OpenOptions::new().write(true).create_new(true).open(path)?;
+    file.write_all(data)?;
+    file.sync_all()
+}
+
+fn write_tmp(
+    tmp_path: &Path,
+    data: &[u8],
+    original: Option<&fs::Metadata>,
+    options: &WriteOptions,
+) -> io::Result<()> {
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(tmp_path)?;
+    file.write_all(data)?;
+    if let Some(metadata) = original {
+        file.set_permissions(metadata.permissions())?;
+        if options.preserve_mtime {
+            file.set_modified(metadata.modified()?)?;
+        }
+    }
+    file.sync_all()
+}
+
+fn tmp_path_for(path: &Path) -> PathBuf {
+    let file_name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    path.with_file_name(format!(
+        ".{}.{}.{}.pngme-tmp",
+        file_name,
+        process::id(),
+        TMP_COUNTER.fetch_add(1, Ordering::Relaxed)
+    ))
+}
+
+// The file at the end of a chain of symlinks, relative targets are relative to
+// the link's directory. Gives up after as many links as Linux follows.
+fn resolve_symlinks(path: &Path) -> PathBuf {
+    let mut path = path.to_path_buf();
+    for _ in 0..40 {
+        match fs::read_link(&path) {
+            Ok(target) => {
+                path = match path.parent() {
+                    Some(dir) => dir.join(target),
+                    None => target,
+                }
+            }
+            Err(_) => break,
+        }
+    }
+    path
+}
+
+#[cfg(unix)]
+fn sync_dir(path: &Path) -> io::Result<()> {
+    match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => File::open(dir)?.sync_all(),
+        _ => File::open(".")?.sync_all(),
+    }
+}
+
+#[cfg(not(unix))]
+fn sync_dir(_path: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, SystemTime};
+
+    fn testing_path(name: &str) -> String {
+        let dir = std::env::temp_dir().join(format!("pngme-output-{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        dir.join(name).to_string_lossy().into_owned()
+    }
+
+    fn no_backup() -> WriteOptions {
+        WriteOptions {
+            backup: None,
+            preserve_mtime: false,
+            dry_run: false,
+        }
+    }
+
+    #[test]
+    fn test_write_new_file() {
+        let path = testing_path("new.png");
+        write_file(&path, b"data", &no_backup()).unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"data");
+        let leftovers = fs::read_dir(Path::new(&path).parent().unwrap())
+            .unwrap()
+            .filter(|e| {
+                let name = e.as_ref().unwrap().file_name();
+                name.to_string_lossy().starts_with(".new.png.")
+            })
+            .count();
+        assert_eq!(leftovers, 0);
+    }
+
+    #[test]
+    fn test_tmp_paths_are_unique() {
+        let path = Path::new("image.png");
+        assert_ne!(tmp_path_for(path), tmp_path_for(path));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_write_through_symlink() {
+        let path = testing_path("target.png");
+        let link = testing_path("link.png");
+        fs::write(&path, b"old").unwrap();
+        let _ = fs::remove_file(&link);
+        std::os::unix::fs::symlink("target.png", &link).unwrap();
+        write_file(&link, b"new", &no_backup()).unwrap();
+        assert!(fs::symlink_metadata(&link).unwrap().is_symlink());
+        assert_eq!(fs::read(&path).unwrap(), b"new");
+    }
+
+    #[test]
+    fn test_write_keeps_backup() {
+        let path = testing_path("backup.png");
+        fs::write(&path, b"old").unwrap();
+        let options = WriteOptions {
+            backup: Some(String::from(".bak")),
+            preserve_mtime: false,
+            dry_run: false,
+        };
+        write_file(&path, b"new", &options).unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"new");
+        assert_eq!(fs::read(format!("{}.bak", path)).unwrap(), b"old");
+    }
+
+    #[test]
+    fn test_write_preserves_mtime() {
+        let path = testing_path("mtime.png");
+        fs::write(&path, b"old").unwrap();
+        let mtime = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000_000);
+        File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(mtime)
+            .unwrap();
+        let options = WriteOptions {
+            backup: None,
+            preserve_mtime: true,
+            dry_run: false,
+        };
+        write_file(&path, b"new", &options).unwrap();
+        assert_eq!(fs::metadata(&path).unwrap().modified().unwrap(), mtime);
+    }
+}
@@ -0,0 +1,740 @@
+use std::{fmt::Display, str::FromStr};
+
+#[cfg(feature = "json")]
+use serde_json::{json, Value};
+
+use crate::{
+    chunk::Chunk,
+    image::{self, ColorType, ImageHeader},
+    png::{ChunkPosition, Png},
+    util::{chunk, read_u16},
+};
+
+// The contents of the PLTE chunk.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Palette {
+    pub colors: Vec<[u8; 3]>,
+}
+
+impl Palette {
+    pub fn from_png(png: &Png) -> Result<Option<Palette>, String> {
+        png.chunk_by_type("PLTE")
+            .map(|c| Palette::try_from(c.data()))
+            .transpose()
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        self.colors.concat()
+    }
+
+    pub fn to_chunk(&self) -> Chunk {
+        chunk("PLTE", self.as_bytes())
+    }
+}
+
+impl TryFrom<&[u8]> for Palette {
+    type Error = String;
+
+    fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
+        if value.is_empty() || !value.len().is_multiple_of(3) || value.len() > 256 * 3 {
+            return Err(String::from("invalid PLTE length"));
+        }
+        Ok(Palette {
+            colors: value.chunks_exact(3).map(|c| [c[0], c[1], c[2]]).collect(),
+        })
+    }
+}
+
+impl Display for Palette {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let colors: Vec<String> = self.colors.iter().map(|c| hex(c)).collect();
+        write!(f, "{} colors {}", self.colors.len(), colors.join(" "))
+    }
+}
+
+// The contents of the tRNS chunk, whose layout depends on the color type.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Transparency {
+    // alpha of the first palette entries, the others are opaque
+    Alphas(Vec<u8>),
+    Gray(u16),
+    Rgb(u16, u16, u16),
+}
+
+impl Transparency {
+    pub fn parse(data: &[u8], color_type: ColorType) -> Result<Transparency, String> {
+        match (color_type, data.len()) {
+            (ColorType::Indexed, 0..=256) => Ok(Transparency::Alphas(data.to_vec())),
+            (ColorType::Grayscale, 2) => Ok(Transparency::Gray(read_u16(data, 0))),
+            (ColorType::Rgb, 6) => Ok(Transparency::Rgb(
+                read_u16(data, 0),
+                read_u16(data, 2),
+                read_u16(data, 4),
+            )),
+            (ColorType::GrayscaleAlpha | ColorType::Rgba, _) => Err(String::from(
+                "tRNS is not allowed for color types with alpha",
+            )),
+            _ => Err(String::from("invalid tRNS length")),
+        }
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        match self {
+            Transparency::Alphas(alphas) => alphas.clone(),
+            Transparency::Gray(gray) => gray.to_be_bytes().to_vec(),
+            Transparency::Rgb(r, g, b) => [r, g, b].iter().flat_map(|v| v.to_be_bytes()).collect(),
+        }
+    }
+
+    pub fn to_chunk(&self) -> Chunk {
+        chunk("tRNS", self.as_bytes())
+    }
+}
+
+// The contents of the bKGD chunk, whose layout depends on the color type.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Background {
+    Index(u8),
+    Gray(u16),
+    Rgb(u16, u16, u16),
+}
+
+impl Background {
+    pub fn parse(data: &[u8], color_type: ColorType) -> Result<Background, String> {
+        match (color_type, data.len()) {
+            (ColorType::Indexed, 1) => Ok(Background::Index(data[0])),
+            (ColorType::Grayscale | ColorType::GrayscaleAlpha, 2) => {
+                Ok(Background::Gray(read_u16(data, 0)))
+            }
+            (ColorType::Rgb | ColorType::Rgba, 6) => Ok(Background::Rgb(
+                read_u16(data, 0),
+                read_u16(data, 2),
+                read_u16(data, 4),
+            )),
+            _ => Err(String::from("invalid bKGD length")),
+        }
+    }
+}
+
+impl Display for Background {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Background::Index(index) => write!(f, "palette entry {}", index),
+            Background::Gray(gray) => write!(f, "gray {}", gray),
+            Background::Rgb(r, g, b) => write!(f, "rgb({}, {}, {})", r, g, b),
+        }
+    }
+}
+
+// The contents of the hIST chunk, how often each palette entry is used.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Histogram {
+    pub frequencies: Vec<u16>,
+}
+
+impl TryFrom<&[u8]> for Histogram {
+    type Error = String;
+
+    fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
+        if value.is_empty() || !value.len().is_multiple_of(2) || value.len() > 512 {
+            return Err(String::from("invalid hIST length"));
+        }
+        Ok(Histogram {
+            frequencies: (0..value.len())
+                .step_by(2)
+                .map(|i| read_u16(value, i))
+                .collect(),
+        })
+    }
+}
+
+impl Display for Histogram {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} frequencies", self.frequencies.len())
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct SuggestedEntry {
+    pub red: u16,
+    pub green: u16,
+    pub blue: u16,
+    pub alpha: u16,
+    pub frequency: u16,
+}
+
+// The contents of an sPLT chunk, a named palette suggested for displays with
+// fewer colors. Samples are 8 or 16 bits deep.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct SuggestedPalette {
+    pub name: String,
+    pub depth: u8,
+    pub entries: Vec<SuggestedEntry>,
+}
+
+impl SuggestedPalette {
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let mut bytes: Vec<u8> = self.name.chars().map(|c| c as u8).collect();
+        bytes.extend_from_slice(&[0, self.depth]);
+        for e in &self.entries {
+            for sample in [e.red, e.green, e.blue, e.alpha] {
+                match self.depth {
+                    8 => bytes.push(sample as u8),
+                    _ => bytes.extend_from_slice(&sample.to_be_bytes()),
+                }
+            }
+            bytes.extend_from_slice(&e.frequency.to_be_bytes());
+        }
+        bytes
+    }
+}
+
+impl TryFrom<&[u8]> for SuggestedPalette {
+    type Error = String;
+
+    fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
+        let separator = value
+            .iter()
+            .position(|&b| b == 0)
+            .filter(|&s| (1..=79).contains(&s))
+            .ok_or_else(|| String::from("invalid sPLT name"))?;
+        let depth = *value
+            .get(separator + 1)
+            .ok_or_else(|| String::from("missing sPLT depth"))?;
+        let entry_size = match depth {
+            8 => 6,
+            16 => 10,
+            _ => return Err(String::from("invalid sPLT depth")),
+        };
+        let entries = &value[separator + 2..];
+        if !entries.len().is_multiple_of(entry_size) {
+            return Err(String::from("invalid sPLT length"));
+        }
+        let sample = |e: &[u8], i: usize| match depth {
+            8 => e[i] as u16,
+            _ => read_u16(e, i * 2),
+        };
+        Ok(SuggestedPalette {
+            name: value[..separator].iter().map(|&b| b as char).collect(),
+            depth,
+            entries: entries
+                .chunks_exact(entry_size)
+                .map(|e| SuggestedEntry {
+                    red: sample(e, 0),
+                    green: sample(e, 1),
+                    blue: sample(e, 2),
+                    alpha: sample(e, 3),
+                    frequency: read_u16(e, entry_size - 2),
+                })
+                .collect(),
+        })
+    }
+}
+
+impl Display for SuggestedPalette {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:?}, {} entries of depth {}",
+            self.name,
+            self.entries.len(),
+            self.depth
+        )
+    }
+}
+
+// The palette of a PNG with the alpha from tRNS, as RGBA colors.
+pub fn colors(png: &Png) -> Result<Vec<[u8; 4]>, String> {
+    let palette = Palette::from_png(png)?.ok_or_else(|| String::from("no palette"))?;
+    let header = ImageHeader::from_png(png)?;
+    let alphas = match png.chunk_by_type("tRNS") {
+        Some(chunk) if header.color_type == ColorType::Indexed => {
+            match Transparency::parse(chunk.data(), header.color_type)? {
+                Transparency::Alphas(alphas) => alphas,
+                _ => Vec::new(),
+            }
+        }
+        _ => Vec::new(),
+    };
+    Ok(palette
+        .colors
+        .iter()
+        .enumerate()
+        .map(|(i, c)| [c[0], c[1], c[2], alphas.get(i).copied().unwrap_or(255)])
+        .collect())
+}
+
+// Replaces the palette of `png` with `colors`, updating tRNS from their alpha
+// and dropping hIST, which counts the old colors. Fails when the image data uses
+// indices that the new palette does not have. Returns the chunks removed.
+pub fn set_colors(png: &mut Png, colors: &[[u8; 4]]) -> Result<Vec<Chunk>, String> {
+    let header = ImageHeader::from_png(png)?;
+    if colors.is_empty() || colors.len() > 256 {
+        return Err(String::from("a palette needs between 1 and 256 colors"));
+    }
+    match header.color_type {
+        ColorType::Indexed => {
+            if colors.len() > 1 << header.bit_depth {
+                return Err(format!(
+                    "{} colors do not fit in a bit depth of {}",
+                    colors.len(),
+                    header.bit_depth
+                ));
+            }
+            let used = max_index(png, &header)?;
+            if used.is_some_and(|i| i as usize >= colors.len()) {
+                return Err(format!(
+                    "the image uses palette entry {}, the new palette only has {} colors",
+                    used.unwrap_or_default(),
+                    colors.len()
+                ));
+            }
+            if let Some(chunk) = png.chunk_by_type("bKGD") {
+                if let Background::Index(index) =
+                    Background::parse(chunk.data(), header.color_type)?
+                {
+                    if index as usize >= colors.len() {
+                        return Err(format!("bKGD uses palette entry {}", index));
+                    }
+                }
+            }
+        }
+        ColorType::Rgb | ColorType::Rgba => {}
+        _ => return Err(String::from("grayscale images cannot have a palette")),
+    }
+
+    let position = match png
+        .chunks()
+        .iter()
+        .position(|c| c.chunk_type().to_string() == "PLTE")
+    {
+        Some(index) => index,
+        // a new palette goes right before the image data
+        None => png
+            .chunks()
+            .iter()
+            .position(|c| c.chunk_type().to_string() == "IDAT")
+            .ok_or_else(|| String::from("missing IDAT chunk"))?,
+    };
+    let mut removed = Vec::new();
+    png.remove_all("PLTE");
+    let palette = Palette {
+        colors: colors.iter().map(|c| [c[0], c[1], c[2]]).collect(),
+    };
+    png.insert_chunk(ChunkPosition::Index(position), palette.to_chunk())?;
+
+    if header.color_type == ColorType::Indexed {
+        // tRNS only needs to cover the colors up to the last transparent one
+        let transparent = colors
+            .iter()
+            .rposition(|c| c[3] != 255)
+            .map_or(0, |i| i + 1);
+        let trns_position = png
+            .chunks()
+            .iter()
+            .position(|c| c.chunk_type().to_string() == "tRNS");
+        png.remove_all("tRNS");
+        if transparent > 0 {
+            let alphas = Transparency::Alphas(colors[..transparent].iter().map(|c| c[3]).collect());
+            png.insert_chunk(
+                ChunkPosition::Index(trns_position.unwrap_or(position + 1)),
+                alphas.to_chunk(),
+            )?;
+        }
+    }
+    removed.extend(png.remove_all("hIST"));
+    Ok(removed)
+}
+
+// The largest palette index used by the image data.
+fn max_index(png: &Png, header: &ImageHeader) -> Result<Option<u8>, String> {
+    let data = image::inflate_image_data(png)?;
+    let passes = if header.interlaced {
+        image::adam7_passes(header)
+    } else {
+        vec![*header]
+    };
+    let depth = header.bit_depth as usize;
+    let mut max = None;
+    let mut start = 0;
+    for pass in passes {
+        let end = start + pass.height as usize * (pass.stride() + 1);
+        let filtered = data
+            .get(start..end)
+            .ok_or_else(|| String::from("invalid image data length"))?;
+        let raw = image::unfilter(filtered, &pass)?;
+        for row in raw.chunks_exact(pass.stride()) {
+            for x in 0..pass.width as usize {
+                let bit = x * depth;
+                let index = (row[bit / 8] >> (8 - depth - bit % 8)) & ((1u16 << depth) - 1) as u8;
+                max = max.max(Some(index));
+            }
+        }
+        start = end;
+    }
+    Ok(max)
+}
+
+// Lists the problems with the palette related chunks of `png`: missing or
+// forbidden palettes and tRNS, bKGD or hIST that do not match it.
+pub fn validate(png: &Png) -> Result<Vec<String>, String> {
+    let header = ImageHeader::from_png(png)?;
+    let palette = Palette::from_png(png)?;
+    let mut problems = Vec::new();
+    let palette_len = palette.as_ref().map_or(0, |p| p.colors.len());
+    match (header.color_type, &palette) {
+        (ColorType::Indexed, None) => problems.push(String::from("PLTE is missing")),
+        (ColorType::Indexed, Some(p)) if p.colors.len() > 1 << header.bit_depth => {
+            problems.push(format!(
+                "PLTE has {} colors, more than a bit depth of {} allows",
+                p.colors.len(),
+                header.bit_depth
+            ))
+        }
+        (ColorType::Grayscale | ColorType::GrayscaleAlpha, Some(_)) => {
+            problems.push(String::from("PLTE is not allowed for grayscale images"))
+        }
+        _ => {}
+    }
+
+    if let Some(chunk) = png.chunk_by_type("tRNS") {
+        match Transparency::parse(chunk.data(), header.color_type) {
+            Ok(Transparency::Alphas(alphas)) if alphas.len() > palette_len => {
+                problems.push(format!(
+                    "tRNS has {} entries but PLTE has {}",
+                    alphas.len(),
+                    palette_len
+                ))
+            }
+            Err(e) => problems.push(format!("tRNS: {}", e)),
+            _ => {}
+        }
+    }
+    if let Some(chunk) = png.chunk_by_type("bKGD") {
+        match Background::parse(chunk.data(), header.color_type) {
+            Ok(Background::Index(index)) if index as usize >= palette_len => problems.push(
+                format!("bKGD uses entry {} but PLTE has {}", index, palette_len),
+            ),
+            Err(e) => problems.push(format!("bKGD: {}", e)),
+            _ => {}
+        }
+    }
+    if let Some(chunk) = png.chunk_by_type("hIST") {
+        match Histogram::try_from(chunk.data()) {
+            Ok(h) if h.frequencies.len() != palette_len => problems.push(format!(
+                "hIST has {} entries but PLTE has {}",
+                h.frequencies.len(),
+                palette_len
+            )),
+            Err(e) => problems.push(format!("hIST: {}", e)),
+            _ => {}
+        }
+    }
+
+    let position = |t: &str| {
+        png.chunks()
+            .iter()
+            .position(|c| c.chunk_type().to_string() == t)
+    };
+    if let (Some(plte), Some(idat)) = (position("PLTE"), position("IDAT")) {
+        if plte > idat {
+            problems.push(String::from("PLTE must come before IDAT"));
+        }
+    }
+    for chunk_type in ["tRNS", "bKGD", "hIST"] {
+        if let Some(index) = position(chunk_type) {
+            if position("PLTE").is_some_and(|p| index < p) {
+                problems.push(format!("{} must come after PLTE", chunk_type));
+            }
+            if position("IDAT").is_some_and(|i| index > i) {
+                problems.push(format!("{} must come before IDAT", chunk_type));
+            }
+        }
+    }
+    Ok(problems)
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum PaletteFormat {
+    // GIMP palette
+    Gpl,
+    // JASC (Paint Shop Pro) palette
+    Pal,
+    #[cfg(feature = "json")]
+    Json,
+}
+
+impl PaletteFormat {
+    // Only JSON keeps the alpha of the colors.
+    pub fn has_alpha(self) -> bool {
+        match self {
+            PaletteFormat::Gpl | PaletteFormat::Pal => false,
+            #[cfg(feature = "json")]
+            PaletteFormat::Json => true,
+        }
+    }
+}
+
+impl FromStr for PaletteFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "gpl" => Ok(PaletteFormat::Gpl),
+            "pal" => Ok(PaletteFormat::Pal),
+            #[cfg(feature = "json")]
+            "json" => Ok(PaletteFormat::Json),
+            #[cfg(not(feature = "json"))]
+            "json" => Err(String::from("JSON palettes need the json feature")),
+            _ => Err(String::from(
+                "invalid palette format, expected gpl, pal or json",
+            )),
+        }
+    }
+}
+
+pub fn export(colors: &[[u8; 4]], format: PaletteFormat, name: &str) -> String {
+    match format {
+        PaletteFormat::Gpl => {
+            let mut text = format!("GIMP Palette\nName: {}\nColumns: 16\n#\n", name);
+            for c in colors {
+                text.push_str(&format!("{:3} {:3} {:3}\t{}\n", c[0], c[1], c[2], hex(c)));
+            }
+            text
+        }
+        PaletteFormat::Pal => {
+            let mut text = format!("JASC-PAL\r\n0100\r\n{}\r\n", colors.len());
+            for c in colors {
+                text.push_str(&format!("{} {} {}\r\n", c[0], c[1], c[2]));
+            }
+            text
+        }
+        #[cfg(feature = "json")]
+        PaletteFormat::Json => {
+            let colors: Vec<Value> = colors
+                .iter()
+                .map(|c| json!({"red": c[0], "green": c[1], "blue": c[2], "alpha": c[3]}))
+                .collect();
+            let value = json!({"name": name, "colors": colors});
+            serde_json::to_string_pretty(&value).expect("serializing a Value never fails") + "\n"
+        }
+    }
+}
+
+pub fn import(text: &str, format: PaletteFormat) -> Result<Vec<[u8; 4]>, String> {
+    let channel = |v: Option<&str>| {
+        v.and_then(|v| v.parse::<u8>().ok())
+            .ok_or_else(|| String::from("invalid color"))
+    };
+    let rgb = |line: &str| -> Result<[u8; 4], String> {
+        let mut values = line.split_whitespace();
+        Ok([
+            channel(values.next())?,
+            channel(values.next())?,
+            channel(values.next())?,
+            255,
+        ])
+    };
+    match format {
+        PaletteFormat::Gpl => {
+            let mut lines = text.lines();
+            if lines.next().map(str::trim) != Some("GIMP Palette") {
+                return Err(String::from("not a GIMP palette"));
+            }
+            lines
+                .map(str::trim)
+                .filter(|l| {
+                    !l.is_empty()
+                        && !l.starts_with('#')
+                        && !l.starts_with("Name:")
+                        && !l.starts_with("Columns:")
+                })
+                .map(rgb)
+                .collect()
+        }
+        PaletteFormat::Pal => {
+            let lines: Vec<&str> = text.lines().map(str::trim).collect();
+            let [magic, _version, count, colors @ ..] = &lines[..] else {
+                return Err(String::from("not a JASC palette"));
+            };
+            if *magic != "JASC-PAL" {
+                return Err(String::from("not a JASC palette"));
+            }
+            let count: usize = count
+                .parse()
+                .map_err(|_| String::from("invalid color count"))?;
+            if colors.len() < count {
+                return Err(String::from("missing colors"));
+            }
+            colors[..count].iter().map(|l| rgb(l)).collect()
+        }
+        #[cfg(feature = "json")]
+        PaletteFormat::Json => {
+            let value: Value =
+                serde_json::from_str(text).map_err(|e| format!("invalid JSON: {}", e))?;
+            let colors = value["colors"]
+                .as_array()
+                .ok_or_else(|| String::from("missing colors array"))?;
+            colors
+                .iter()
+                .map(|c| {
+                    let field = |name: &str, default: Option<u8>| match &c[name] {
+                        Value::Null => default.ok_or_else(|| format!("missing {}", name)),
+                        v => v
+                            .as_u64()
+                            .and_then(|v| u8::try_from(v).ok())
+                            .ok_or_else(|| format!("invalid {}", name)),
+                    };
+                    Ok([
+                        field("red", None)?,
+                        field("green", None)?,
+                        field("blue", None)?,
+                        field("alpha", Some(255))?,
+                    ])
+                })
+                .collect()
+        }
+    }
+}
+
+pub fn hex(color: &[u8]) -> String {
+    color[..3]
+        .iter()
+        .fold(String::from("#"), |s, c| s + &format!("{:02x}", c))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A 4x1 indexed image with 2 bits per pixel using entries 0 to 2.
+    fn testing_png(extra: Vec<Chunk>) -> Png {
+        let header = ImageHeader {
+            width: 4,
+            height: 1,
+            bit_depth: 2,
+            color_type: ColorType::Indexed,
+            interlaced: false,
+        };
+        let mut chunks = vec![chunk("IHDR", header.as_bytes())];
+        chunks.extend(extra);
+        // filter type 0, pixels 0 1 2 1
+        chunks.push(chunk("IDAT", image::deflate(&[0, 0b00011001], 9)));
+        chunks.push(chunk("IEND", vec![]));
+        Png::from_chunks(chunks)
+    }
+
+    fn testing_palette() -> Palette {
+        Palette {
+            colors: vec![[255, 0, 0], [0, 255, 0], [0, 0, 255]],
+        }
+    }
+
+    #[test]
+    fn test_palette_round_trip() {
+        let palette = testing_palette();
+        assert_eq!(
+            Palette::try_from(&palette.as_bytes()[..]),
+            Ok(palette.clone())
+        );
+        assert_eq!(palette.to_string(), "3 colors #ff0000 #00ff00 #0000ff");
+        assert!(Palette::try_from(&[0, 0][..]).is_err());
+    }
+
+    #[test]
+    fn test_suggested_palette_round_trip() {
+        let bytes = b"web\0\x08\xff\x00\x00\xff\x00\x10".to_vec();
+        let palette = SuggestedPalette::try_from(&bytes[..]).unwrap();
+        assert_eq!(palette.entries[0].red, 255);
+        assert_eq!(palette.entries[0].frequency, 16);
+        assert_eq!(palette.as_bytes(), bytes);
+    }
+
+    #[test]
+    fn test_validate() {
+        let png = testing_png(vec![
+            testing_palette().to_chunk(),
+            chunk("tRNS", vec![0, 0, 0, 0]),
+            chunk("hIST", vec![0, 1]),
+        ]);
+        assert_eq!(
+            validate(&png).unwrap(),
+            vec![
+                "tRNS has 4 entries but PLTE has 3",
+                "hIST has 1 entries but PLTE has 3",
+            ]
+        );
+        assert_eq!(
+            validate(&testing_png(vec![])).unwrap(),
+            vec!["PLTE is missing"]
+        );
+    }
+
+    #[test]
+    fn test_set_colors() {
+        let mut png = testing_png(vec![
+            testing_palette().to_chunk(),
+            chunk("hIST", vec![0, 1, 0, 2, 0, 1]),
+        ]);
+        let new_colors = [[1, 1, 1, 0], [2, 2, 2, 255], [3, 3, 3, 128], [4, 4, 4, 255]];
+        let removed = set_colors(&mut png, &new_colors).unwrap();
+        assert_eq!(removed.len(), 1);
+        assert!(png.chunk_by_type("hIST").is_none());
+        assert_eq!(colors(&png).unwrap(), new_colors);
+        assert_eq!(png.chunk_by_type("tRNS").unwrap().data(), &[0, 255, 128]);
+        assert!(validate(&png).unwrap().is_empty());
+
+        // pixel data uses entry 2
+        assert!(set_colors(&mut png, &new_colors[..2]).is_err());
+        // a bit depth of 2 allows 4 colors
+        assert!(set_colors(&mut png, &[[0; 4]; 5]).is_err());
+
+        // the histogram counts the old colors even when there are as many
+        png.append_chunk(chunk("hIST", vec![0, 1, 0, 2, 0, 1, 0, 0]));
+        let removed = set_colors(&mut png, &new_colors).unwrap();
+        assert_eq!(removed.len(), 1);
+    }
+
+    #[test]
+    fn test_set_colors_interlaced() {
+        // a 3x3 image has pixels in passes 1, 4, 5, 6 and 7: 0, 1, 2 2, 1 1, 1 1 1
+        let header = ImageHeader {
+            width: 3,
+            height: 3,
+            bit_depth: 2,
+            color_type: ColorType::Indexed,
+            interlaced: true,
+        };
+        let passes = [
+            0, 0b00000000, 0, 0b01000000, 0, 0b10100000, 0, 0b01000000, 0, 0b01000000, 0,
+            0b01010100,
+        ];
+        let mut png = Png::from_chunks(vec![
+            chunk("IHDR", header.as_bytes()),
+            testing_palette().to_chunk(),
+            chunk("IDAT", image::deflate(&passes, 9)),
+            chunk("IEND", vec![]),
+        ]);
+        assert_eq!(max_index(&png, &header), Ok(Some(2)));
+        assert!(set_colors(&mut png, &[[0; 4]; 2]).is_err());
+        assert!(set_colors(&mut png, &[[0; 4]; 3]).is_ok());
+    }
+
+    #[test]
+    fn test_formats_round_trip() {
+        let colors = vec![[255, 0, 0, 255], [0, 128, 255, 255]];
+        for format in [PaletteFormat::Gpl, PaletteFormat::Pal] {
+            let text = export(&colors, format, "test");
+            assert_eq!(import(&text, format).unwrap(), colors, "{:?}", format);
+        }
+        assert!(import("not a palette", PaletteFormat::Gpl).is_err());
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn test_json_round_trip() {
+        let colors = vec![[255, 0, 0, 255], [1, 2, 3, 4]];
+        let text = export(&colors, PaletteFormat::Json, "test");
+        assert_eq!(import(&text, PaletteFormat::Json).unwrap(), colors);
+    }
+}
@@ -1,8 +1,35 @@
-use std::fmt::Display;
+use std::{fmt::Display, str::FromStr};
+
+use crate::{
+    apng,
+    chunk::{Chunk, ChunkRef},
+};
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ChunkPosition {
+    // right after IHDR
+    Start,
+    // right before IEND
+    End,
+    Index(usize),
+}
 
-use crate::chunk::Chunk;
+impl FromStr for ChunkPosition {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "start" => Ok(ChunkPosition::Start),
+            "end" => Ok(ChunkPosition::End),
+            _ => s
+                .parse::<usize>()
+                .map(ChunkPosition::Index)
+                .map_err(|_| String::from("invalid chunk position")),
+        }
+    }
+}
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq, Eq, Clone)]
 pub struct Png {
     header: [u8; 8],
     chunks: Vec<Chunk>,
@@ -11,14 +38,14 @@ pub struct Png {
 impl Png {
     pub const STANDARD_HEADER: [u8; 8] = [137, 80, 78, 71, 13, 10, 26, 10];
 
-    fn from_chunks(chunks: Vec<Chunk>) -> Png {
+    pub fn from_chunks(chunks: Vec<Chunk>) -> Png {
         Png {
             header: Png::STANDARD_HEADER,
             chunks,
         }
     }
 
-    fn chunks(&self) -> &[Chunk] {
+    pub fn chunks(&self) -> &[Chunk] {
         &self.chunks
     }
 
@@ -26,15 +53,67 @@ impl Png {
         self.chunks.push(chunk);
     }
 
+    pub fn insert_chunk(&mut self, position: ChunkPosition, chunk: Chunk) -> Result<usize, String> {
+        let index = match position {
+            ChunkPosition::Start => self
+                .chunks
+                .iter()
+                .position(|c| c.chunk_type().to_string() == "IHDR")
+                .map_or(0, |i| i + 1),
+            ChunkPosition::End => self
+                .chunks
+                .iter()
+                .rposition(|c| c.chunk_type().to_string() == "IEND")
+                .unwrap_or(self.chunks.len()),
+            ChunkPosition::Index(i) if i <= self.chunks.len() => i,
+            ChunkPosition::Index(_) => return Err(String::from("invalid chunk position")),
+        };
+        let renumber = apng::is_sequence_chunk(&chunk);
+        self.chunks.insert(index, chunk);
+        if renumber {
+            apng::renumber(&mut self.chunks);
+        }
+        Ok(index)
+    }
+
     pub fn remove_first_chunk(&mut self, chunk_type: &str) -> Option<Chunk> {
         for (i, c) in self.chunks.iter().enumerate() {
             if c.chunk_type().to_string() == chunk_type {
-                return Some(self.chunks.remove(i));
+                let removed = self.chunks.remove(i);
+                if apng::is_sequence_chunk(&removed) {
+                    apng::renumber(&mut self.chunks);
+                }
+                return Some(removed);
             }
         }
         None
     }
 
+    pub fn remove_chunks_where<F>(&mut self, mut predicate: F) -> Vec<Chunk>
+    where
+        F: FnMut(&Chunk) -> bool,
+    {
+        let mut removed = Vec::new();
+        let mut kept = Vec::with_capacity(self.chunks.len());
+        for chunk in self.chunks.drain(..) {
+            if predicate(&chunk) {
+                removed.push(chunk);
+            } else {
+                kept.push(chunk);
+            }
+        }
+        self.chunks = kept;
+        // keep the APNG sequence numbers contiguous
+        if removed.iter().any(apng::is_sequence_chunk) {
+            apng::renumber(&mut self.chunks);
+        }
+        removed
+    }
+
+    pub fn remove_all(&mut self, chunk_type: &str) -> Vec<Chunk> {
+        self.remove_chunks_where(|c| c.chunk_type().to_string() == chunk_type)
+    }
+
     fn header(&self) -> &[u8; 8] {
         &self.header
     }
@@ -45,11 +124,19 @@ impl Png {
             .iter()
             .filter(|t| t.chunk_type().to_string() == chunk_type)
             .collect();
-        if chunks.len() > 0 {
+        if !chunks.is_empty() {
             return Some(chunks[0]);
         }
         None
     }
+
+    pub fn chunk_by_type_at(&self, chunk_type: &str, index: usize) -> Option<&Chunk> {
+        self.chunks()
+            .iter()
+            .filter(|c| c.chunk_type().to_string() == chunk_type)
+            .nth(index)
+    }
+
     pub fn as_bytes(&self) -> Vec<u8> {
         let mut header_bytes = self.header().to_vec();
         let mut chunk_bytes: Vec<u8> = self.chunks.iter().flat_map(|c| c.as_bytes()).collect();
@@ -62,24 +149,87 @@ impl TryFrom<&[u8]> for Png {
     type Error = String;
 
     fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
-        let mut chunks: Vec<Chunk> = Vec::new();
-        let header: [u8; 8] = core::array::from_fn(|j| value[j]);
-        if header != Png::STANDARD_HEADER {
+        PngRef::try_from(value).map(|png| png.to_png())
+    }
+}
+
+// A PNG whose chunks borrow from the parsed bytes, for commands that only read.
+#[derive(Debug, Clone)]
+pub struct PngRef<'a> {
+    header: [u8; 8],
+    chunks: Vec<ChunkRef<'a>>,
+}
+
+impl<'a> PngRef<'a> {
+    // Walks the chunk headers without checking any crc, so only the length and
+    // type of each chunk are read. Check `ChunkRef::has_valid_crc` on the
+    // chunks that are actually used.
+    pub fn scan(value: &'a [u8]) -> Result<PngRef<'a>, String> {
+        let mut chunks: Vec<ChunkRef> = Vec::new();
+        if value.len() < 8 || value[..8] != Png::STANDARD_HEADER {
             return Err(String::from("invalid header"));
         }
+        let header = Png::STANDARD_HEADER;
         let mut i = 8;
         while i < value.len() - 1 {
-            let parsed_length: [u8; 4] = core::array::from_fn(|j| value[i + j]);
-            let parsed_length = u32::from_be_bytes(parsed_length);
-            let length_of_chunk = 12 + parsed_length;
-            let chunk = match Chunk::try_from(&value[i..length_of_chunk as usize + i]) {
+            let chunk = match ChunkRef::parse_unchecked(&value[i..]) {
                 Ok(chunk) => chunk,
                 Err(_) => return Err(String::from("invalid chunk")),
             };
+            i += chunk.as_bytes().len();
             chunks.push(chunk);
-            i += length_of_chunk as usize;
         }
-        Ok(Png { chunks, header })
+        Ok(PngRef { header, chunks })
+    }
+
+    pub fn header(&self) -> &[u8; 8] {
+        &self.header
+    }
+
+    pub fn chunks(&self) -> &[ChunkRef<'a>] {
+        &self.chunks
+    }
+
+    pub fn chunk_by_type(&self, chunk_type: &str) -> Option<&ChunkRef<'a>> {
+        self.chunk_by_type_at(chunk_type, 0)
+    }
+
+    pub fn chunk_by_type_at(&self, chunk_type: &str, index: usize) -> Option<&ChunkRef<'a>> {
+        self.chunks
+            .iter()
+            .filter(|c| c.chunk_type().to_string() == chunk_type)
+            .nth(index)
+    }
+
+    pub fn to_png(&self) -> Png {
+        Png {
+            header: self.header,
+            chunks: self.chunks.iter().map(|c| c.to_chunk()).collect(),
+        }
+    }
+}
+
+impl<'a> TryFrom<&'a [u8]> for PngRef<'a> {
+    type Error = String;
+
+    fn try_from(value: &'a [u8]) -> Result<Self, Self::Error> {
+        let png = PngRef::scan(value)?;
+        if !png.chunks.iter().all(|c| c.has_valid_crc()) {
+            return Err(String::from("invalid chunk"));
+        }
+        Ok(png)
+    }
+}
+
+impl Display for PngRef<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let string_chunks: Vec<String> = self.chunks.iter().map(|c| c.to_string()).collect();
+        write!(
+            f,
+            "Png: {{ header: {:?}, chunks: {:?} }}",
+            self.header(),
+            string_chunks
+        )
     }
 }
 
@@ -117,8 +267,6 @@ mod tests {
     }
 
     fn chunk_from_strings(chunk_type: &str, data: &str) -> Result<Chunk, String> {
-        use std::str::FromStr;
-
         let chunk_type = ChunkType::from_str(chunk_type)?;
         let data: Vec<u8> = data.bytes().collect();
 
@@ -193,6 +341,45 @@ mod tests {
         assert!(png.is_err_and(|e| e.as_str() == "invalid chunk"));
     }
 
+    #[test]
+    fn test_truncated_png() {
+        let bytes = &PNG_FILE[..PNG_FILE.len() - 3];
+        let png = Png::try_from(bytes);
+
+        assert!(png.is_err_and(|e| e.as_str() == "invalid chunk"));
+        assert!(Png::try_from(&PNG_FILE[..4]).is_err());
+    }
+
+    #[test]
+    fn test_png_ref_matches_png() {
+        let png_ref = PngRef::try_from(&PNG_FILE[..]).unwrap();
+        let png = Png::try_from(&PNG_FILE[..]).unwrap();
+
+        assert_eq!(png_ref.chunks().len(), png.chunks().len());
+        assert_eq!(png_ref.to_string(), png.to_string());
+        assert_eq!(png_ref.to_png().as_bytes(), PNG_FILE.to_vec());
+        assert_eq!(
+            png_ref
+                .chunk_by_type("RuSt")
+                .unwrap()
+                .data_as_str()
+                .unwrap(),
+            "hey"
+        );
+    }
+
+    #[test]
+    fn test_png_ref_scan_ignores_crc() {
+        let mut bytes = PNG_FILE.to_vec();
+        // flip a bit inside the IDAT data
+        bytes[100] ^= 1;
+
+        assert!(PngRef::try_from(&bytes[..]).is_err());
+        let png = PngRef::scan(&bytes).unwrap();
+        assert!(png.chunk_by_type("RuSt").unwrap().has_valid_crc());
+        assert!(!png.chunk_by_type("IDAT").unwrap().has_valid_crc());
+    }
+
     #[test]
     fn test_list_chunks() {
         let png = testing_png();
@@ -226,6 +413,73 @@ mod tests {
         assert!(chunk.is_none());
     }
 
+    #[test]
+    fn test_remove_all() {
+        let mut png = testing_png();
+        png.append_chunk(chunk_from_strings("TeSt", "first").unwrap());
+        png.append_chunk(chunk_from_strings("TeSt", "second").unwrap());
+        let removed = png.remove_all("TeSt");
+        assert_eq!(removed.len(), 2);
+        assert_eq!(png.chunks().len(), 3);
+        assert!(png.chunk_by_type("TeSt").is_none());
+    }
+
+    #[test]
+    fn test_remove_chunks_where() {
+        let mut png = testing_png();
+        let removed = png.remove_chunks_where(|c| !c.chunk_type().is_critical());
+        assert_eq!(removed.len(), 1);
+        assert_eq!(&removed[0].chunk_type().to_string(), "miDl");
+        assert_eq!(png.chunks().len(), 2);
+    }
+
+    #[test]
+    fn test_chunk_by_type_at() {
+        let mut png = testing_png();
+        png.append_chunk(chunk_from_strings("TeSt", "first").unwrap());
+        png.append_chunk(chunk_from_strings("TeSt", "second").unwrap());
+        let chunk = png.chunk_by_type_at("TeSt", 1).unwrap();
+        assert_eq!(&chunk.data_as_string().unwrap(), "second");
+        assert!(png.chunk_by_type_at("TeSt", 2).is_none());
+    }
+
+    #[test]
+    fn test_insert_chunk() {
+        let mut png = Png::try_from(&PNG_FILE[..]).unwrap();
+        let start = png
+            .insert_chunk(
+                ChunkPosition::Start,
+                chunk_from_strings("TeSt", "a").unwrap(),
+            )
+            .unwrap();
+        assert_eq!(start, 1);
+        let end = png
+            .insert_chunk(ChunkPosition::End, chunk_from_strings("TeSt", "b").unwrap())
+            .unwrap();
+        assert_eq!(end, png.chunks().len() - 2);
+        assert_eq!(
+            &png.chunks().last().unwrap().chunk_type().to_string(),
+            "IEND"
+        );
+
+        let chunk = chunk_from_strings("TeSt", "c").unwrap();
+        assert!(png.insert_chunk(ChunkPosition::Index(100), chunk).is_err());
+    }
+
+    #[test]
+    fn test_chunk_position_from_str() {
+        assert_eq!(
+            ChunkPosition::from_str("start").unwrap(),
+            ChunkPosition::Start
+        );
+        assert_eq!(ChunkPosition::from_str("end").unwrap(), ChunkPosition::End);
+        assert_eq!(
+            ChunkPosition::from_str("3").unwrap(),
+            ChunkPosition::Index(3)
+        );
+        assert!(ChunkPosition::from_str("middle").is_err());
+    }
+
     #[test]
     fn test_png_from_image_file() {
         let png = Png::try_from(&PNG_FILE[..]);
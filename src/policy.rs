@@ -0,0 +1,119 @@
+use std::fmt::Display;
+
+use crate::{chunk_type::ChunkType, registry::Registry};
+
+// Why a chunk type is a bad place for a message.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Violation {
+    // decoders fail on critical chunks they do not know, or misread known ones
+    Critical(ChunkType),
+    ReservedBit(ChunkType),
+    // a public ancillary chunk that readers parse as what it is registered for
+    Registered(ChunkType),
+}
+
+impl Violation {
+    // Errors refuse the chunk type unless forced, the others are warnings.
+    pub fn is_error(&self) -> bool {
+        !matches!(self, Violation::Registered(_))
+    }
+}
+
+impl Display for Violation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Violation::Critical(t) => write!(
+                f,
+                "chunk type {} is critical, the first letter must be lowercase",
+                t
+            ),
+            Violation::ReservedBit(t) => write!(
+                f,
+                "chunk type {} has the reserved bit set, the third letter must be uppercase",
+                t
+            ),
+            Violation::Registered(t) => write!(
+                f,
+                "chunk type {} is a registered chunk, readers will try to parse the message as one",
+                t
+            ),
+        }
+    }
+}
+
+// Everything wrong with writing a message to a chunk of this type, registered
+// types are those in `registry`.
+pub fn check(chunk_type: &ChunkType, registry: &Registry) -> Vec<Violation> {
+    let mut violations = Vec::new();
+    if chunk_type.is_critical() {
+        violations.push(Violation::Critical(chunk_type.clone()));
+    }
+    if !chunk_type.is_reserved_bit_valid() {
+        violations.push(Violation::ReservedBit(chunk_type.clone()));
+    }
+    if !chunk_type.is_critical() && chunk_type.is_public() && registry.is_registered(chunk_type) {
+        violations.push(Violation::Registered(chunk_type.clone()));
+    }
+    violations
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn check_str(chunk_type: &str) -> Vec<Violation> {
+        check(
+            &ChunkType::from_str(chunk_type).unwrap(),
+            &Registry::builtin(),
+        )
+    }
+
+    fn chunk_type(chunk_type: &str) -> ChunkType {
+        ChunkType::from_str(chunk_type).unwrap()
+    }
+
+    #[test]
+    fn test_private_ancillary_is_allowed() {
+        assert_eq!(check_str("ruSt"), []);
+        assert_eq!(check_str("ruST"), []);
+    }
+
+    #[test]
+    fn test_critical() {
+        assert_eq!(check_str("IDAT"), [Violation::Critical(chunk_type("IDAT"))]);
+        assert_eq!(check_str("RuSt"), [Violation::Critical(chunk_type("RuSt"))]);
+        assert!(check_str("IEND")[0].is_error());
+        assert_eq!(
+            check_str("IEND")[0].to_string(),
+            "chunk type IEND is critical, the first letter must be lowercase"
+        );
+    }
+
+    #[test]
+    fn test_reserved_bit() {
+        assert_eq!(
+            check_str("rust"),
+            [Violation::ReservedBit(chunk_type("rust"))]
+        );
+        assert!(check_str("rust")[0].is_error());
+        assert_eq!(
+            check_str("Rust"),
+            [
+                Violation::Critical(chunk_type("Rust")),
+                Violation::ReservedBit(chunk_type("Rust"))
+            ]
+        );
+    }
+
+    #[test]
+    fn test_registered_public_ancillary_is_a_warning() {
+        assert_eq!(
+            check_str("tEXt"),
+            [Violation::Registered(chunk_type("tEXt"))]
+        );
+        assert!(!check_str("tIME")[0].is_error());
+        // public but not registered
+        assert_eq!(check_str("rUSt"), []);
+    }
+}
@@ -0,0 +1,466 @@
+use std::{fmt::Display, str::FromStr};
+
+use toml::{Table, Value};
+
+use crate::{chunk_type::ChunkType, metadata};
+
+// Renders chunk data human readable, None when it does not parse.
+pub type Parser = fn(&str, &[u8]) -> Option<String>;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Multiplicity {
+    One,
+    Optional,
+    Many,
+}
+
+impl FromStr for Multiplicity {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "one" => Ok(Multiplicity::One),
+            "optional" => Ok(Multiplicity::Optional),
+            "many" => Ok(Multiplicity::Many),
+            _ => Err(String::from(
+                "invalid multiplicity, expected one, optional or many",
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ChunkInfo {
+    pub chunk_type: ChunkType,
+    pub name: String,
+    // where the chunk is specified, None for private chunks
+    pub section: Option<String>,
+    pub multiplicity: Multiplicity,
+    // chunk types this one has to come before or after when present, "*" for
+    // all others apart from those that have to come before or after this one
+    pub before: Vec<String>,
+    pub after: Vec<String>,
+    pub parser: Option<Parser>,
+}
+
+impl ChunkInfo {
+    pub fn is_registered(&self) -> bool {
+        self.section.is_some()
+    }
+}
+
+impl Display for ChunkInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.section {
+            Some(section) => write!(f, "{} ({})", self.name, section),
+            None => write!(f, "{}", self.name),
+        }
+    }
+}
+
+const COLOR: &[&str] = &["PLTE", "IDAT"];
+const DATA: &[&str] = &["IDAT"];
+const PALETTE: &[&str] = &["PLTE"];
+const HEADER: &[&str] = &["IHDR"];
+const ALL: &[&str] = &["*"];
+
+// type, name, section, multiplicity, before, after
+type Entry = (
+    &'static str,
+    &'static str,
+    Option<&'static str>,
+    Multiplicity,
+    &'static [&'static str],
+    &'static [&'static str],
+);
+
+// Sections of the PNG Third Edition and of the PNG extensions.
+#[rustfmt::skip]
+const BUILTIN: [Entry; 47] = [
+    ("IHDR", "Image header", Some("11.2.1"), Multiplicity::One, ALL, &[]),
+    ("PLTE", "Palette", Some("11.2.2"), Multiplicity::Optional, DATA, &[]),
+    ("IDAT", "Image data", Some("11.2.3"), Multiplicity::Many, &[], &[]),
+    ("IEND", "Image trailer", Some("11.2.4"), Multiplicity::One, &[], ALL),
+    ("tRNS", "Transparency", Some("11.3.1.1"), Multiplicity::Optional, DATA, PALETTE),
+    ("cHRM", "Primary chromaticities and white point", Some("11.3.2.1"), Multiplicity::Optional, COLOR, &[]),
+    ("gAMA", "Image gamma", Some("11.3.2.2"), Multiplicity::Optional, COLOR, &[]),
+    ("iCCP", "Embedded ICC profile", Some("11.3.2.3"), Multiplicity::Optional, COLOR, &[]),
+    ("sBIT", "Significant bits", Some("11.3.2.4"), Multiplicity::Optional, COLOR, &[]),
+    ("sRGB", "Standard RGB color space", Some("11.3.2.5"), Multiplicity::Optional, COLOR, &[]),
+    ("cICP", "Coding-independent code points", Some("11.3.2.6"), Multiplicity::Optional, COLOR, &[]),
+    ("mDCV", "Mastering display color volume", Some("11.3.2.7"), Multiplicity::Optional, COLOR, &[]),
+    ("cLLI", "Content light level information", Some("11.3.2.8"), Multiplicity::Optional, COLOR, &[]),
+    ("tEXt", "Textual data", Some("11.3.3.3"), Multiplicity::Many, &[], &[]),
+    ("zTXt", "Compressed textual data", Some("11.3.3.4"), Multiplicity::Many, &[], &[]),
+    ("iTXt", "International textual data", Some("11.3.3.5"), Multiplicity::Many, &[], &[]),
+    ("bKGD", "Background color", Some("11.3.4.1"), Multiplicity::Optional, DATA, PALETTE),
+    ("hIST", "Image histogram", Some("11.3.4.2"), Multiplicity::Optional, DATA, PALETTE),
+    ("pHYs", "Physical pixel dimensions", Some("11.3.4.3"), Multiplicity::Optional, DATA, &[]),
+    ("sPLT", "Suggested palette", Some("11.3.4.4"), Multiplicity::Many, DATA, &[]),
+    ("eXIf", "Exchangeable image file profile", Some("11.3.4.5"), Multiplicity::Optional, DATA, &[]),
+    ("tIME", "Image last-modification time", Some("11.3.5.1"), Multiplicity::Optional, &[], &[]),
+    ("acTL", "Animation control", Some("11.3.6.1"), Multiplicity::Optional, DATA, &[]),
+    ("fcTL", "Frame control", Some("11.3.6.2"), Multiplicity::Many, &[], &[]),
+    ("fdAT", "Frame data", Some("11.3.6.3"), Multiplicity::Many, &[], DATA),
+    ("oFFs", "Image offset", Some("extensions 4.1.1"), Multiplicity::Optional, DATA, &[]),
+    ("pCAL", "Calibration of pixel values", Some("extensions 4.1.2"), Multiplicity::Optional, DATA, &[]),
+    ("sCAL", "Physical scale of image subject", Some("extensions 4.1.3"), Multiplicity::Optional, DATA, &[]),
+    ("gIFg", "GIF graphic control extension", Some("extensions 4.1.4"), Multiplicity::Many, &[], &[]),
+    ("gIFt", "GIF plain text extension, deprecated", Some("extensions 4.1.5"), Multiplicity::Many, &[], &[]),
+    ("gIFx", "GIF application extension", Some("extensions 4.1.6"), Multiplicity::Many, &[], &[]),
+    ("sTER", "Indicator of stereo image", Some("extensions 4.1.7"), Multiplicity::Optional, DATA, &[]),
+    ("dSIG", "Digital signature", Some("extensions 4.1.8"), Multiplicity::Many, &[], &[]),
+    ("CgBI", "Apple iOS optimized PNG", None, Multiplicity::Optional, HEADER, &[]),
+    ("iDOT", "Apple multithreaded decoding offsets", None, Multiplicity::Optional, DATA, &[]),
+    ("npTc", "Android nine-patch data", None, Multiplicity::Optional, &[], &[]),
+    ("npLb", "Android nine-patch layout bounds", None, Multiplicity::Optional, &[], &[]),
+    ("npOl", "Android nine-patch outline", None, Multiplicity::Optional, &[], &[]),
+    ("vpAg", "ImageMagick virtual page", None, Multiplicity::Optional, &[], &[]),
+    ("caNv", "ImageMagick canvas", None, Multiplicity::Optional, &[], &[]),
+    ("orNT", "ImageMagick orientation", None, Multiplicity::Optional, &[], &[]),
+    ("prVW", "Fireworks preview", None, Multiplicity::Optional, &[], &[]),
+    ("mkBF", "Fireworks private data", None, Multiplicity::Many, &[], &[]),
+    ("mkBS", "Fireworks private data", None, Multiplicity::Many, &[], &[]),
+    ("mkBT", "Fireworks private data", None, Multiplicity::Many, &[], &[]),
+    ("mkTS", "Fireworks private data", None, Multiplicity::Many, &[], &[]),
+    ("msOG", "Microsoft Office GIF data", None, Multiplicity::Optional, &[], &[]),
+];
+
+// metadata::describe knows these
+const DESCRIBED: [&str; 9] = [
+    "pHYs", "tIME", "oFFs", "sCAL", "sBIT", "PLTE", "hIST", "sPLT", "eXIf",
+];
+
+#[derive(Debug, Clone)]
+pub struct Registry {
+    chunks: Vec<ChunkInfo>,
+}
+
+impl Registry {
+    // The registered chunk types and private ones written by common software.
+    pub fn builtin() -> Registry {
+        Registry {
+            chunks: BUILTIN
+                .iter()
+                .map(
+                    |&(chunk_type, name, section, multiplicity, before, after)| ChunkInfo {
+                        chunk_type: ChunkType::from_str(chunk_type).expect("valid chunk type"),
+                        name: String::from(name),
+                        section: section.map(String::from),
+                        multiplicity,
+                        before: before.iter().map(|t| t.to_string()).collect(),
+                        after: after.iter().map(|t| t.to_string()).collect(),
+                        parser: DESCRIBED
+                            .contains(&chunk_type)
+                            .then_some(metadata::describe as Parser),
+                    },
+                )
+                .collect(),
+        }
+    }
+
+    pub fn chunks(&self) -> &[ChunkInfo] {
+        &self.chunks
+    }
+
+    pub fn get(&self, chunk_type: &ChunkType) -> Option<&ChunkInfo> {
+        self.chunks.iter().find(|c| c.chunk_type == *chunk_type)
+    }
+
+    pub fn is_registered(&self, chunk_type: &ChunkType) -> bool {
+        self.get(chunk_type).is_some_and(ChunkInfo::is_registered)
+    }
+
+    pub fn describe(&self, chunk_type: &ChunkType, data: &[u8]) -> Option<String> {
+        let parser = self.get(chunk_type)?.parser?;
+        parser(&chunk_type.to_string(), data)
+    }
+
+    // Adds the chunk types of a TOML file, replacing known ones of the same type:
+    //
+    //   [[chunk]]
+    //   type = "buIl"
+    //   name = "Build information"
+    //   section = "in-house spec 2.1"   # optional
+    //   multiplicity = "optional"       # one, optional or many, many by default
+    //   before = ["IDAT"]               # optional, "*" for all other chunks
+    //   after = ["IHDR"]                # optional
+    pub fn extend_from_toml(&mut self, text: &str) -> Result<(), String> {
+        let table = Table::from_str(text).map_err(|e| e.message().to_string())?;
+        if let Some(key) = table.keys().find(|k| *k != "chunk") {
+            return Err(format!("unknown key {}", key));
+        }
+        let chunks = match table.get("chunk") {
+            Some(Value::Array(chunks)) => chunks,
+            Some(_) => return Err(String::from("chunk must be an array of tables")),
+            None => return Ok(()),
+        };
+        for (i, chunk) in chunks.iter().enumerate() {
+            let info = chunk
+                .as_table()
+                .ok_or_else(|| String::from("not a table"))
+                .and_then(chunk_info)
+                .map_err(|e| format!("chunk {}: {}", i, e))?;
+            self.chunks.retain(|c| c.chunk_type != info.chunk_type);
+            self.chunks.push(info);
+        }
+        Ok(())
+    }
+
+    // Problems with the chunk order and count of a file with these chunks.
+    pub fn validate(&self, chunk_types: &[ChunkType]) -> Vec<String> {
+        let mut problems = Vec::new();
+        let positions = |t: &str, except: &[&ChunkType]| -> Vec<usize> {
+            (0..chunk_types.len())
+                .filter(|&i| t == "*" || chunk_types[i].to_string() == t)
+                .filter(|&i| !except.contains(&&chunk_types[i]))
+                .collect()
+        };
+        for info in &self.chunks {
+            let name = info.chunk_type.to_string();
+            let own = positions(&name, &[]);
+            // the types that have to be on the other side of this one
+            let first: Vec<&ChunkType> = self
+                .chunks
+                .iter()
+                .filter(|c| c.before.contains(&name))
+                .map(|c| &c.chunk_type)
+                .collect();
+            let last: Vec<&ChunkType> = self
+                .chunks
+                .iter()
+                .filter(|c| c.after.contains(&name))
+                .map(|c| &c.chunk_type)
+                .collect();
+            match (info.multiplicity, own.len()) {
+                (Multiplicity::One, 0) => problems.push(format!("{} is missing", info.chunk_type)),
+                (Multiplicity::One | Multiplicity::Optional, n) if n > 1 => {
+                    problems.push(format!("{} appears {} times", info.chunk_type, n))
+                }
+                _ => {}
+            }
+            for other in &info.before {
+                let others = positions(other, &first);
+                if own
+                    .iter()
+                    .any(|&i| others.iter().any(|&j| j < i && !own.contains(&j)))
+                {
+                    problems.push(match other.as_str() {
+                        "*" => format!("{} must come first", info.chunk_type),
+                        _ => format!("{} must come before {}", info.chunk_type, other),
+                    });
+                }
+            }
+            for other in &info.after {
+                let others = positions(other, &last);
+                if own
+                    .iter()
+                    .any(|&i| others.iter().any(|&j| j > i && !own.contains(&j)))
+                {
+                    problems.push(match other.as_str() {
+                        "*" => format!("{} must come last", info.chunk_type),
+                        _ => format!("{} must come after {}", info.chunk_type, other),
+                    });
+                }
+            }
+        }
+        let data = positions("IDAT", &[]);
+        if data.windows(2).any(|w| w[1] != w[0] + 1) {
+            problems.push(String::from("IDAT chunks must be consecutive"));
+        }
+        for chunk_type in chunk_types {
+            if chunk_type.is_critical() && self.get(chunk_type).is_none() {
+                problems.push(format!("unknown critical chunk {}", chunk_type));
+            }
+        }
+        problems.dedup();
+        problems
+    }
+}
+
+fn chunk_info(table: &Table) -> Result<ChunkInfo, String> {
+    if let Some(key) = table.keys().find(|k| {
+        !matches!(
+            k.as_str(),
+            "type" | "name" | "section" | "multiplicity" | "before" | "after"
+        )
+    }) {
+        return Err(format!("unknown key {}", key));
+    }
+    let string = |key: &str| -> Result<Option<String>, String> {
+        match table.get(key) {
+            Some(Value::String(s)) => Ok(Some(s.clone())),
+            Some(_) => Err(format!("{} must be a string", key)),
+            None => Ok(None),
+        }
+    };
+    let types = |key: &str| -> Result<Vec<String>, String> {
+        let Some(value) = table.get(key) else {
+            return Ok(Vec::new());
+        };
+        let array = value
+            .as_array()
+            .ok_or_else(|| format!("{} must be an array", key))?;
+        array
+            .iter()
+            .map(|t| match t.as_str() {
+                Some("*") => Ok(String::from("*")),
+                Some(t) => ChunkType::from_str(t).map(|t| t.to_string()),
+                None => Err(format!("{} must only hold chunk types", key)),
+            })
+            .collect()
+    };
+    let chunk_type = string("type")?.ok_or_else(|| String::from("missing type"))?;
+    Ok(ChunkInfo {
+        chunk_type: ChunkType::from_str(&chunk_type)?,
+        name: string("name")?.ok_or_else(|| String::from("missing name"))?,
+        section: string("section")?,
+        multiplicity: string("multiplicity")?
+            .map_or(Ok(Multiplicity::Many), |m| Multiplicity::from_str(&m))?,
+        before: types("before")?,
+        after: types("after")?,
+        parser: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn types(types: &[&str]) -> Vec<ChunkType> {
+        types
+            .iter()
+            .map(|t| ChunkType::from_str(t).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_builtin() {
+        let registry = Registry::builtin();
+        let ihdr = registry.get(&ChunkType::from_str("IHDR").unwrap()).unwrap();
+        assert_eq!(ihdr.to_string(), "Image header (11.2.1)");
+        assert!(ihdr.is_registered());
+        let nine_patch = registry.get(&ChunkType::from_str("npTc").unwrap()).unwrap();
+        assert_eq!(nine_patch.to_string(), "Android nine-patch data");
+        assert!(!nine_patch.is_registered());
+        assert!(registry
+            .get(&ChunkType::from_str("ruSt").unwrap())
+            .is_none());
+        assert_eq!(
+            registry
+                .describe(
+                    &ChunkType::from_str("tIME").unwrap(),
+                    &[7, 233, 1, 2, 3, 4, 5]
+                )
+                .unwrap(),
+            "2025-01-02T03:04:05Z"
+        );
+    }
+
+    #[test]
+    fn test_is_registered() {
+        let registry = Registry::builtin();
+        assert!(registry.is_registered(&ChunkType::from_str("tEXt").unwrap()));
+        assert!(registry.is_registered(&ChunkType::from_str("IDAT").unwrap()));
+        assert!(!registry.is_registered(&ChunkType::from_str("npTc").unwrap()));
+        assert!(!registry.is_registered(&ChunkType::from_str("text").unwrap()));
+    }
+
+    #[test]
+    fn test_validate() {
+        let registry = Registry::builtin();
+        assert!(registry
+            .validate(&types(&[
+                "IHDR", "gAMA", "PLTE", "tRNS", "IDAT", "IDAT", "ruSt", "IEND"
+            ]))
+            .is_empty());
+        assert_eq!(
+            registry.validate(&types(&["IHDR", "PLTE", "gAMA", "IDAT", "IEND", "gAMA"])),
+            [
+                "IEND must come last",
+                "gAMA appears 2 times",
+                "gAMA must come before PLTE",
+                "gAMA must come before IDAT"
+            ]
+        );
+        assert_eq!(
+            registry.validate(&types(&["tRNS", "IHDR", "PLTE", "IDAT", "RuSt"])),
+            [
+                "IHDR must come first",
+                "IEND is missing",
+                "tRNS must come after PLTE",
+                "unknown critical chunk RuSt"
+            ]
+        );
+        // iOS files put CgBI before IHDR
+        assert!(registry
+            .validate(&types(&["CgBI", "IHDR", "IDAT", "IEND"]))
+            .is_empty());
+        assert_eq!(
+            registry.validate(&types(&["IHDR", "CgBI", "IDAT", "IEND"])),
+            ["CgBI must come before IHDR"]
+        );
+        assert_eq!(
+            registry.validate(&types(&["IHDR", "IDAT", "tEXt", "IDAT", "IEND"])),
+            ["IDAT chunks must be consecutive"]
+        );
+    }
+
+    #[test]
+    fn test_extend_from_toml() {
+        let mut registry = Registry::builtin();
+        registry
+            .extend_from_toml(
+                r#"
+                [[chunk]]
+                type = "buIl"
+                name = "Build information"
+                multiplicity = "optional"
+                before = ["IDAT"]
+
+                [[chunk]]
+                type = "npTc"
+                name = "Nine-patch"
+                section = "in-house 1.2"
+                "#,
+            )
+            .unwrap();
+        let build = registry.get(&ChunkType::from_str("buIl").unwrap()).unwrap();
+        assert_eq!(build.multiplicity, Multiplicity::Optional);
+        assert_eq!(build.before, ["IDAT"]);
+        assert_eq!(
+            registry
+                .get(&ChunkType::from_str("npTc").unwrap())
+                .unwrap()
+                .to_string(),
+            "Nine-patch (in-house 1.2)"
+        );
+        assert_eq!(
+            registry.chunks().len(),
+            Registry::builtin().chunks().len() + 1
+        );
+        assert_eq!(
+            registry.validate(&types(&["IHDR", "IDAT", "buIl", "IEND"])),
+            ["buIl must come before IDAT"]
+        );
+    }
+
+    #[test]
+    fn test_invalid_toml() {
+        let error = |text: &str| Registry::builtin().extend_from_toml(text).unwrap_err();
+        assert_eq!(error("[[chunk]]\ntype = \"buIl\""), "chunk 0: missing name");
+        assert_eq!(
+            error("[[chunk]]\ntype = \"bu1l\"\nname = \"x\""),
+            "chunk 0: invalid chunk type \"bu1l\", '1' is not an ASCII letter"
+        );
+        assert_eq!(
+            error("[[chunk]]\ntype = \"buIl\"\nname = \"x\"\nmultiplicity = \"two\""),
+            "chunk 0: invalid multiplicity, expected one, optional or many"
+        );
+        assert_eq!(
+            error("[[chunk]]\ntype = \"buIl\"\nname = \"x\"\ncolor = 1"),
+            "chunk 0: unknown key color"
+        );
+        assert_eq!(error("chunks = []"), "unknown key chunks");
+    }
+}
@@ -0,0 +1,268 @@
+use std::fmt::Display;
+
+use crate::{
+    chunk::{Chunk, ChunkRef},
+    chunk_type::ChunkType,
+    png::Png,
+};
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Problem {
+    // the chunk is kept with the crc it was read with
+    InvalidCrc { index: usize, chunk_type: ChunkType },
+    // the chunk was recovered from where the next chunk starts
+    InvalidLength { index: usize, chunk_type: ChunkType },
+    Unreadable { offset: usize, length: usize },
+    Truncated { offset: usize, length: usize },
+    MissingIend,
+}
+
+impl Problem {
+    // What `repair` does about the problem.
+    pub fn fix(&self) -> String {
+        match self {
+            Problem::InvalidCrc { index, chunk_type } => {
+                format!("Rewrote the crc of chunk {} ({})", index, chunk_type)
+            }
+            Problem::InvalidLength { index, chunk_type } => {
+                format!("Fixed the length of chunk {} ({})", index, chunk_type)
+            }
+            Problem::Unreadable { offset, length } => {
+                format!("Dropped {} unreadable bytes at offset {}", length, offset)
+            }
+            Problem::Truncated { offset, length } => format!(
+                "Dropped a truncated chunk of {} bytes at offset {}",
+                length, offset
+            ),
+            Problem::MissingIend => String::from("Appended the missing IEND chunk"),
+        }
+    }
+}
+
+impl Display for Problem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Problem::InvalidCrc { index, chunk_type } => {
+                write!(f, "chunk {} ({}) has an invalid crc", index, chunk_type)
+            }
+            Problem::InvalidLength { index, chunk_type } => {
+                write!(f, "chunk {} ({}) has an invalid length", index, chunk_type)
+            }
+            Problem::Unreadable { offset, length } => {
+                write!(f, "{} unreadable bytes at offset {}", length, offset)
+            }
+            Problem::Truncated { offset, length } => {
+                write!(
+                    f,
+                    "truncated chunk of {} bytes at offset {}",
+                    length, offset
+                )
+            }
+            Problem::MissingIend => write!(f, "missing IEND chunk"),
+        }
+    }
+}
+
+// Reads as much of a damaged PNG as possible. Chunks with a wrong crc are kept
+// as they are, and after a chunk whose length is wrong reading resumes at the
+// next chunk with a valid crc. Only a missing PNG signature is an error.
+pub fn parse_lenient(value: &[u8]) -> Result<(Png, Vec<Problem>), String> {
+    if value.len() < 8 || value[..8] != Png::STANDARD_HEADER {
+        return Err(String::from("invalid header"));
+    }
+    let mut chunks = Vec::new();
+    let mut problems = Vec::new();
+    let mut offset = 8;
+    while offset < value.len() {
+        if let Some(chunk) = chunk_at(value, offset) {
+            let end = offset + chunk.as_bytes().len();
+            // a wrong crc with a plausible length means the data is damaged,
+            // otherwise the length itself is suspect
+            if chunk.has_valid_crc() || end == value.len() || chunk_at(value, end).is_some() {
+                if !chunk.has_valid_crc() {
+                    problems.push(Problem::InvalidCrc {
+                        index: chunks.len(),
+                        chunk_type: chunk.chunk_type().clone(),
+                    });
+                }
+                chunks.push(chunk.to_chunk());
+                offset = end;
+                continue;
+            }
+        }
+        let next = ChunkRef::find(value, offset + 1).map(|(next, _)| next);
+        let end = next.unwrap_or(value.len());
+        match recover(&value[offset..end]) {
+            Some(chunk) => {
+                problems.push(Problem::InvalidLength {
+                    index: chunks.len(),
+                    chunk_type: chunk.chunk_type().clone(),
+                });
+                chunks.push(chunk);
+            }
+            None if next.is_some() => problems.push(Problem::Unreadable {
+                offset,
+                length: end - offset,
+            }),
+            None => problems.push(Problem::Truncated {
+                offset,
+                length: end - offset,
+            }),
+        }
+        offset = end;
+    }
+    if !chunks.iter().any(|c| c.chunk_type().bytes() == *b"IEND") {
+        problems.push(Problem::MissingIend);
+    }
+    Ok((Png::from_chunks(chunks), problems))
+}
+
+// Fixes the `problems` that `parse_lenient` found in `png`: crcs are
+// recomputed, unreadable bytes were already dropped and a missing IEND is
+// appended.
+pub fn repair(png: &Png, problems: &[Problem]) -> Png {
+    let mut chunks: Vec<Chunk> = png
+        .chunks()
+        .iter()
+        .map(|c| Chunk::new(c.chunk_type().clone(), c.data().to_vec()))
+        .collect();
+    if problems.contains(&Problem::MissingIend) {
+        chunks.push(Chunk::new(
+            ChunkType::try_from(*b"IEND").expect("valid chunk type"),
+            Vec::new(),
+        ));
+    }
+    Png::from_chunks(chunks)
+}
+
+fn chunk_at(value: &[u8], offset: usize) -> Option<ChunkRef<'_>> {
+    ChunkRef::parse_unchecked(value.get(offset..)?).ok()
+}
+
+// A chunk whose length field is wrong but whose type and crc are intact, when
+// `value` spans exactly from its start to the next chunk.
+fn recover(value: &[u8]) -> Option<Chunk> {
+    if value.len() < 12 {
+        return None;
+    }
+    let bytes = <[u8; 4]>::try_from(&value[4..8]).ok()?;
+    if !bytes.iter().all(u8::is_ascii_alphabetic) {
+        return None;
+    }
+    let chunk_type = ChunkType::try_from(bytes).expect("letters");
+    let chunk = Chunk::new(chunk_type, value[8..value.len() - 4].to_vec());
+    (chunk.crc().to_be_bytes() == value[value.len() - 4..]).then_some(chunk)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        png::ChunkPosition,
+        testing::{self, chunk},
+    };
+    use std::str::FromStr;
+
+    fn testing_png() -> Png {
+        let mut png = testing::testing_png(vec![chunk("ruSt", b"first message")]);
+        png.insert_chunk(ChunkPosition::End, chunk("ruSt", b"second message"))
+            .unwrap();
+        png
+    }
+
+    // offset of the chunk at `index` in the bytes of `testing_png`
+    fn offset(index: usize) -> usize {
+        8 + testing_png().chunks()[..index]
+            .iter()
+            .map(|c| c.as_bytes().len())
+            .sum::<usize>()
+    }
+
+    #[test]
+    fn test_intact_file() {
+        let bytes = testing_png().as_bytes();
+        let (png, problems) = parse_lenient(&bytes).unwrap();
+        assert!(problems.is_empty());
+        assert_eq!(png, testing_png());
+    }
+
+    #[test]
+    fn test_invalid_crc() {
+        let mut bytes = testing_png().as_bytes();
+        bytes[offset(1) + 8] ^= 1;
+        assert!(Png::try_from(&bytes[..]).is_err());
+
+        let (png, problems) = parse_lenient(&bytes).unwrap();
+        assert_eq!(
+            problems,
+            [Problem::InvalidCrc {
+                index: 1,
+                chunk_type: ChunkType::from_str("ruSt").unwrap()
+            }]
+        );
+        assert!(!png.chunks()[1].has_valid_crc());
+        assert_eq!(png.chunks()[3].data(), b"second message");
+
+        let png = repair(&png, &problems);
+        assert_eq!(png.chunks()[1].data(), b"girst message");
+        assert!(Png::try_from(&png.as_bytes()[..]).is_ok());
+    }
+
+    #[test]
+    fn test_invalid_length() {
+        let mut bytes = testing_png().as_bytes();
+        bytes[offset(1)] = 0x40;
+        let (png, problems) = parse_lenient(&bytes).unwrap();
+        let png = repair(&png, &problems);
+        assert_eq!(
+            problems,
+            [Problem::InvalidLength {
+                index: 1,
+                chunk_type: ChunkType::from_str("ruSt").unwrap()
+            }]
+        );
+        assert_eq!(png, testing_png());
+    }
+
+    #[test]
+    fn test_unreadable_bytes() {
+        let mut bytes = testing_png().as_bytes();
+        // garbage in place of the chunk type
+        bytes[offset(2) + 4..offset(2) + 8].copy_from_slice(&[0, 1, 2, 3]);
+        let (png, problems) = parse_lenient(&bytes).unwrap();
+        let png = repair(&png, &problems);
+        assert_eq!(
+            problems,
+            [Problem::Unreadable {
+                offset: offset(2),
+                length: offset(3) - offset(2)
+            }]
+        );
+        let types: Vec<String> = png
+            .chunks()
+            .iter()
+            .map(|c| c.chunk_type().to_string())
+            .collect();
+        assert_eq!(types, ["IHDR", "ruSt", "ruSt", "IEND"]);
+    }
+
+    #[test]
+    fn test_truncated_file() {
+        let bytes = testing_png().as_bytes();
+        let (png, problems) = parse_lenient(&bytes[..offset(3) + 10]).unwrap();
+        let png = repair(&png, &problems);
+        assert_eq!(
+            problems,
+            [
+                Problem::Truncated {
+                    offset: offset(3),
+                    length: 10
+                },
+                Problem::MissingIend
+            ]
+        );
+        assert_eq!(png.chunks().len(), 4);
+        assert_eq!(png.chunks()[3].chunk_type().to_string(), "IEND");
+        assert!(Png::try_from(&png.as_bytes()[..]).is_ok());
+    }
+}
@@ -0,0 +1,220 @@
+// Serde support, behind the `serde` feature. Human readable formats get chunk
+// types as strings and data as base64, binary formats get raw bytes and a PNG
+// is stored as the file itself.
+
+use std::{borrow::Cow, fmt, str::FromStr};
+
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use serde::{
+    de::{self, SeqAccess, Visitor},
+    Deserialize, Deserializer, Serialize, Serializer,
+};
+
+use crate::{chunk::Chunk, chunk_type::ChunkType, png::Png};
+
+impl Serialize for ChunkType {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for ChunkType {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = Cow::<str>::deserialize(deserializer)?;
+        ChunkType::from_str(&s).map_err(de::Error::custom)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "Chunk")]
+struct RawChunk<'a> {
+    #[serde(rename = "type")]
+    chunk_type: ChunkType,
+    #[serde(with = "data")]
+    data: Cow<'a, [u8]>,
+    // only checked when present, so hand written chunks can leave it out
+    #[serde(default)]
+    crc: Option<u32>,
+}
+
+impl Serialize for Chunk {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        RawChunk {
+            chunk_type: self.chunk_type().clone(),
+            data: Cow::Borrowed(self.data()),
+            crc: Some(self.crc()),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Chunk {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let raw = RawChunk::deserialize(deserializer)?;
+        let chunk = Chunk::new(raw.chunk_type, raw.data.into_owned());
+        match raw.crc {
+            Some(crc) if crc != chunk.crc() => Err(de::Error::custom(format!(
+                "invalid crc for chunk {}, expected {}",
+                chunk.chunk_type(),
+                chunk.crc()
+            ))),
+            _ => Ok(chunk),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "Png")]
+struct RawPng<'a> {
+    chunks: Cow<'a, [Chunk]>,
+}
+
+impl Serialize for Png {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            RawPng {
+                chunks: Cow::Borrowed(self.chunks()),
+            }
+            .serialize(serializer)
+        } else {
+            serializer.serialize_bytes(&self.as_bytes())
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Png {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        if deserializer.is_human_readable() {
+            let raw = RawPng::deserialize(deserializer)?;
+            Ok(Png::from_chunks(raw.chunks.into_owned()))
+        } else {
+            let bytes = deserializer.deserialize_byte_buf(BytesVisitor)?;
+            Png::try_from(&bytes[..]).map_err(de::Error::custom)
+        }
+    }
+}
+
+mod data {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(data: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            serializer.serialize_str(&BASE64.encode(data))
+        } else {
+            serializer.serialize_bytes(data)
+        }
+    }
+
+    pub fn deserialize<'de, 'a, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Cow<'a, [u8]>, D::Error> {
+        if deserializer.is_human_readable() {
+            let s = Cow::<str>::deserialize(deserializer)?;
+            let data = BASE64.decode(s.as_bytes()).map_err(de::Error::custom)?;
+            Ok(Cow::Owned(data))
+        } else {
+            Ok(Cow::Owned(deserializer.deserialize_byte_buf(BytesVisitor)?))
+        }
+    }
+}
+
+// Accepts bytes as well as a sequence of u8, since binary formats differ in
+// how they hand back what `serialize_bytes` wrote.
+struct BytesVisitor;
+
+impl<'de> Visitor<'de> for BytesVisitor {
+    type Value = Vec<u8>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("bytes")
+    }
+
+    fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<Self::Value, E> {
+        Ok(v.to_vec())
+    }
+
+    fn visit_byte_buf<E: de::Error>(self, v: Vec<u8>) -> Result<Self::Value, E> {
+        Ok(v)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let mut bytes = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+        while let Some(byte) = seq.next_element()? {
+            bytes.push(byte);
+        }
+        Ok(bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::png::ChunkPosition;
+    use serde_test::{assert_de_tokens_error, assert_tokens, Configure, Token};
+
+    fn testing_chunk() -> Chunk {
+        Chunk::new(ChunkType::from_str("ruSt").unwrap(), b"hi".to_vec())
+    }
+
+    #[test]
+    fn test_chunk_type_tokens() {
+        let chunk_type = ChunkType::from_str("RuSt").unwrap();
+        assert_tokens(&chunk_type, &[Token::Str("RuSt")]);
+        assert_de_tokens_error::<ChunkType>(
+            &[Token::Str("Ru1t")],
+            "invalid chunk type \"Ru1t\", '1' is not an ASCII letter",
+        );
+    }
+
+    #[test]
+    fn test_chunk_tokens() {
+        let crc = testing_chunk().crc();
+        let tokens = |data: Token| {
+            [
+                Token::Struct {
+                    name: "Chunk",
+                    len: 3,
+                },
+                Token::Str("type"),
+                Token::Str("ruSt"),
+                Token::Str("data"),
+                data,
+                Token::Str("crc"),
+                Token::Some,
+                Token::U32(crc),
+                Token::StructEnd,
+            ]
+        };
+        assert_tokens(&testing_chunk().readable(), &tokens(Token::Str("aGk=")));
+        assert_tokens(&testing_chunk().compact(), &tokens(Token::Bytes(b"hi")));
+    }
+
+    #[test]
+    fn test_chunk_crc_is_checked() {
+        let json = r#"{"type": "ruSt", "data": "aGk=", "crc": 1}"#;
+        let error = serde_json::from_str::<Chunk>(json).unwrap_err();
+        assert!(error.to_string().starts_with("invalid crc for chunk ruSt"));
+
+        let json = r#"{"type": "ruSt", "data": "aGk="}"#;
+        assert_eq!(
+            serde_json::from_str::<Chunk>(json).unwrap(),
+            testing_chunk()
+        );
+    }
+
+    #[test]
+    fn test_png_round_trip() {
+        // just the signature and IEND
+        const BYTES: &[u8] = &[
+            137, 80, 78, 71, 13, 10, 26, 10, 0, 0, 0, 0, 73, 69, 78, 68, 174, 66, 96, 130,
+        ];
+        let mut png = Png::try_from(BYTES).unwrap();
+        assert_tokens(&png.clone().compact(), &[Token::Bytes(BYTES)]);
+
+        png.insert_chunk(ChunkPosition::Start, testing_chunk())
+            .unwrap();
+        let json = serde_json::to_string(&png).unwrap();
+        assert!(json.starts_with(r#"{"chunks":[{"type":"ruSt","data":"aGk=","crc":"#));
+        assert_eq!(serde_json::from_str::<Png>(&json).unwrap(), png);
+    }
+}
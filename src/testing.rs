@@ -0,0 +1,12 @@
+use crate::{chunk::Chunk, png::Png};
+
+pub use crate::util::chunk;
+
+// A 1x1 greyscale image with `extra` between its header and its data.
+pub fn testing_png(extra: Vec<Chunk>) -> Png {
+    let mut chunks = vec![chunk("IHDR", [0, 0, 0, 1, 0, 0, 0, 1, 8, 0, 0, 0, 0])];
+    chunks.extend(extra);
+    chunks.push(chunk("IDAT", [0x78, 0x9c, 0x63, 0x60, 0, 0, 0, 2, 0, 1]));
+    chunks.push(chunk("IEND", []));
+    Png::from_chunks(chunks)
+}
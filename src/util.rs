@@ -0,0 +1,19 @@
+use std::str::FromStr;
+
+use crate::{chunk::Chunk, chunk_type::ChunkType};
+
+// Builds a chunk of a type known to be valid.
+pub fn chunk(chunk_type: &str, data: impl Into<Vec<u8>>) -> Chunk {
+    Chunk::new(
+        ChunkType::from_str(chunk_type).expect("valid chunk type"),
+        data.into(),
+    )
+}
+
+pub fn read_u16(value: &[u8], offset: usize) -> u16 {
+    u16::from_be_bytes([value[offset], value[offset + 1]])
+}
+
+pub fn read_u32(value: &[u8], offset: usize) -> u32 {
+    u32::from_be_bytes(core::array::from_fn(|i| value[offset + i]))
+}